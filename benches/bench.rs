@@ -1,5 +1,6 @@
 use criterion::{criterion_group, criterion_main, Criterion};
-use soy::Tofu;
+use soy::{SoyValue, Tofu};
+use std::collections::HashMap;
 
 fn simple_benchmark(c: &mut Criterion) {
     let tofu = Tofu::with_string_template(HELLO_WORLD).unwrap();
@@ -12,7 +13,29 @@ fn simple_benchmark(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, simple_benchmark);
+fn product_page_benchmark(c: &mut Criterion) {
+    let tofu = Tofu::with_string_template(PRODUCT_PAGE).unwrap();
+    let mut product = HashMap::new();
+    product.insert("name".to_owned(), SoyValue::from("Widget <Deluxe>"));
+    product.insert("price".to_owned(), SoyValue::from(19.99));
+    product.insert("stock".to_owned(), SoyValue::from(3));
+    product.insert(
+        "tags".to_owned(),
+        SoyValue::from(vec!["new", "sale", "popular"]),
+    );
+    let product = SoyValue::from(product);
+    c.bench_function("product page", move |b| {
+        b.iter(|| {
+            tofu.renderer("benches.product")
+                .param("product", product.clone())
+                .param("user", "Alex")
+                .param("cartSize", 2)
+                .render(&mut std::io::sink())
+        })
+    });
+}
+
+criterion_group!(benches, simple_benchmark, product_page_benchmark);
 criterion_main!(benches);
 
 static HELLO_WORLD: &str = "
@@ -25,3 +48,54 @@ static HELLO_WORLD: &str = "
 Hello world
 {/template}
 ";
+
+static PRODUCT_PAGE: &str = "
+{namespace benches}
+
+/**
+ * A product page.
+ * @param product
+ * @param user
+ * @param cartSize
+ */
+{template .product}
+<!doctype html>
+<html>
+  <head>
+    <title>{$product.name} - Shop</title>
+  </head>
+  <body class=\"{css page} {css product-page}\">
+    <header>
+      {msg desc=\"Greeting\"}Welcome back, {$user}!{/msg}
+      <a href=\"/cart\">
+        {msg desc=\"Cart size\"}
+          {plural $cartSize}{case 0}Your cart is empty{case 1}1 item{default}{$cartSize} items{/plural}
+        {/msg}
+      </a>
+    </header>
+    <main id=\"{xid main}\">
+      <h1>{$product.name}</h1>
+      <p class=\"price\">${round($product.price * 100) / 100}</p>
+      {if $product.stock > 10}
+        <p class=\"stock\">In stock</p>
+      {elseif $product.stock > 0}
+        <p class=\"stock low\">Only {$product.stock} left</p>
+      {else}
+        <p class=\"stock none\">Out of stock</p>
+      {/if}
+      <ul class=\"tags\">
+        <li>{$product.tags[0]}</li>
+        <li>{$product.tags[1]}</li>
+        <li>{$product.tags[2]}</li>
+        <li>{$product.tags?[3] ?: 'none'}</li>
+      </ul>
+      <p>{$product.name|truncate:8}</p>
+      <p>{['a': 1, 'b': 2]} {[1, 2, 3]}</p>
+    </main>
+    <footer>
+      {msg desc=\"Copyright\"}&copy; Example Shop. All rights reserved.{/msg}
+    </footer>
+  </body>
+</html>
+{/template}
+";
@@ -0,0 +1,5 @@
+fn main() {
+    if let Err(e) = soy::build::compile_dir("templates") {
+        panic!("{}", e);
+    }
+}
@@ -0,0 +1,3 @@
+//! The templates in `templates`, compiled to Rust by the build script.
+
+include!(concat!(env!("OUT_DIR"), "/soy_templates.rs"));
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::fs;
+
+use soy::error::RenderError;
+use soy::{SoyValue, Tofu};
+use soy_codegen_tests::{common, pages};
+
+/// The same templates as the build script compiles, for the interpreter.
+fn tofu() -> Tofu {
+    let mut builder = Tofu::builder();
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/templates");
+    for entry in fs::read_dir(dir).unwrap() {
+        builder = builder.add_file(entry.unwrap().path());
+    }
+    builder
+        .build()
+        .unwrap_or_else(|e| panic!("should compile: {}", e))
+}
+
+fn render<F>(f: F) -> String
+where
+    F: FnOnce(&mut Vec<u8>) -> Result<(), RenderError>,
+{
+    let mut output = vec![];
+    f(&mut output).unwrap_or_else(|e| panic!("should render: {}", e));
+    String::from_utf8(output).unwrap()
+}
+
+fn map(entries: &[(&str, SoyValue)]) -> SoyValue {
+    let entries: HashMap<_, _> = entries
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.clone()))
+        .collect();
+    entries.into()
+}
+
+#[test]
+fn test_generated_templates_render_as_the_interpreter_does() {
+    let tofu = tofu();
+    let items = SoyValue::from(vec!["<a>", "b"]);
+    for count in [0, 1, 3] {
+        let params = pages::HomeParams {
+            name: "<Ann>".into(),
+            items: items.clone(),
+            count: Some(count.into()),
+        };
+        let expected = tofu
+            .renderer("pages.home")
+            .param("name", "<Ann>")
+            .param("items", items.clone())
+            .param("count", count)
+            .render_to_string()
+            .unwrap();
+        assert_eq!(render(|w| pages::home(w, &params)), expected);
+    }
+
+    let user = map(&[("name", "<Bob>".into())]);
+    let params = pages::CallsParams { user: user.clone() };
+    let expected = tofu
+        .renderer("pages.calls")
+        .param("user", user)
+        .render_to_string()
+        .unwrap();
+    assert_eq!(render(|w| pages::calls(w, &params)), expected);
+
+    let params = pages::SearchParams {
+        query: "a b&c".into(),
+    };
+    let expected = tofu
+        .renderer("pages.search")
+        .param("query", "a b&c")
+        .render_to_string()
+        .unwrap();
+    assert_eq!(render(|w| pages::search(w, &params)), expected);
+
+    for (x, y) in [(7, 2), (0, 3)] {
+        let params = pages::ExpressionsParams {
+            x: x.into(),
+            y: y.into(),
+        };
+        let expected = tofu
+            .renderer("pages.expressions")
+            .param("x", x)
+            .param("y", y)
+            .render_to_string()
+            .unwrap();
+        assert_eq!(render(|w| pages::expressions(w, &params)), expected);
+    }
+}
+
+#[test]
+fn test_calls_to_templates_of_another_kind_are_escaped() {
+    let params = common::widgets::LabelParams {
+        label: "<Bob>".into(),
+    };
+    assert_eq!(render(|w| common::widgets::label(w, &params)), "[<Bob>]");
+    let user = map(&[("name", "<Bob>".into())]);
+    let output = render(|w| pages::calls(w, &pages::CallsParams { user }));
+    assert!(
+        output.ends_with(" [&lt;Bob&gt;] <b>&lt;bold&gt;</b>"),
+        "{}",
+        output
+    );
+    let params = pages::SearchParams {
+        query: "a b&c".into(),
+    };
+    assert_eq!(
+        render(|w| pages::search(w, &params)),
+        "/search?q=%5Ba%20b%26c%5D"
+    );
+}
@@ -12,7 +12,7 @@ static TEMPLATE: &str = "
 {/template}
 ";
 
-fn main() -> Result<(), Box<Error>> {
+fn main() -> Result<(), Box<dyn Error>> {
     let tofu = Tofu::with_string_template(TEMPLATE)?;
     println!("{}", tofu.render_to_string("example.helloWorld")?);
     Ok(())
@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+
+use crate::error::TemplateLocation;
+
+#[derive(Debug, PartialEq)]
+pub struct SoyFile {
+    pub namespace: Namespace,
+    pub aliases: Vec<Alias>,
+    pub delpackage: Option<String>,
+    pub templates: Vec<Template>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Namespace {
+    pub name: String,
+    pub attributes: NamespaceAttributes,
+}
+
+/// The attributes given in a `{namespace}` tag, which provide defaults for the
+/// templates in the file.
+#[derive(Debug, PartialEq, Default)]
+pub struct NamespaceAttributes {
+    /// Ignored, as for templates.
+    pub autoescape: Option<AutoescapeMode>,
+    pub require_css: Vec<String>,
+    pub css_base: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Alias {
+    pub from: String,
+    pub to: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Template {
+    /// The name relative to the file's namespace, e.g. `bar` for `{template .bar}`.
+    pub name: String,
+    pub attributes: TemplateAttributes,
+    pub body: TemplateBlock,
+    pub soydoc_params: Vec<SoydocParam>,
+    /// Where the template is defined, i.e. its `{template}` tag.
+    pub location: Location,
+}
+
+/// A location in a soy file, for errors about what is there. Locations are all equal to
+/// each other, so that ASTs can be compared regardless of where they were parsed from.
+#[derive(Debug, Default, Clone)]
+pub struct Location(pub TemplateLocation);
+
+impl PartialEq for Location {
+    fn eq(&self, _: &Location) -> bool {
+        true
+    }
+}
+
+/// The attributes given in a `{template}` tag.
+#[derive(Debug, PartialEq)]
+pub struct TemplateAttributes {
+    pub visibility: Visibility,
+    /// The kind of content the template produces, which determines how it is escaped.
+    pub kind: ContentKind,
+    /// `None` if the template doesn't specify a mode. It is parsed so that templates using
+    /// the deprecated modes are accepted, but ignored on purpose: every template is escaped
+    /// as a strict one, according to its kind, since the other modes turn off escaping.
+    pub autoescape: Option<AutoescapeMode>,
+    /// The CSS namespaces the template requires, for use by asset tooling.
+    pub require_css: Vec<String>,
+    /// The prefix for `{css %name}` commands.
+    pub css_base: Option<String>,
+    pub strict_html: bool,
+}
+
+impl Default for TemplateAttributes {
+    fn default() -> Self {
+        TemplateAttributes {
+            visibility: Visibility::Public,
+            kind: ContentKind::Html,
+            autoescape: None,
+            require_css: vec![],
+            css_base: None,
+            strict_html: true,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Visibility {
+    Public,
+    /// Can only be called from templates in the same file, and can't be rendered directly.
+    Private,
+}
+
+/// The kind of content produced by a template or held by a sanitized value.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ContentKind {
+    Html,
+    Text,
+    Attributes,
+    Uri,
+    TrustedResourceUri,
+    Css,
+    Js,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AutoescapeMode {
+    Strict,
+    Contextual,
+    NonContextual,
+}
+
+pub type TemplateBlock = Vec<TemplateNode>;
+
+#[derive(Debug, PartialEq)]
+pub enum TemplateNode {
+    RawText(String),
+    Statement(Command),
+    Special(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SoydocParam {
+    pub name: String,
+    pub required: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    If {
+        if_block: ConditionalBlock,
+        else_ifs: Vec<ConditionalBlock>,
+        else_block: Option<TemplateBlock>,
+    },
+    Msg {
+        /// The id that translations of the message are found by. It is computed from the
+        /// message's content and meaning (but not its description).
+        id: u64,
+        desc: Option<String>,
+        meaning: Option<String>,
+        body: MsgBody,
+    },
+    Print {
+        expression: Expression,
+        directives: Vec<PrintDirective>,
+        /// The placeholder name to use for the print inside a `{msg}`.
+        phname: Option<String>,
+    },
+    Literal(String),
+    /// `{css name}` or `{css $base, name}`.
+    Css {
+        base: Option<Expression>,
+        name: String,
+    },
+    Xid(String),
+    Call(Call),
+}
+
+/// `{call .foo data="all"}{param bar: 1 /}{/call}`
+#[derive(Debug, PartialEq)]
+pub struct Call {
+    /// The fully-qualified name of the called template, as resolved against the file's
+    /// namespace and aliases.
+    pub target: String,
+    /// The data passed to the template, which the params are added to.
+    pub data: Option<CallData>,
+    pub params: Vec<CallParam>,
+    /// The placeholder name to use for the call inside a `{msg}`.
+    pub phname: Option<String>,
+    /// Where the call is, i.e. its `{call}` tag.
+    pub location: Location,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CallData {
+    /// `data="all"`, which passes all of the caller's parameters.
+    All,
+    /// `data="$foo"`, which passes the entries of a map.
+    Expression(Expression),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CallParam {
+    pub name: String,
+    pub value: CallParamValue,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CallParamValue {
+    /// `{param foo: $bar /}`
+    Expression(Expression),
+    /// `{param foo kind="html"}...{/param}`, which is passed as sanitized content of its
+    /// kind (HTML if it isn't given).
+    Block {
+        kind: ContentKind,
+        block: TemplateBlock,
+    },
+}
+
+/// The calls in a block, including those nested in other commands (e.g. in the
+/// branches of an `{if}` or the params of another call).
+pub fn calls(block: &[TemplateNode]) -> Vec<&Call> {
+    let mut calls = vec![];
+    collect_calls(block, &mut calls);
+    calls
+}
+
+fn collect_calls<'a>(block: &'a [TemplateNode], calls: &mut Vec<&'a Call>) {
+    for node in block {
+        match node {
+            TemplateNode::Statement(Command::If {
+                if_block,
+                else_ifs,
+                else_block,
+            }) => {
+                for conditional in std::iter::once(if_block).chain(else_ifs) {
+                    collect_calls(&conditional.block, calls);
+                }
+                if let Some(block) = else_block {
+                    collect_calls(block, calls);
+                }
+            }
+            TemplateNode::Statement(Command::Msg { body, .. }) => {
+                for block in body.blocks() {
+                    collect_calls(block, calls);
+                }
+            }
+            TemplateNode::Statement(Command::Call(call)) => {
+                calls.push(call);
+                for param in &call.params {
+                    if let CallParamValue::Block { block, .. } = &param.value {
+                        collect_calls(block, calls);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Applies a function to each of the calls in a block, e.g. to resolve their targets.
+pub fn for_each_call_mut<F: FnMut(&mut Call)>(block: &mut [TemplateNode], f: &mut F) {
+    for node in block {
+        match node {
+            TemplateNode::Statement(Command::If {
+                if_block,
+                else_ifs,
+                else_block,
+            }) => {
+                for conditional in std::iter::once(if_block).chain(else_ifs) {
+                    for_each_call_mut(&mut conditional.block, f);
+                }
+                if let Some(block) = else_block {
+                    for_each_call_mut(block, f);
+                }
+            }
+            TemplateNode::Statement(Command::Msg { body, .. }) => {
+                for block in body.blocks_mut() {
+                    for_each_call_mut(block, f);
+                }
+            }
+            TemplateNode::Statement(Command::Call(call)) => {
+                f(call);
+                for param in &mut call.params {
+                    if let CallParamValue::Block { block, .. } = &mut param.value {
+                        for_each_call_mut(block, f);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ConditionalBlock {
+    pub expression: Expression,
+    pub block: TemplateBlock,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MsgBody {
+    Plural {
+        expression: Expression,
+        /// Subtracted from the value before its plural category is found (but not before
+        /// it is compared to explicit cases), as in "You and {remainder($n)} others".
+        offset: i64,
+        cases: Vec<PluralCase>,
+        default: Box<MsgBody>,
+    },
+    /// `{select $gender}{case 'female'}...{default}...{/select}`
+    Select {
+        expression: Expression,
+        cases: Vec<SelectCase>,
+        default: Box<MsgBody>,
+    },
+    Block(TemplateBlock),
+}
+
+impl MsgBody {
+    /// The blocks of text and commands in the message, in each of its cases.
+    pub fn blocks(&self) -> Vec<&TemplateBlock> {
+        match self {
+            MsgBody::Block(block) => vec![block],
+            MsgBody::Plural { cases, default, .. } => cases
+                .iter()
+                .flat_map(|case| case.body.blocks())
+                .chain(default.blocks())
+                .collect(),
+            MsgBody::Select { cases, default, .. } => cases
+                .iter()
+                .flat_map(|case| case.body.blocks())
+                .chain(default.blocks())
+                .collect(),
+        }
+    }
+
+    fn blocks_mut(&mut self) -> Vec<&mut TemplateBlock> {
+        match self {
+            MsgBody::Block(block) => vec![block],
+            MsgBody::Plural { cases, default, .. } => cases
+                .iter_mut()
+                .flat_map(|case| case.body.blocks_mut())
+                .chain(default.blocks_mut())
+                .collect(),
+            MsgBody::Select { cases, default, .. } => cases
+                .iter_mut()
+                .flat_map(|case| case.body.blocks_mut())
+                .chain(default.blocks_mut())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PluralCase {
+    pub expression: Expression,
+    pub body: MsgBody,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SelectCase {
+    pub value: String,
+    pub body: MsgBody,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PrintDirective {
+    pub name: String,
+    pub arguments: Vec<Expression>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Expression {
+    Null,
+    Boolean(bool),
+    Float(f64),
+    Integer(i64),
+    String(String),
+    List(Vec<Expression>),
+    Map(HashMap<String, Expression>),
+    Function {
+        name: String,
+        parameters: Vec<Expression>,
+    },
+    DataReference {
+        referent: Referent,
+        references: Vec<Reference>,
+    },
+    GlobalReference(String),
+    BinaryOperation {
+        lhs: Box<Expression>,
+        op: BinaryOperator,
+        rhs: Box<Expression>,
+    },
+    UnaryOperation {
+        op: UnaryOperator,
+        rhs: Box<Expression>,
+    },
+    TernaryOperation {
+        condition: Box<Expression>,
+        if_true: Box<Expression>,
+        if_false: Box<Expression>,
+    },
+}
+
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Copy)]
+pub enum BinaryOperator {
+    Plus,
+    Minus,
+    Times,
+    Divide,
+    Modulo,
+    Less,
+    LessEquals,
+    Greater,
+    GreaterEquals,
+    Equals,
+    NotEquals,
+    And,
+    Or,
+    Elvis,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum UnaryOperator {
+    Minus,
+    Not,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Referent {
+    Variable(String),
+    Injected(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Reference {
+    Dotted(ReferenceKey),
+    QuestionDotted(ReferenceKey),
+    Bracketed(Expression),
+    QuestionBracketed(Expression),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReferenceKey {
+    Number(usize),
+    Name(String),
+}
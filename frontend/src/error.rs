@@ -0,0 +1,374 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::string::FromUtf8Error;
+
+#[derive(Debug, Default, Clone)]
+pub struct TemplateLocation {
+    pub filename: Option<String>,
+    pub template_name: Option<String>,
+    pub line_number: usize,
+    pub column: usize,
+    /// The number of characters, starting at `column`, to underline in the snippet.
+    pub length: usize,
+    /// The source line containing the location.
+    pub snippet: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct RenderError {
+    pub kind: RenderErrorKind,
+    pub location: Option<Box<TemplateLocation>>,
+}
+
+#[derive(Debug)]
+pub enum RenderErrorKind {
+    IoError(io::Error),
+    Utf8Error(FromUtf8Error),
+    TemplateNotFound(String),
+    PrivateTemplate(String),
+    UnknownFunction(String),
+    UnknownDirective(String),
+    UnknownGlobal(String),
+    TypeError(String),
+    MissingCssBase(String),
+    UnknownPlaceholder(String),
+    // TODO: more error kinds
+}
+
+#[derive(Debug)]
+pub struct CompileError {
+    pub kind: CompileErrorKind,
+    pub location: Option<Box<TemplateLocation>>,
+    pub cause: Option<Box<dyn std::error::Error + Send + Sync>>,
+}
+
+/// The kinds of problems the compiler reports. Each kind has a stable code (see
+/// `CompileErrorKind::code`), so new kinds must be given new codes rather than
+/// reusing old ones.
+#[derive(Debug)]
+pub enum CompileErrorKind {
+    Parse(String),
+    UndeclaredParameter(String),
+    /// The template's name, and where it was first defined.
+    DuplicateTemplate(String, Box<TemplateLocation>),
+    Io(String, io::Error),
+    UnusedParameter(String),
+    Malformed(String),
+    Unsupported(String),
+    InvalidNumber(String),
+    TooDeeplyNested(usize),
+    InvalidCharacter(String),
+    UnknownAttribute(String),
+    InvalidAttributeValue(String, String),
+    WrongNamespace(String, String),
+    MissingCssBase(String),
+    UnknownFunction(String),
+    UnknownDirective(String),
+    UnknownTemplate(String),
+    CallToPrivateTemplate(String),
+    // TODO: more error kinds
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// All of the errors and warnings reported while compiling a set of files.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    diagnostics: Vec<CompileError>,
+}
+
+/// The error returned when a template set fails to compile. Contains every error
+/// (and warning) found across all of the files, not just the first.
+#[derive(Debug)]
+pub struct CompileErrors {
+    pub diagnostics: Diagnostics,
+}
+
+impl Error for RenderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+impl Error for CompileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+impl Error for CompileErrors {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+impl CompileErrorKind {
+    /// A stable identifier for this kind of error, e.g. `E0001`.
+    pub fn code(&self) -> &'static str {
+        use self::CompileErrorKind::*;
+        match self {
+            Parse(_) => "E0001",
+            UndeclaredParameter(_) => "E0002",
+            DuplicateTemplate(..) => "E0003",
+            Io(..) => "E0004",
+            Malformed(_) => "E0005",
+            Unsupported(_) => "E0006",
+            InvalidNumber(_) => "E0007",
+            TooDeeplyNested(_) => "E0008",
+            InvalidCharacter(_) => "E0009",
+            UnknownAttribute(_) => "E0010",
+            InvalidAttributeValue(..) => "E0011",
+            WrongNamespace(..) => "E0012",
+            MissingCssBase(_) => "E0013",
+            UnknownFunction(_) => "E0014",
+            UnknownDirective(_) => "E0015",
+            UnknownTemplate(_) => "E0016",
+            CallToPrivateTemplate(_) => "E0017",
+            UnusedParameter(_) => "W0001",
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        use self::CompileErrorKind::*;
+        match self {
+            UnusedParameter(_) => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl CompileError {
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+
+    /// Records the file the error occurred in, if it isn't already known.
+    pub fn with_filename(mut self, filename: Option<&str>) -> Self {
+        if let (Some(location), Some(filename)) = (&mut self.location, filename) {
+            location.filename.get_or_insert_with(|| filename.to_owned());
+        }
+        self
+    }
+}
+
+impl Diagnostics {
+    pub fn push(&mut self, diagnostic: CompileError) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &CompileError> {
+        self.iter().filter(|d| d.severity() == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &CompileError> {
+        self.iter().filter(|d| d.severity() == Severity::Warning)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CompileError> {
+        self.diagnostics.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.diagnostics.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+}
+
+impl Extend<CompileError> for Diagnostics {
+    fn extend<I: IntoIterator<Item = CompileError>>(&mut self, iter: I) {
+        self.diagnostics.extend(iter);
+    }
+}
+
+impl IntoIterator for Diagnostics {
+    type Item = CompileError;
+    type IntoIter = std::vec::IntoIter<CompileError>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.diagnostics.into_iter()
+    }
+}
+
+impl From<io::Error> for RenderError {
+    fn from(from: io::Error) -> Self {
+        RenderError {
+            kind: RenderErrorKind::IoError(from),
+            location: None,
+        }
+    }
+}
+
+impl From<FromUtf8Error> for RenderError {
+    fn from(from: FromUtf8Error) -> Self {
+        RenderError {
+            kind: RenderErrorKind::Utf8Error(from),
+            location: None,
+        }
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::RenderErrorKind::*;
+        write!(f, "error: ")?;
+        match &self.kind {
+            TemplateNotFound(t) => write!(f, "Template not found: {}", t)?,
+            PrivateTemplate(t) => write!(f, "Template is private: {}", t)?,
+            UnknownFunction(name) => write!(f, "Unknown function: {}", name)?,
+            UnknownDirective(name) => write!(f, "Unknown print directive: |{}", name)?,
+            UnknownGlobal(name) => write!(f, "Unknown global: {}", name)?,
+            TypeError(message) => write!(f, "Type error: {}", message)?,
+            UnknownPlaceholder(name) => {
+                write!(f, "Translation has an unknown placeholder: {}", name)?
+            }
+            MissingCssBase(t) => write!(f, "Template {} uses {{css %...}} without a cssbase", t)?,
+            IoError(e) => write!(f, "IO Error: {}", e)?,
+            Utf8Error(e) => write!(f, "UTF8 Encoding Error: {}", e)?,
+        }
+        if let Some(location) = &self.location {
+            write!(f, "\n{}", location)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}]: {}",
+            self.severity(),
+            self.kind.code(),
+            self.kind
+        )?;
+        if let Some(location) = &self.location {
+            write!(f, "\n{}", location)?;
+        }
+        if let CompileErrorKind::DuplicateTemplate(_, first) = &self.kind {
+            write!(f, "\nnote: first defined here\n{}", first)?;
+        }
+        Ok(())
+    }
+}
+
+/// The message for the error, without its code or location.
+impl fmt::Display for CompileErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::CompileErrorKind::*;
+        match self {
+            Parse(message) => write!(f, "{}", message),
+            UndeclaredParameter(param) => write!(f, "Usage of undeclared parameter: {}", param),
+            DuplicateTemplate(name, _) => write!(f, "Template defined more than once: {}", name),
+            Io(path, e) => write!(f, "Unable to read {}: {}", path, e),
+            UnusedParameter(param) => write!(f, "Parameter is never used: {}", param),
+            Malformed(message) => write!(f, "Malformed input: {}", message),
+            Unsupported(feature) => write!(f, "Unsupported: {}", feature),
+            InvalidNumber(number) => write!(f, "Number out of range: {}", number),
+            TooDeeplyNested(max) => write!(f, "Nested more than {} levels deep", max),
+            InvalidCharacter(hex) => write!(f, "Not a valid unicode character: U+{}", hex),
+            UnknownAttribute(name) => write!(f, "Unknown attribute: {}", name),
+            InvalidAttributeValue(name, value) => {
+                write!(f, "Invalid value for attribute {}: {}", name, value)
+            }
+            WrongNamespace(template, namespace) => write!(
+                f,
+                "Template {} is not in the file's namespace ({})",
+                template, namespace
+            ),
+            MissingCssBase(name) => write!(
+                f,
+                "{{css {}}} needs a cssbase on its template or namespace",
+                name
+            ),
+            UnknownFunction(name) => write!(f, "Unknown function: {}", name),
+            UnknownDirective(name) => write!(f, "Unknown print directive: |{}", name),
+            UnknownTemplate(name) => write!(f, "Call to unknown template: {}", name),
+            CallToPrivateTemplate(name) => {
+                write!(f, "Call to private template {} from another file", name)
+            }
+        }
+    }
+}
+
+impl fmt::Display for CompileErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for diagnostic in self.diagnostics.iter() {
+            writeln!(f, "{}\n", diagnostic)?;
+        }
+        let errors = self.diagnostics.errors().count();
+        let warnings = self.diagnostics.warnings().count();
+        write!(
+            f,
+            "error: compilation failed with {} error{} and {} warning{}",
+            errors,
+            if errors == 1 { "" } else { "s" },
+            warnings,
+            if warnings == 1 { "" } else { "s" },
+        )
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Formats the location similarly to rustc and codespan, e.g.
+///
+/// ```text
+/// --> example.soy:3:4 (in example.foo)
+///   |
+/// 3 |   {if}
+///   |    ^^^
+/// ```
+impl fmt::Display for TemplateLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let line_number = self.line_number.to_string();
+        let gutter = " ".repeat(line_number.len());
+        write!(
+            f,
+            "{}--> {}:{}:{}",
+            gutter,
+            self.filename.as_ref().map_or("<input>", String::as_str),
+            self.line_number,
+            self.column
+        )?;
+        if let Some(name) = &self.template_name {
+            write!(f, " (in {})", name)?;
+        }
+        if let Some(snippet) = &self.snippet {
+            // Keep tabs in the padding so the carets line up with the snippet.
+            let padding: String = snippet
+                .chars()
+                .take(self.column.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            write!(f, "\n{} |", gutter)?;
+            write!(f, "\n{} | {}", line_number, snippet)?;
+            write!(
+                f,
+                "\n{} | {}{}",
+                gutter,
+                padding,
+                "^".repeat(self.length.max(1))
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,574 @@
+//! The form templates are compiled to for rendering. Lowering a template from its AST
+//! joins adjacent text, builds literal values once, resolves functions and directives
+//! by name, and replaces parameter names with slots, so that rendering does as little
+//! work as possible.
+
+use crate::ast::{self, BinaryOperator, ContentKind, UnaryOperator, Visibility};
+use crate::error::TemplateLocation;
+use crate::msg::{Message, MsgPart, PlaceholderPiece};
+use crate::value::SoyValue;
+
+pub struct Template {
+    /// The name relative to the file's namespace, as in `ast::Template`.
+    pub name: String,
+    pub visibility: Visibility,
+    pub kind: ContentKind,
+    /// Whether printed values are escaped for the template's kind (unless a directive says
+    /// otherwise).
+    pub autoescape: bool,
+    /// The names of the parameters the template refers to, by slot.
+    pub params: Vec<String>,
+    /// The fully-qualified names of the templates it calls, by slot. They are resolved
+    /// when the templates are linked.
+    pub calls: Vec<String>,
+    pub body: Block,
+    /// Where the template is defined.
+    pub location: TemplateLocation,
+}
+
+pub type Block = Vec<Node>;
+
+pub enum Node {
+    Text(String),
+    Print {
+        expression: Expression,
+        directives: Vec<Directive>,
+    },
+    If {
+        branches: Vec<(Expression, Block)>,
+        else_block: Block,
+    },
+    Msg(Msg),
+    /// A class name, which is `Err` with the name if it uses `%` without a `cssbase`.
+    Css {
+        base: Option<Expression>,
+        name: Result<String, String>,
+    },
+    Xid(String),
+    Call(Call),
+}
+
+pub struct Call {
+    /// The slot of the called template in `Template::calls`.
+    pub slot: usize,
+    pub data: Option<CallData>,
+    pub params: Vec<(String, CallParam)>,
+}
+
+pub enum CallData {
+    /// All of the caller's parameters.
+    All,
+    /// The entries of a map.
+    Expression(Expression),
+}
+
+pub enum CallParam {
+    Expression(Expression),
+    /// A block rendered by the caller and passed as sanitized content of the kind.
+    Block(ContentKind, Block),
+}
+
+pub struct Msg {
+    pub id: u64,
+    /// The message as written, used when there is no translation.
+    pub parts: Vec<MsgPart>,
+    pub placeholders: Vec<(String, Block)>,
+    pub variables: Vec<(String, Expression)>,
+}
+
+impl Msg {
+    pub fn placeholder(&self, name: &str) -> Option<&Block> {
+        self.placeholders
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, block)| block)
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&Expression> {
+        self.variables
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, expression)| expression)
+    }
+}
+
+pub struct Directive {
+    pub kind: DirectiveKind,
+    pub arguments: Vec<Expression>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DirectiveKind {
+    NoAutoescape,
+    EscapeHtml,
+    EscapeUri,
+    ChangeNewlineToBr,
+    BidiSpanWrap,
+    BidiUnicodeWrap,
+    Truncate,
+    /// Reported when the directive is applied. The parser rejects unknown directives, so
+    /// this is only reached by templates built from an AST that it didn't check.
+    Unknown(String),
+}
+
+impl DirectiveKind {
+    pub fn from_name(name: &str) -> DirectiveKind {
+        match name {
+            "noAutoescape" => DirectiveKind::NoAutoescape,
+            "escapeHtml" => DirectiveKind::EscapeHtml,
+            "escapeUri" => DirectiveKind::EscapeUri,
+            "changeNewlineToBr" => DirectiveKind::ChangeNewlineToBr,
+            "bidiSpanWrap" => DirectiveKind::BidiSpanWrap,
+            "bidiUnicodeWrap" => DirectiveKind::BidiUnicodeWrap,
+            "truncate" => DirectiveKind::Truncate,
+            name => DirectiveKind::Unknown(name.to_owned()),
+        }
+    }
+}
+
+pub enum Expression {
+    Constant(SoyValue),
+    List(Vec<Expression>),
+    Map(Vec<(String, Expression)>),
+    Param {
+        slot: usize,
+        references: Vec<Reference>,
+    },
+    Injected {
+        name: String,
+        references: Vec<Reference>,
+    },
+    Global(String),
+    Function {
+        function: Function,
+        arguments: Vec<Expression>,
+    },
+    /// Reported when the function is called. The parser rejects unknown functions, so
+    /// this is only reached by templates built from an AST that it didn't check.
+    UnknownFunction(String),
+    Binary {
+        lhs: Box<Expression>,
+        op: BinaryOperator,
+        rhs: Box<Expression>,
+    },
+    Unary {
+        op: UnaryOperator,
+        rhs: Box<Expression>,
+    },
+    Ternary {
+        condition: Box<Expression>,
+        if_true: Box<Expression>,
+        if_false: Box<Expression>,
+    },
+}
+
+pub struct Reference {
+    pub key: Key,
+    /// Whether the reference is `?.` or `?[]`, which give null when looked up on null.
+    pub null_safe: bool,
+}
+
+pub enum Key {
+    Constant(SoyValue),
+    Computed(Expression),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Function {
+    IsNonnull,
+    IsNull,
+    Length,
+    Keys,
+    Round,
+    Floor,
+    Ceiling,
+    Min,
+    Max,
+    Css,
+    Xid,
+    BidiGlobalDir,
+    BidiStartEdge,
+    BidiEndEdge,
+    BidiTextDir,
+    BidiDirAttr,
+    BidiMarkAfter,
+}
+
+impl Function {
+    pub fn from_name(name: &str) -> Option<Function> {
+        Some(match name {
+            "isNonnull" => Function::IsNonnull,
+            "isNull" => Function::IsNull,
+            "length" => Function::Length,
+            "keys" => Function::Keys,
+            "round" => Function::Round,
+            "floor" => Function::Floor,
+            "ceiling" => Function::Ceiling,
+            "min" => Function::Min,
+            "max" => Function::Max,
+            "css" => Function::Css,
+            "xid" => Function::Xid,
+            "bidiGlobalDir" => Function::BidiGlobalDir,
+            "bidiStartEdge" => Function::BidiStartEdge,
+            "bidiEndEdge" => Function::BidiEndEdge,
+            "bidiTextDir" => Function::BidiTextDir,
+            "bidiDirAttr" => Function::BidiDirAttr,
+            "bidiMarkAfter" => Function::BidiMarkAfter,
+            _ => return None,
+        })
+    }
+
+    /// Whether the result depends only on the arguments, and not on render options.
+    pub fn is_pure(self) -> bool {
+        !matches!(
+            self,
+            Function::Css
+                | Function::Xid
+                | Function::BidiGlobalDir
+                | Function::BidiStartEdge
+                | Function::BidiEndEdge
+                | Function::BidiTextDir
+                | Function::BidiDirAttr
+                | Function::BidiMarkAfter
+        )
+    }
+
+    /// The name the function is called by, for error messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            Function::IsNonnull => "isNonnull",
+            Function::IsNull => "isNull",
+            Function::Length => "length",
+            Function::Keys => "keys",
+            Function::Round => "round",
+            Function::Floor => "floor",
+            Function::Ceiling => "ceiling",
+            Function::Min => "min",
+            Function::Max => "max",
+            Function::Css => "css",
+            Function::Xid => "xid",
+            Function::BidiGlobalDir => "bidiGlobalDir",
+            Function::BidiStartEdge => "bidiStartEdge",
+            Function::BidiEndEdge => "bidiEndEdge",
+            Function::BidiTextDir => "bidiTextDir",
+            Function::BidiDirAttr => "bidiDirAttr",
+            Function::BidiMarkAfter => "bidiMarkAfter",
+        }
+    }
+}
+
+/// Lowers a template from its AST.
+pub fn lower(template: &ast::Template) -> Template {
+    let attributes = &template.attributes;
+    let mut lowering = Lowering {
+        css_base: attributes.css_base.as_deref(),
+        params: vec![],
+        calls: vec![],
+    };
+    let body = lowering.block(&template.body);
+    Template {
+        name: template.name.clone(),
+        visibility: attributes.visibility,
+        kind: attributes.kind,
+        autoescape: autoescape(attributes.kind),
+        params: lowering.params,
+        calls: lowering.calls,
+        body,
+        location: template.location.0.clone(),
+    }
+}
+
+/// Whether content of a kind, such as a template's, escapes printed values: HTML and
+/// attributes for HTML, URIs by percent-encoding them, JavaScript as literals, and CSS by
+/// only allowing safe values. Only text isn't escaped. The `autoescape` attribute doesn't
+/// affect this (see `ast::TemplateAttributes`).
+pub fn autoescape(kind: ContentKind) -> bool {
+    kind != ContentKind::Text
+}
+
+struct Lowering<'t> {
+    css_base: Option<&'t str>,
+    params: Vec<String>,
+    calls: Vec<String>,
+}
+
+impl<'t> Lowering<'t> {
+    fn block(&mut self, block: &[ast::TemplateNode]) -> Block {
+        let mut nodes = vec![];
+        for node in block {
+            self.node(node, &mut nodes);
+        }
+        nodes
+    }
+
+    /// Lowers a node onto the end of a block.
+    fn node(&mut self, node: &ast::TemplateNode, nodes: &mut Block) {
+        let node = match node {
+            ast::TemplateNode::RawText(text) | ast::TemplateNode::Special(text) => {
+                Node::Text(text.clone())
+            }
+            ast::TemplateNode::Statement(command) => self.command(command),
+        };
+        push_node(nodes, node);
+    }
+
+    fn command(&mut self, command: &ast::Command) -> Node {
+        match command {
+            ast::Command::If {
+                if_block,
+                else_ifs,
+                else_block,
+            } => Node::If {
+                branches: std::iter::once(if_block)
+                    .chain(else_ifs)
+                    .map(|conditional| {
+                        (
+                            self.expression(&conditional.expression),
+                            self.block(&conditional.block),
+                        )
+                    })
+                    .collect(),
+                else_block: else_block
+                    .as_ref()
+                    .map_or_else(Vec::new, |block| self.block(block)),
+            },
+            ast::Command::Msg { id, body, .. } => {
+                let message = Message::new(body);
+                let placeholders = message
+                    .placeholders
+                    .iter()
+                    .map(|(name, pieces)| {
+                        let mut nodes = vec![];
+                        for piece in pieces {
+                            match piece {
+                                PlaceholderPiece::Text(text) => {
+                                    push_node(&mut nodes, Node::Text(text.clone()))
+                                }
+                                PlaceholderPiece::Node(node) => self.node(node, &mut nodes),
+                            }
+                        }
+                        (name.clone(), nodes)
+                    })
+                    .collect();
+                let variables = message
+                    .variables
+                    .iter()
+                    .map(|(name, expression)| (name.clone(), self.expression(expression)))
+                    .collect();
+                Node::Msg(Msg {
+                    id: *id,
+                    parts: message.parts,
+                    placeholders,
+                    variables,
+                })
+            }
+            ast::Command::Print {
+                expression,
+                directives,
+                ..
+            } => Node::Print {
+                expression: self.expression(expression),
+                directives: directives
+                    .iter()
+                    .map(|directive| Directive {
+                        kind: DirectiveKind::from_name(&directive.name),
+                        arguments: directive
+                            .arguments
+                            .iter()
+                            .map(|argument| self.expression(argument))
+                            .collect(),
+                    })
+                    .collect(),
+            },
+            ast::Command::Literal(text) => Node::Text(text.clone()),
+            ast::Command::Css { base, name } => Node::Css {
+                base: base.as_ref().map(|base| self.expression(base)),
+                name: match (base, name.strip_prefix('%')) {
+                    (None, Some(suffix)) => match self.css_base {
+                        Some(css_base) => Ok(format!("{}-{}", css_base, suffix)),
+                        None => Err(name.clone()),
+                    },
+                    _ => Ok(name.clone()),
+                },
+            },
+            ast::Command::Xid(name) => Node::Xid(name.clone()),
+            ast::Command::Call(call) => Node::Call(Call {
+                slot: self.call_slot(&call.target),
+                data: call.data.as_ref().map(|data| match data {
+                    ast::CallData::All => CallData::All,
+                    ast::CallData::Expression(expression) => {
+                        CallData::Expression(self.expression(expression))
+                    }
+                }),
+                params: call
+                    .params
+                    .iter()
+                    .map(|param| {
+                        let value = match &param.value {
+                            ast::CallParamValue::Expression(expression) => {
+                                CallParam::Expression(self.expression(expression))
+                            }
+                            ast::CallParamValue::Block { kind, block } => {
+                                CallParam::Block(*kind, self.block(block))
+                            }
+                        };
+                        (param.name.clone(), value)
+                    })
+                    .collect(),
+            }),
+        }
+    }
+
+    fn expression(&mut self, expression: &ast::Expression) -> Expression {
+        match expression {
+            ast::Expression::Null => Expression::Constant(SoyValue::Null),
+            ast::Expression::Boolean(b) => Expression::Constant(SoyValue::Bool(*b)),
+            ast::Expression::Integer(i) => Expression::Constant(SoyValue::Int(*i)),
+            ast::Expression::Float(f) => Expression::Constant(SoyValue::Float(*f)),
+            ast::Expression::String(s) => Expression::Constant(SoyValue::String(s.clone())),
+            ast::Expression::List(items) => {
+                let items: Vec<_> = items.iter().map(|item| self.expression(item)).collect();
+                if items.iter().all(Expression::is_constant) {
+                    Expression::Constant(SoyValue::List(
+                        items.into_iter().map(Expression::into_constant).collect(),
+                    ))
+                } else {
+                    Expression::List(items)
+                }
+            }
+            ast::Expression::Map(entries) => {
+                let entries: Vec<_> = entries
+                    .iter()
+                    .map(|(key, value)| (key.clone(), self.expression(value)))
+                    .collect();
+                if entries.iter().all(|(_, value)| value.is_constant()) {
+                    Expression::Constant(SoyValue::Map(
+                        entries
+                            .into_iter()
+                            .map(|(key, value)| (key, value.into_constant()))
+                            .collect(),
+                    ))
+                } else {
+                    Expression::Map(entries)
+                }
+            }
+            ast::Expression::Function { name, parameters } => match Function::from_name(name) {
+                Some(function) => Expression::Function {
+                    function,
+                    arguments: parameters.iter().map(|p| self.expression(p)).collect(),
+                },
+                None => Expression::UnknownFunction(name.clone()),
+            },
+            ast::Expression::DataReference {
+                referent,
+                references,
+            } => {
+                let references = references
+                    .iter()
+                    .map(|reference| self.reference(reference))
+                    .collect();
+                match referent {
+                    ast::Referent::Variable(name) => Expression::Param {
+                        slot: self.slot(name),
+                        references,
+                    },
+                    ast::Referent::Injected(name) => Expression::Injected {
+                        name: name.clone(),
+                        references,
+                    },
+                }
+            }
+            ast::Expression::GlobalReference(name) => Expression::Global(name.clone()),
+            ast::Expression::BinaryOperation { lhs, op, rhs } => Expression::Binary {
+                lhs: Box::new(self.expression(lhs)),
+                op: *op,
+                rhs: Box::new(self.expression(rhs)),
+            },
+            ast::Expression::UnaryOperation { op, rhs } => Expression::Unary {
+                op: *op,
+                rhs: Box::new(self.expression(rhs)),
+            },
+            ast::Expression::TernaryOperation {
+                condition,
+                if_true,
+                if_false,
+            } => Expression::Ternary {
+                condition: Box::new(self.expression(condition)),
+                if_true: Box::new(self.expression(if_true)),
+                if_false: Box::new(self.expression(if_false)),
+            },
+        }
+    }
+
+    fn reference(&mut self, reference: &ast::Reference) -> Reference {
+        let constant_key = |key: &ast::ReferenceKey| {
+            Key::Constant(match key {
+                ast::ReferenceKey::Number(i) => SoyValue::Int(*i as i64),
+                ast::ReferenceKey::Name(name) => SoyValue::String(name.clone()),
+            })
+        };
+        let computed_key = |lowering: &mut Self, key| match lowering.expression(key) {
+            Expression::Constant(value) => Key::Constant(value),
+            key => Key::Computed(key),
+        };
+        match reference {
+            ast::Reference::Dotted(key) => Reference {
+                key: constant_key(key),
+                null_safe: false,
+            },
+            ast::Reference::QuestionDotted(key) => Reference {
+                key: constant_key(key),
+                null_safe: true,
+            },
+            ast::Reference::Bracketed(key) => Reference {
+                key: computed_key(self, key),
+                null_safe: false,
+            },
+            ast::Reference::QuestionBracketed(key) => Reference {
+                key: computed_key(self, key),
+                null_safe: true,
+            },
+        }
+    }
+
+    fn slot(&mut self, name: &str) -> usize {
+        match self.params.iter().position(|param| param == name) {
+            Some(slot) => slot,
+            None => {
+                self.params.push(name.to_owned());
+                self.params.len() - 1
+            }
+        }
+    }
+
+    fn call_slot(&mut self, target: &str) -> usize {
+        match self.calls.iter().position(|call| call == target) {
+            Some(slot) => slot,
+            None => {
+                self.calls.push(target.to_owned());
+                self.calls.len() - 1
+            }
+        }
+    }
+}
+
+/// Adds a node to the end of a block, joining it to the last node if both are text.
+fn push_node(nodes: &mut Block, node: Node) {
+    match (nodes.last_mut(), node) {
+        (Some(Node::Text(last)), Node::Text(text)) => last.push_str(&text),
+        (_, node) => nodes.push(node),
+    }
+}
+
+impl Expression {
+    fn is_constant(&self) -> bool {
+        matches!(self, Expression::Constant(_))
+    }
+
+    fn into_constant(self) -> SoyValue {
+        match self {
+            Expression::Constant(value) => value,
+            _ => unreachable!("checked with is_constant"),
+        }
+    }
+}
@@ -0,0 +1,12 @@
+//! soy's front end: the parser, the AST and IR it produces, and the errors it reports.
+//! It is shared by soy and soy-macros (which soy depends on), and isn't meant to be used
+//! directly.
+
+pub mod ast;
+pub mod error;
+pub mod ir;
+pub mod link;
+pub mod msg;
+pub mod parser;
+pub mod plural;
+pub mod value;
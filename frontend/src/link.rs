@@ -0,0 +1,86 @@
+//! Checks the calls between templates, which can only be done once every template in a set
+//! is known.
+
+use std::collections::HashMap;
+
+use crate::ast::{self, SoyFile, Visibility};
+use crate::error::{CompileError, CompileErrorKind, Diagnostics, TemplateLocation};
+
+/// A call to a template, as far as checking it is concerned.
+#[derive(Debug, Clone)]
+pub struct CallSite {
+    /// The fully-qualified name of the called template.
+    pub target: String,
+    pub location: TemplateLocation,
+}
+
+impl CallSite {
+    /// The calls made by the templates in a file.
+    pub fn in_file(file: &SoyFile) -> Vec<CallSite> {
+        file.templates
+            .iter()
+            .flat_map(|template| ast::calls(&template.body))
+            .map(|call| CallSite {
+                target: call.target.clone(),
+                location: call.location.0.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Collects the templates in a set of files and the calls between them, to check that
+/// every call is to a template that exists and may be called from where it is.
+#[derive(Default)]
+pub struct Linker {
+    /// The visibility of each template and the file it is in, by fully-qualified name.
+    templates: HashMap<String, (Visibility, usize)>,
+    /// The calls made from each file.
+    calls: Vec<Vec<CallSite>>,
+}
+
+impl Linker {
+    pub fn add_file(&mut self, file: &SoyFile) {
+        let namespace = &file.namespace.name;
+        let templates = file.templates.iter().map(|template| {
+            (
+                format!("{}.{}", namespace, template.name),
+                template.attributes.visibility,
+            )
+        });
+        self.add(templates, CallSite::in_file(file));
+    }
+
+    /// Adds a file, given its templates (by fully-qualified name) and the calls made by
+    /// them. When a template is defined more than once, the first definition is kept.
+    pub fn add<I>(&mut self, templates: I, calls: Vec<CallSite>)
+    where
+        I: IntoIterator<Item = (String, Visibility)>,
+    {
+        let file = self.calls.len();
+        for (name, visibility) in templates {
+            self.templates.entry(name).or_insert((visibility, file));
+        }
+        self.calls.push(calls);
+    }
+
+    /// Reports the calls to templates that don't exist, and to private templates from
+    /// other files.
+    pub fn check(&self, diagnostics: &mut Diagnostics) {
+        for (file, calls) in self.calls.iter().enumerate() {
+            for call in calls {
+                let kind = match self.templates.get(&call.target) {
+                    None => CompileErrorKind::UnknownTemplate(call.target.clone()),
+                    Some((Visibility::Private, defined_in)) if *defined_in != file => {
+                        CompileErrorKind::CallToPrivateTemplate(call.target.clone())
+                    }
+                    Some(_) => continue,
+                };
+                diagnostics.push(CompileError {
+                    kind,
+                    location: Some(Box::new(call.location.clone())),
+                    cause: None,
+                });
+            }
+        }
+    }
+}
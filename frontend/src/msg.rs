@@ -0,0 +1,685 @@
+//! The translatable form of `{msg}` commands: a message is a sequence of text and named
+//! placeholders (standing for the commands and HTML tags inside it), which is what
+//! translations are written in terms of, and what message ids are computed from.
+
+use crate::ast::{
+    Call, Command, Expression, MsgBody, Reference, ReferenceKey, Referent, TemplateBlock,
+    TemplateNode,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MsgPart {
+    RawText(String),
+    Placeholder(String),
+    Plural {
+        /// The placeholder-style name of the plural expression, e.g. `NUM_PEOPLE`.
+        var: String,
+        offset: i64,
+        cases: Vec<(PluralCaseSpec, Vec<MsgPart>)>,
+    },
+    Select {
+        /// The placeholder-style name of the select expression, e.g. `GENDER`.
+        var: String,
+        /// The cases by value, with `other` for `{default}`.
+        cases: Vec<(String, Vec<MsgPart>)>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PluralCaseSpec {
+    /// `{case 1}`, written `=1` in translations.
+    Explicit(i64),
+    /// A CLDR plural category, e.g. `other` for `{default}` or `few` for `{case 'few'}`.
+    Category(String),
+}
+
+/// Part of what a placeholder stands for. A command is a placeholder on its own, while an
+/// HTML tag is made of its text and any commands inside it (e.g. `<a href="{$url}">`).
+#[derive(Debug, PartialEq)]
+pub enum PlaceholderPiece<'a> {
+    Text(String),
+    Node(&'a TemplateNode),
+}
+
+/// A placeholder before it is named.
+#[derive(PartialEq)]
+struct Placeholder<'a> {
+    base_name: String,
+    /// Whether the name was given with `phname`, in which case it is used as is.
+    explicit: bool,
+    pieces: Vec<PlaceholderPiece<'a>>,
+}
+
+/// A message body before its placeholders and variables are named.
+enum Segment<'a> {
+    Text(String),
+    Placeholder(Placeholder<'a>),
+    Plural {
+        expression: &'a Expression,
+        offset: i64,
+        cases: Vec<(PluralCaseSpec, Vec<Segment<'a>>)>,
+    },
+    Select {
+        expression: &'a Expression,
+        cases: Vec<(String, Vec<Segment<'a>>)>,
+    },
+}
+
+/// A message's parts, along with what its placeholders stand for.
+pub struct Message<'a> {
+    pub parts: Vec<MsgPart>,
+    pub placeholders: Vec<(String, Vec<PlaceholderPiece<'a>>)>,
+    /// The expressions that plurals and selects choose their cases by.
+    pub variables: Vec<(String, &'a Expression)>,
+}
+
+impl<'a> Message<'a> {
+    pub fn new(body: &'a MsgBody) -> Message<'a> {
+        let segments = body_segments(body);
+        let mut placeholders = vec![];
+        let mut variables = vec![];
+        collect(&segments, &mut placeholders, &mut variables);
+        let placeholder_names = unique_names(
+            placeholders
+                .iter()
+                .map(|p| (p.base_name.as_str(), p.explicit)),
+        );
+        let variable_names = unique_names(
+            variables
+                .iter()
+                .map(|(base_name, _)| (base_name.as_str(), false)),
+        );
+        let placeholders: Vec<_> = placeholder_names
+            .into_iter()
+            .zip(placeholders)
+            .map(|(name, placeholder)| (name, copy_pieces(&placeholder.pieces)))
+            .collect();
+        let variables: Vec<_> = variable_names
+            .into_iter()
+            .zip(variables)
+            .map(|(name, (_, expression))| (name, expression))
+            .collect();
+        Message {
+            parts: segment_parts(segments, &placeholders, &variables),
+            placeholders,
+            variables,
+        }
+    }
+
+    /// The expression that a plural or select variable stands for.
+    pub fn variable(&self, name: &str) -> Option<&'a Expression> {
+        self.variables
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, expression)| *expression)
+    }
+
+    /// What a placeholder stands for.
+    pub fn placeholder(&self, name: &str) -> Option<&[PlaceholderPiece<'a>]> {
+        self.placeholders
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, pieces)| pieces.as_slice())
+    }
+
+    /// A name given (with `phname`) to placeholders that stand for different things.
+    pub fn conflicting_name(&self) -> Option<&str> {
+        self.placeholders
+            .iter()
+            .enumerate()
+            .find(|(i, (name, _))| self.placeholders[..*i].iter().any(|(n, _)| n == name))
+            .map(|(_, (name, _))| name.as_str())
+    }
+}
+
+/// Whether a `phname` is valid: an identifier in lower camel or upper underscore case.
+pub fn is_valid_phname(name: &str) -> bool {
+    let mut chars = name.chars();
+    let starts_with_letter = chars.next().is_some_and(|c| c.is_ascii_alphabetic());
+    starts_with_letter && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn body_segments(body: &MsgBody) -> Vec<Segment<'_>> {
+    match body {
+        MsgBody::Block(block) => segments(block),
+        MsgBody::Plural {
+            expression,
+            offset,
+            cases,
+            default,
+        } => {
+            let mut plural_cases: Vec<_> = cases
+                .iter()
+                .map(|case| {
+                    let spec = match &case.expression {
+                        Expression::Integer(i) => PluralCaseSpec::Explicit(*i),
+                        Expression::String(category) => PluralCaseSpec::Category(category.clone()),
+                        _ => PluralCaseSpec::Category("other".to_owned()),
+                    };
+                    (spec, body_segments(&case.body))
+                })
+                .collect();
+            plural_cases.push((
+                PluralCaseSpec::Category("other".to_owned()),
+                body_segments(default),
+            ));
+            vec![Segment::Plural {
+                expression,
+                offset: *offset,
+                cases: plural_cases,
+            }]
+        }
+        MsgBody::Select {
+            expression,
+            cases,
+            default,
+        } => {
+            let mut select_cases: Vec<_> = cases
+                .iter()
+                .map(|case| (case.value.clone(), body_segments(&case.body)))
+                .collect();
+            select_cases.push(("other".to_owned(), body_segments(default)));
+            vec![Segment::Select {
+                expression,
+                cases: select_cases,
+            }]
+        }
+    }
+}
+
+/// Splits a block into text and placeholders, finding the HTML tags in its text.
+fn segments(block: &TemplateBlock) -> Vec<Segment<'_>> {
+    let mut segments = vec![];
+    let mut text = String::new();
+    // The pieces of the HTML tag being read, and the quote that an attribute value
+    // being read is in.
+    let mut tag: Option<Vec<PlaceholderPiece>> = None;
+    let mut quote = None;
+    for node in block {
+        let node_text = match node {
+            TemplateNode::RawText(t) | TemplateNode::Special(t) => t,
+            node => {
+                match &mut tag {
+                    Some(pieces) => {
+                        push_tag_text(pieces, &mut text);
+                        pieces.push(PlaceholderPiece::Node(node));
+                    }
+                    None => {
+                        push_text(&mut segments, &mut text);
+                        segments.push(Segment::Placeholder(command_placeholder(node)));
+                    }
+                }
+                continue;
+            }
+        };
+        let mut chars = node_text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match &mut tag {
+                None => {
+                    let starts_tag = c == '<'
+                        && chars
+                            .peek()
+                            .is_some_and(|&n| n.is_ascii_alphabetic() || n == '/');
+                    if starts_tag {
+                        push_text(&mut segments, &mut text);
+                        tag = Some(vec![]);
+                    }
+                    text.push(c);
+                }
+                Some(pieces) => {
+                    text.push(c);
+                    match quote {
+                        Some(q) if c == q => quote = None,
+                        Some(_) => {}
+                        None if c == '"' || c == '\'' => quote = Some(c),
+                        None if c == '>' => {
+                            push_tag_text(pieces, &mut text);
+                            let pieces = std::mem::take(pieces);
+                            segments.push(Segment::Placeholder(tag_placeholder(pieces)));
+                            tag = None;
+                        }
+                        None => {}
+                    }
+                }
+            }
+        }
+    }
+    // An unterminated tag is left as text
+    if let Some(pieces) = tag {
+        for piece in pieces {
+            match piece {
+                PlaceholderPiece::Text(t) => push_segment_text(&mut segments, &t),
+                PlaceholderPiece::Node(node) => {
+                    segments.push(Segment::Placeholder(command_placeholder(node)))
+                }
+            }
+        }
+    }
+    push_text(&mut segments, &mut text);
+    segments
+}
+
+fn push_text(segments: &mut Vec<Segment>, text: &mut String) {
+    if !text.is_empty() {
+        push_segment_text(segments, text);
+        text.clear();
+    }
+}
+
+fn push_segment_text(segments: &mut Vec<Segment>, text: &str) {
+    match segments.last_mut() {
+        Some(Segment::Text(previous)) => previous.push_str(text),
+        _ => segments.push(Segment::Text(text.to_owned())),
+    }
+}
+
+fn push_tag_text(pieces: &mut Vec<PlaceholderPiece>, text: &mut String) {
+    if !text.is_empty() {
+        pieces.push(PlaceholderPiece::Text(std::mem::take(text)));
+    }
+}
+
+fn command_placeholder(node: &TemplateNode) -> Placeholder<'_> {
+    let (base_name, explicit) = match node {
+        TemplateNode::Statement(Command::Print {
+            phname: Some(name), ..
+        }) => (upper_underscore(name), true),
+        TemplateNode::Statement(Command::Print { expression, .. }) => {
+            (expression_name(expression), false)
+        }
+        TemplateNode::Statement(Command::Call(Call {
+            phname: Some(name), ..
+        })) => (upper_underscore(name), true),
+        _ => ("XXX".to_owned(), false),
+    };
+    Placeholder {
+        base_name,
+        explicit,
+        pieces: vec![PlaceholderPiece::Node(node)],
+    }
+}
+
+/// Names an HTML tag after the element, so `<a href="...">` becomes `START_LINK`, `</a>`
+/// becomes `END_LINK` and `<br>` becomes `BREAK`, unless a `phname` attribute (which is
+/// removed from the tag) gives the name.
+fn tag_placeholder(mut pieces: Vec<PlaceholderPiece>) -> Placeholder {
+    let mut phname = None;
+    for piece in &mut pieces {
+        if let PlaceholderPiece::Text(text) = piece {
+            if let Some((name, without)) = remove_phname(text) {
+                phname = Some(name);
+                *text = without;
+            }
+        }
+    }
+    if let Some(name) = phname.filter(|name| is_valid_phname(name)) {
+        return Placeholder {
+            base_name: upper_underscore(&name),
+            explicit: true,
+            pieces,
+        };
+    }
+    let text = match pieces.first() {
+        Some(PlaceholderPiece::Text(text)) => text.as_str(),
+        _ => "",
+    };
+    let is_end_tag = text.starts_with("</");
+    let tag_name: String = text
+        .trim_start_matches(['<', '/'])
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect::<String>()
+        .to_ascii_lowercase();
+    let is_self_closing = match pieces.last() {
+        Some(PlaceholderPiece::Text(text)) => text.ends_with("/>"),
+        _ => false,
+    };
+    let name = match tag_name.as_str() {
+        "" => "XXX".to_owned(),
+        "a" => "LINK".to_owned(),
+        "br" => "BREAK".to_owned(),
+        "b" => "BOLD".to_owned(),
+        "i" => "ITALIC".to_owned(),
+        "li" => "ITEM".to_owned(),
+        "ol" => "ORDERED_LIST".to_owned(),
+        "ul" => "UNORDERED_LIST".to_owned(),
+        "p" => "PARAGRAPH".to_owned(),
+        "img" => "IMAGE".to_owned(),
+        "em" => "EMPHASIS".to_owned(),
+        other => other.replace('-', "_").to_ascii_uppercase(),
+    };
+    let base_name = if is_end_tag {
+        format!("END_{}", name)
+    } else if is_self_closing || is_void_element(&tag_name) {
+        name
+    } else {
+        format!("START_{}", name)
+    };
+    Placeholder {
+        base_name,
+        explicit: false,
+        pieces,
+    }
+}
+
+/// Removes a `phname="..."` attribute from the text of a tag, returning the name and the
+/// remaining text.
+fn remove_phname(text: &str) -> Option<(String, String)> {
+    let start = text.find(" phname=")?;
+    let value_start = start + " phname=".len();
+    let quote = text[value_start..]
+        .chars()
+        .next()
+        .filter(|&c| c == '"' || c == '\'')?;
+    let value_end = value_start + 1 + text[value_start + 1..].find(quote)?;
+    let name = text[value_start + 1..value_end].to_owned();
+    let without = format!("{}{}", &text[..start], &text[value_end + 1..]);
+    Some((name, without))
+}
+
+fn is_void_element(tag_name: &str) -> bool {
+    matches!(
+        tag_name,
+        "area"
+            | "base"
+            | "br"
+            | "col"
+            | "embed"
+            | "hr"
+            | "img"
+            | "input"
+            | "link"
+            | "meta"
+            | "param"
+            | "source"
+            | "track"
+            | "wbr"
+    )
+}
+
+/// Collects the distinct placeholders and plural or select variables in a message, in
+/// order of appearance.
+fn collect<'s, 'a>(
+    segments: &'s [Segment<'a>],
+    placeholders: &mut Vec<&'s Placeholder<'a>>,
+    variables: &mut Vec<(String, &'a Expression)>,
+) {
+    for segment in segments {
+        match segment {
+            Segment::Text(_) => {}
+            Segment::Placeholder(placeholder) => {
+                if !placeholders.contains(&placeholder) {
+                    placeholders.push(placeholder);
+                }
+            }
+            Segment::Plural {
+                expression, cases, ..
+            } => {
+                add_variable(variables, expression);
+                for (_, segments) in cases {
+                    collect(segments, placeholders, variables);
+                }
+            }
+            Segment::Select { expression, cases } => {
+                add_variable(variables, expression);
+                for (_, segments) in cases {
+                    collect(segments, placeholders, variables);
+                }
+            }
+        }
+    }
+}
+
+fn add_variable<'a>(variables: &mut Vec<(String, &'a Expression)>, expression: &'a Expression) {
+    if !variables.iter().any(|(_, e)| *e == expression) {
+        variables.push((expression_name(expression), expression));
+    }
+}
+
+/// Names distinct things from their base names (and whether the names were given
+/// explicitly). Things with a base name of their own keep it, while different things
+/// that would have the same name are numbered (`NAME_1`, `NAME_2`) in order, skipping
+/// names that are already taken. Explicit names are used as is.
+fn unique_names<'n>(base_names: impl Iterator<Item = (&'n str, bool)>) -> Vec<String> {
+    let base_names: Vec<_> = base_names.collect();
+    let is_unique = |index: usize| {
+        let (base, explicit) = base_names[index];
+        explicit
+            || base_names
+                .iter()
+                .enumerate()
+                .all(|(i, (b, _))| i == index || *b != base)
+    };
+    let mut taken: Vec<String> = (0..base_names.len())
+        .filter(|&i| is_unique(i))
+        .map(|i| base_names[i].0.to_owned())
+        .collect();
+    (0..base_names.len())
+        .map(|i| {
+            let base = base_names[i].0;
+            if is_unique(i) {
+                return base.to_owned();
+            }
+            let name = (1..)
+                .map(|n| format!("{}_{}", base, n))
+                .find(|name| !taken.contains(name))
+                .unwrap_or_default();
+            taken.push(name.clone());
+            name
+        })
+        .collect()
+}
+
+fn copy_pieces<'a>(pieces: &[PlaceholderPiece<'a>]) -> Vec<PlaceholderPiece<'a>> {
+    pieces
+        .iter()
+        .map(|piece| match piece {
+            PlaceholderPiece::Text(text) => PlaceholderPiece::Text(text.clone()),
+            PlaceholderPiece::Node(node) => PlaceholderPiece::Node(node),
+        })
+        .collect()
+}
+
+/// The name for a placeholder or plural variable: the last key of a data reference in
+/// upper underscore case, or `XXX` for other expressions.
+fn expression_name(expression: &Expression) -> String {
+    let name = match expression {
+        Expression::DataReference {
+            referent,
+            references,
+        } => match references.last() {
+            Some(Reference::Dotted(ReferenceKey::Name(name)))
+            | Some(Reference::QuestionDotted(ReferenceKey::Name(name))) => name,
+            Some(_) => return "XXX".to_owned(),
+            None => match referent {
+                Referent::Variable(name) | Referent::Injected(name) => name,
+            },
+        },
+        _ => return "XXX".to_owned(),
+    };
+    upper_underscore(name)
+}
+
+/// Converts a camel case name to upper underscore case, e.g. `userName2` to `USER_NAME_2`.
+fn upper_underscore(name: &str) -> String {
+    let mut converted = String::with_capacity(name.len() + 4);
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if let Some(p) = previous {
+            let boundary = (c.is_ascii_uppercase()
+                && (p.is_ascii_lowercase() || p.is_ascii_digit()))
+                || (c.is_ascii_digit() && p.is_ascii_alphabetic());
+            if boundary && p != '_' {
+                converted.push('_');
+            }
+        }
+        converted.push(c.to_ascii_uppercase());
+        previous = Some(c);
+    }
+    converted
+}
+
+fn segment_parts(
+    segments: Vec<Segment>,
+    placeholders: &[(String, Vec<PlaceholderPiece>)],
+    variables: &[(String, &Expression)],
+) -> Vec<MsgPart> {
+    let variable_name = |expression: &Expression| {
+        variables
+            .iter()
+            .find(|(_, e)| *e == expression)
+            .map(|(name, _)| name.clone())
+            .unwrap_or_else(|| expression_name(expression))
+    };
+    segments
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => MsgPart::RawText(text),
+            Segment::Placeholder(placeholder) => {
+                let name = placeholders
+                    .iter()
+                    .find(|(_, pieces)| *pieces == placeholder.pieces)
+                    .map(|(name, _)| name.clone())
+                    .unwrap_or_else(|| placeholder.base_name.clone());
+                MsgPart::Placeholder(name)
+            }
+            Segment::Plural {
+                expression,
+                offset,
+                cases,
+            } => MsgPart::Plural {
+                var: variable_name(expression),
+                offset,
+                cases: cases
+                    .into_iter()
+                    .map(|(spec, segments)| {
+                        (spec, segment_parts(segments, placeholders, variables))
+                    })
+                    .collect(),
+            },
+            Segment::Select { expression, cases } => MsgPart::Select {
+                var: variable_name(expression),
+                cases: cases
+                    .into_iter()
+                    .map(|(value, segments)| {
+                        (value, segment_parts(segments, placeholders, variables))
+                    })
+                    .collect(),
+            },
+        })
+        .collect()
+}
+
+/// Computes a message's id, which is compatible with the Closure Templates compiler's
+/// (`SoyMsgIdComputer`), so that existing translations can be used.
+pub fn compute_id(parts: &[MsgPart], meaning: Option<&str>) -> u64 {
+    let braced = parts
+        .iter()
+        .any(|part| matches!(part, MsgPart::Plural { .. } | MsgPart::Select { .. }));
+    let mut fp = fingerprint(&id_content(parts, braced));
+    if let Some(meaning) = meaning {
+        fp = (fp << 1)
+            .wrapping_add(if (fp as i64) < 0 { 1 } else { 0 })
+            .wrapping_add(fingerprint(meaning));
+    }
+    // Strips the high bit so ids are never negative
+    fp & 0x7fff_ffff_ffff_ffff
+}
+
+/// The string that a message's id is a fingerprint of. Messages containing plurals or
+/// selects have braces around their placeholders.
+fn id_content(parts: &[MsgPart], braced: bool) -> String {
+    let mut content = String::new();
+    for part in parts {
+        match part {
+            MsgPart::RawText(text) => content.push_str(text),
+            MsgPart::Placeholder(name) if braced => {
+                content.push('{');
+                content.push_str(name);
+                content.push('}');
+            }
+            MsgPart::Placeholder(name) => content.push_str(name),
+            MsgPart::Plural { var, offset, cases } => {
+                content.push('{');
+                content.push_str(var);
+                content.push_str(",plural,");
+                if *offset != 0 {
+                    content.push_str(&format!("offset:{} ", offset));
+                }
+                for (spec, parts) in cases {
+                    match spec {
+                        PluralCaseSpec::Explicit(n) => content.push_str(&format!("={}", n)),
+                        PluralCaseSpec::Category(category) => content.push_str(category),
+                    }
+                    content.push('{');
+                    content.push_str(&id_content(parts, braced));
+                    content.push('}');
+                }
+                content.push('}');
+            }
+            MsgPart::Select { var, cases } => {
+                content.push('{');
+                content.push_str(var);
+                content.push_str(",select,");
+                for (value, parts) in cases {
+                    content.push_str(value);
+                    content.push('{');
+                    content.push_str(&id_content(parts, braced));
+                    content.push('}');
+                }
+                content.push('}');
+            }
+        }
+    }
+    content
+}
+
+/// The 64 bit fingerprint used for message ids, made of two 32 bit Jenkins hashes.
+fn fingerprint(text: &str) -> u64 {
+    let bytes = text.as_bytes();
+    let mut hi = hash32(bytes, 0);
+    let mut lo = hash32(bytes, 102_072);
+    if hi == 0 && (lo == 0 || lo == 1) {
+        // Turns 0 and 1 into other fingerprints
+        hi ^= 0x130f_9bef;
+        lo ^= 0x94a0_a928;
+    }
+    (u64::from(hi) << 32) | u64::from(lo)
+}
+
+fn hash32(bytes: &[u8], seed: u32) -> u32 {
+    let word = |b: &[u8]| u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+    let (mut a, mut b, mut c) = (0x9e37_79b9u32, 0x9e37_79b9u32, seed);
+    let mut chunks = bytes.chunks_exact(12);
+    for chunk in &mut chunks {
+        a = a.wrapping_add(word(&chunk[0..4]));
+        b = b.wrapping_add(word(&chunk[4..8]));
+        c = c.wrapping_add(word(&chunk[8..12]));
+        mix(&mut a, &mut b, &mut c);
+    }
+    c = c.wrapping_add(bytes.len() as u32);
+    // The remaining bytes are added in little endian order, except that the low byte of
+    // `c` is reserved for the length.
+    for (i, &byte) in chunks.remainder().iter().enumerate() {
+        let byte = u32::from(byte);
+        match i {
+            0..=3 => a = a.wrapping_add(byte << (8 * i)),
+            4..=7 => b = b.wrapping_add(byte << (8 * (i - 4))),
+            _ => c = c.wrapping_add(byte << (8 * (i - 7))),
+        }
+    }
+    mix(&mut a, &mut b, &mut c);
+    c
+}
+
+fn mix(a: &mut u32, b: &mut u32, c: &mut u32) {
+    *a = a.wrapping_sub(*b).wrapping_sub(*c) ^ (*c >> 13);
+    *b = b.wrapping_sub(*c).wrapping_sub(*a) ^ (*a << 8);
+    *c = c.wrapping_sub(*a).wrapping_sub(*b) ^ (*b >> 13);
+    *a = a.wrapping_sub(*b).wrapping_sub(*c) ^ (*c >> 12);
+    *b = b.wrapping_sub(*c).wrapping_sub(*a) ^ (*a << 16);
+    *c = c.wrapping_sub(*a).wrapping_sub(*b) ^ (*b >> 5);
+    *a = a.wrapping_sub(*b).wrapping_sub(*c) ^ (*c >> 3);
+    *b = b.wrapping_sub(*c).wrapping_sub(*a) ^ (*a << 10);
+    *c = c.wrapping_sub(*a).wrapping_sub(*b) ^ (*b >> 15);
+}
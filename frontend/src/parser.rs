@@ -0,0 +1,1421 @@
+use pest::error::{Error as PestError, LineColLocation};
+use pest::iterators::{Pair, Pairs};
+use pest::{Parser, Span};
+use pest_derive::Parser;
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::error::{CompileError, CompileErrorKind, Diagnostics, Severity, TemplateLocation};
+use crate::ir::{DirectiveKind, Function};
+use crate::msg::{self, Message};
+use crate::plural::PluralCategory;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Parser)]
+#[grammar = "soy.pest"]
+pub struct SoyParser;
+
+/// Parses a soy file, failing with the first error found.
+pub fn parse(input: &str) -> Result<SoyFile, CompileError> {
+    let mut diagnostics = Diagnostics::default();
+    let file = parse_file(None, input, &mut diagnostics);
+    match diagnostics
+        .into_iter()
+        .find(|d| d.severity() == Severity::Error)
+    {
+        Some(error) => Err(error),
+        None => file.ok_or_else(malformed_file),
+    }
+}
+
+/// Parses a soy file, reporting any errors or warnings (attributed to the given filename)
+/// to `diagnostics`. Returns `None` if the file could not be parsed at all, and otherwise
+/// the templates that could be.
+pub fn parse_file(
+    filename: Option<&str>,
+    input: &str,
+    diagnostics: &mut Diagnostics,
+) -> Option<SoyFile> {
+    if let Err(e) = check_nesting_depth(input) {
+        diagnostics.push(e.with_filename(filename));
+        return None;
+    }
+    let rules = match SoyParser::parse(Rule::soy_file, input) {
+        Ok(mut rules) => match rules.next() {
+            Some(rules) => rules,
+            None => {
+                diagnostics.push(malformed_file().with_filename(filename));
+                return None;
+            }
+        },
+        Err(e) => {
+            diagnostics.push(convert_pest_error(e).with_filename(filename));
+            return None;
+        }
+    };
+    let mut file_diagnostics = Diagnostics::default();
+    let mut file = parse_soyfile(rules.clone(), &mut file_diagnostics);
+    if let Some(file) = &mut file {
+        check_template_params(rules.clone(), &file.namespace.name, &mut file_diagnostics);
+        check_function_names(rules, &file.namespace.name, &mut file_diagnostics);
+        for template in &mut file.templates {
+            template.location.0.filename = filename.map(str::to_owned);
+            for_each_call_mut(&mut template.body, &mut |call| {
+                call.location.0.filename = filename.map(str::to_owned)
+            });
+        }
+    }
+    diagnostics.extend(
+        file_diagnostics
+            .into_iter()
+            .map(|d| d.with_filename(filename)),
+    );
+    file
+}
+
+/// The deepest nesting of blocks and brackets accepted. The generated parser is recursive,
+/// so deeper input could overflow the stack, particularly on threads with small stacks.
+const MAX_NESTING_DEPTH: usize = 100;
+
+/// Rejects input that nests too deeply, before handing it to the recursive parser. This is
+/// a lexical approximation: it counts open block commands (e.g. `{if}`, but not self-closing
+/// tags) and, within tags, open parentheses and brackets, and operators. Parentheses and
+/// brackets count twice, as they take the most stack to parse. Each operator counts for the
+/// rest of the tag, since a chain of them (e.g. `- - 1` or `1 + 1 + 1`) nests as deeply as
+/// it is long.
+fn check_nesting_depth(input: &str) -> Result<(), CompileError> {
+    const BLOCKS: &[&str] = &[
+        "if",
+        "msg",
+        "plural",
+        "select",
+        "switch",
+        "for",
+        "foreach",
+        "call",
+        "delcall",
+        "let",
+        "param",
+        "template",
+        "deltemplate",
+    ];
+    let mut block_depth = 0usize;
+    let mut expression_depth = 0usize;
+    let mut tag_start = None;
+    let mut quote = None;
+    let mut skip_to = 0;
+    for (i, c) in input.char_indices() {
+        if i < skip_to {
+            continue;
+        }
+        match (tag_start, quote, c) {
+            (None, _, '{') if input[i..].starts_with("{literal}") => {
+                match input[i..].find("{/literal}") {
+                    Some(end) => skip_to = i + end + "{/literal}".len(),
+                    None => return Ok(()),
+                }
+            }
+            (None, _, '{') => {
+                tag_start = Some(i);
+                if input[i..].starts_with("{/") {
+                    block_depth = block_depth.saturating_sub(1);
+                }
+            }
+            (None, ..) => {}
+            (Some(_), Some(q), _) if c == q => quote = None,
+            (Some(_), Some(_), '\\') => skip_to = i + 2,
+            (Some(_), Some(_), _) => {}
+            (Some(_), None, '\'') | (Some(_), None, '"') => quote = Some(c),
+            (Some(_), None, '(') | (Some(_), None, '[') => expression_depth += 2,
+            (Some(_), None, ')') | (Some(_), None, ']') => {
+                expression_depth = expression_depth.saturating_sub(2)
+            }
+            (Some(_), None, '-' | '+' | '*' | '/' | '%') => expression_depth += 1,
+            // The second character of `<=`, `>=`, `==` and `!=` isn't another operator
+            (Some(_), None, '<' | '>' | '=' | '!')
+                if !input[..i].ends_with(['<', '>', '=', '!']) =>
+            {
+                expression_depth += 1
+            }
+            // `?.` and `?[` are references rather than operators
+            (Some(_), None, '?') if !input[i + 1..].starts_with(['.', '[']) => {
+                expression_depth += 1
+            }
+            (Some(_), None, c) if c.is_ascii_alphabetic() && !ends_with_ident(&input[..i]) => {
+                let word = input[i..]
+                    .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .next();
+                if matches!(word, Some("not" | "and" | "or")) {
+                    expression_depth += 1;
+                }
+            }
+            (Some(start), None, '}') => {
+                let keyword = input[start + 1..i]
+                    .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .next();
+                if keyword.is_some_and(|k| BLOCKS.contains(&k)) && !input[..i].ends_with('/') {
+                    block_depth += 1;
+                }
+                tag_start = None;
+                expression_depth = 0;
+            }
+            (Some(_), None, _) => {}
+        }
+        if block_depth + expression_depth > MAX_NESTING_DEPTH {
+            return Err(CompileError {
+                kind: CompileErrorKind::TooDeeplyNested(MAX_NESTING_DEPTH),
+                location: Span::new(input, i, i + c.len_utf8())
+                    .map(|span| Box::new(span_location(&span))),
+                cause: None,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Whether text ends with part of a name (e.g. `$a` or `foo.b`), so that a word following
+/// it would be part of the same name.
+fn ends_with_ident(text: &str) -> bool {
+    text.ends_with(|c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '$' | '.'))
+}
+
+fn malformed_file() -> CompileError {
+    CompileError {
+        kind: CompileErrorKind::Malformed("expecting a soy file".to_owned()),
+        location: None,
+        cause: None,
+    }
+}
+
+fn convert_pest_error(error: PestError<Rule>) -> CompileError {
+    let ((line_number, column), length) = match error.line_col {
+        LineColLocation::Pos(pos) => (pos, 1),
+        LineColLocation::Span(start, end) if start.0 == end.0 => (start, end.1 - start.1),
+        LineColLocation::Span(start, _) => (start, error.line().chars().count() + 1 - start.1),
+    };
+    CompileError {
+        kind: CompileErrorKind::Parse(error.variant.message().into_owned()),
+        location: Some(Box::new(TemplateLocation {
+            line_number,
+            column,
+            length,
+            snippet: Some(error.line().to_owned()),
+            ..Default::default()
+        })),
+        cause: None,
+    }
+}
+
+/// Computes the location of a span in the source, for attaching to errors.
+fn span_location(span: &Span) -> TemplateLocation {
+    let start = span.start_pos();
+    let (line_number, column) = start.line_col();
+    let length = span
+        .as_str()
+        .lines()
+        .next()
+        .map_or(0, |l| l.chars().count());
+    TemplateLocation {
+        line_number,
+        column,
+        length,
+        snippet: Some(
+            start
+                .line_of()
+                .trim_end_matches(&['\r', '\n'][..])
+                .to_owned(),
+        ),
+        ..Default::default()
+    }
+}
+
+/// Reports usages of undeclared parameters, and declared parameters that are never used.
+fn check_template_params(pair: Pair<Rule>, namespace: &str, diagnostics: &mut Diagnostics) {
+    for template in pair.into_inner().filter(|p| p.as_rule() == Rule::template) {
+        let template_name = full_template_name(&template, namespace);
+        let mut params = vec![];
+        let mut usages = vec![];
+        // A call with `data="all"` uses every parameter
+        let mut passes_all = false;
+        for p in template.into_inner() {
+            match p.as_rule() {
+                Rule::soydoc => params.extend(
+                    p.into_inner()
+                        .flat_map(|param| param.into_inner())
+                        .filter(|p| p.as_rule() == Rule::name),
+                ),
+                Rule::template_body => {
+                    for p in p.into_inner().flatten() {
+                        match p.as_rule() {
+                            Rule::variable => usages.push(p),
+                            Rule::call_data_all => passes_all = true,
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        let error = |kind, span: Span| CompileError {
+            kind,
+            location: Some(Box::new(TemplateLocation {
+                template_name: template_name.clone(),
+                ..span_location(&span)
+            })),
+            cause: None,
+        };
+        for usage in &usages {
+            let name = usage.as_str().trim_start_matches('$');
+            if !params.iter().any(|p| p.as_str() == name) {
+                diagnostics.push(error(
+                    CompileErrorKind::UndeclaredParameter(name.to_owned()),
+                    usage.as_span(),
+                ));
+            }
+        }
+        for param in params.iter().filter(|_| !passes_all) {
+            if !usages
+                .iter()
+                .any(|u| u.as_str().trim_start_matches('$') == param.as_str())
+            {
+                diagnostics.push(error(
+                    CompileErrorKind::UnusedParameter(param.as_str().to_owned()),
+                    param.as_span(),
+                ));
+            }
+        }
+    }
+}
+
+/// Reports calls to functions and print directives that don't exist, so that templates
+/// using them are rejected when they are compiled rather than when they are rendered.
+fn check_function_names(pair: Pair<Rule>, namespace: &str, diagnostics: &mut Diagnostics) {
+    for template in pair.into_inner().filter(|p| p.as_rule() == Rule::template) {
+        let template_name = full_template_name(&template, namespace);
+        check_names_in(template, &template_name, false, diagnostics);
+    }
+}
+
+fn check_names_in(
+    pair: Pair<Rule>,
+    template_name: &Option<String>,
+    in_plural: bool,
+    diagnostics: &mut Diagnostics,
+) {
+    for p in pair.into_inner() {
+        let kind = match p.as_rule() {
+            Rule::function => {
+                let name = p.clone().into_inner().next().map_or("", |n| n.as_str());
+                // `remainder` is replaced within plurals (see `replace_remainder`)
+                let known =
+                    Function::from_name(name).is_some() || (in_plural && name == "remainder");
+                Some(CompileErrorKind::UnknownFunction(name.to_owned())).filter(|_| !known)
+            }
+            Rule::print_directive => {
+                let name = p.clone().into_inner().next().map_or("", |n| n.as_str());
+                match DirectiveKind::from_name(name) {
+                    DirectiveKind::Unknown(name) => Some(CompileErrorKind::UnknownDirective(name)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            diagnostics.push(CompileError {
+                kind,
+                location: Some(Box::new(TemplateLocation {
+                    template_name: template_name.clone(),
+                    ..span_location(&p.as_span())
+                })),
+                cause: None,
+            });
+        }
+        let in_plural = in_plural || p.as_rule() == Rule::msg_plural;
+        check_names_in(p, template_name, in_plural, diagnostics);
+    }
+}
+
+/// The fully-qualified name of a template, if it has one.
+fn full_template_name(template: &Pair<Rule>, namespace: &str) -> Option<String> {
+    let name = template
+        .clone()
+        .into_inner()
+        .find(|p| p.as_rule() == Rule::template_name)?;
+    Some(match name.as_str() {
+        partial if partial.starts_with('.') => format!("{}{}", namespace, partial),
+        global => global.to_owned(),
+    })
+}
+
+/// Parses a file, reporting the errors in each of its parts (e.g. each template) to
+/// `diagnostics` rather than stopping at the first. Returns `None` if it has no namespace.
+fn parse_soyfile(pair: Pair<Rule>, diagnostics: &mut Diagnostics) -> Option<SoyFile> {
+    let span = pair.as_span();
+    let mut delpackage = None;
+    let mut namespace = None;
+    let mut aliases = vec![];
+    let mut templates = vec![];
+    let mut failed = false;
+    for p in pair.into_inner() {
+        let result = match p.as_rule() {
+            Rule::namespace => parse_namespace(p).map(|n| namespace = Some(n)),
+            Rule::alias => parse_alias(p).map(|alias| aliases.push(alias)),
+            Rule::template => {
+                parse_file_template(p, namespace.as_ref(), &aliases).map(|t| templates.push(t))
+            }
+            Rule::delpackage => first_inner(p).map(|p| delpackage = Some(p.as_str().to_owned())),
+            Rule::EOI => Ok(()),
+            _ => Err(unexpected(&p)),
+        };
+        if let Err(e) = result {
+            diagnostics.push(e);
+            failed = true;
+        }
+    }
+    if namespace.is_none() && !failed {
+        diagnostics.push(missing(&span, "namespace"));
+    }
+    Some(SoyFile {
+        delpackage,
+        namespace: namespace?,
+        aliases,
+        templates,
+    })
+}
+
+/// Parses a template, resolving its name (and the names of the templates it calls) against
+/// the file's namespace and inheriting the namespace's attributes.
+fn parse_file_template(
+    pair: Pair<Rule>,
+    namespace: Option<&Namespace>,
+    aliases: &[Alias],
+) -> Result<Template, CompileError> {
+    let name_span = pair
+        .clone()
+        .into_inner()
+        .find(|p| p.as_rule() == Rule::template_name)
+        .map(|p| p.as_span());
+    let body = pair.clone();
+    let mut template = parse_template(pair)?;
+    if let (Some(namespace), Some(name_span)) = (namespace, name_span) {
+        template.name = local_template_name(&namespace.name, &template, &name_span)?;
+        let template_name = format!("{}.{}", namespace.name, template.name);
+        for_each_call_mut(&mut template.body, &mut |call| {
+            call.target = resolve_call_target(&call.target, &namespace.name, aliases);
+            call.location.0.template_name = Some(template_name.clone());
+        });
+        template.location.0.template_name = Some(template_name);
+        inherit_attributes(&mut template.attributes, &namespace.attributes);
+    }
+    if template.attributes.css_base.is_none() {
+        check_css_base(body)?;
+    }
+    Ok(template)
+}
+
+/// The fully-qualified name of a called template: `.foo` is in the file's namespace, and a
+/// name starting with an alias (e.g. `bar.foo` after `{alias baz.bar}`, or after
+/// `{alias baz.qux as bar}`) is in the aliased namespace. Other names are already
+/// fully-qualified.
+fn resolve_call_target(target: &str, namespace: &str, aliases: &[Alias]) -> String {
+    if target.starts_with('.') {
+        return format!("{}{}", namespace, target);
+    }
+    let (first, rest) = target.split_at(target.find('.').unwrap_or(target.len()));
+    let alias = aliases.iter().find(|alias| {
+        let name = alias
+            .to
+            .as_deref()
+            .unwrap_or_else(|| alias.from.rsplit('.').next().unwrap_or(&alias.from));
+        name == first
+    });
+    match alias {
+        Some(alias) if !rest.is_empty() => format!("{}{}", alias.from, rest),
+        _ => target.to_owned(),
+    }
+}
+
+/// Fills in the defaults a template takes from its namespace. The namespace's required
+/// CSS is required by every template, in addition to the template's own.
+fn inherit_attributes(template: &mut TemplateAttributes, namespace: &NamespaceAttributes) {
+    if template.autoescape.is_none() {
+        template.autoescape = namespace.autoescape;
+    }
+    if template.css_base.is_none() {
+        template.css_base = namespace.css_base.clone();
+    }
+    let own = std::mem::replace(&mut template.require_css, namespace.require_css.clone());
+    for css in own {
+        if !template.require_css.contains(&css) {
+            template.require_css.push(css);
+        }
+    }
+}
+
+/// Checks that a template without a `cssbase` doesn't use `{css %name}`, which is
+/// relative to it.
+fn check_css_base(template: Pair<Rule>) -> Result<(), CompileError> {
+    for css in template
+        .into_inner()
+        .flatten()
+        .filter(|p| p.as_rule() == Rule::css_statement)
+    {
+        let mut inner = css.clone().into_inner();
+        let relative = inner
+            .clone()
+            .find(|p| p.as_rule() == Rule::css_name)
+            .filter(|name| name.as_str().starts_with('%'));
+        let has_base = inner.any(|p| p.as_rule() == Rule::css_base);
+        if let (Some(name), false) = (relative, has_base) {
+            return Err(error_at(
+                CompileErrorKind::MissingCssBase(name.as_str().to_owned()),
+                &css.as_span(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that a template belongs to the file's namespace, returning its name relative
+/// to the namespace. Fully-qualified names (`{template ns.foo}`) must be directly
+/// within the namespace.
+fn local_template_name(
+    namespace: &str,
+    template: &Template,
+    span: &Span,
+) -> Result<String, CompileError> {
+    if !template.name.contains('.') {
+        return Ok(template.name.clone());
+    }
+    match template.name.rsplitn(2, '.').collect::<Vec<_>>().as_slice() {
+        [name, prefix] if *prefix == namespace => Ok((*name).to_owned()),
+        _ => Err(error_at(
+            CompileErrorKind::WrongNamespace(template.name.clone(), namespace.to_owned()),
+            span,
+        )),
+    }
+}
+
+fn parse_namespace(pair: Pair<Rule>) -> Result<Namespace, CompileError> {
+    let span = pair.as_span();
+    let mut name = None;
+    let mut attributes = NamespaceAttributes::default();
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::global_name => name = Some(p.as_str().to_owned()),
+            Rule::attributes => {
+                for attr in p.into_inner() {
+                    let span = attr.as_span();
+                    let (name, value) = parse_attribute(attr)?;
+                    match name.as_str() {
+                        "autoescape" => {
+                            attributes.autoescape =
+                                Some(parse_autoescape(&value).ok_or_else(|| {
+                                    invalid_attribute(name.clone(), value.clone(), &span)
+                                })?)
+                        }
+                        "requirecss" => attributes.require_css = parse_require_css(&value),
+                        "cssbase" => attributes.css_base = Some(value),
+                        _ => return Err(error_at(CompileErrorKind::UnknownAttribute(name), &span)),
+                    }
+                }
+            }
+            _ => return Err(unexpected(&p)),
+        }
+    }
+    Ok(Namespace {
+        name: name.ok_or_else(|| missing(&span, "name"))?,
+        attributes,
+    })
+}
+
+fn parse_alias(pair: Pair<Rule>) -> Result<Alias, CompileError> {
+    let span = pair.as_span();
+    let mut from = None;
+    let mut to = None;
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::global_name => from = Some(p.as_str().to_owned()),
+            Rule::alias_as => to = Some(first_inner(p)?.as_str().to_owned()),
+            _ => return Err(unexpected(&p)),
+        }
+    }
+    Ok(Alias {
+        from: from.ok_or_else(|| missing(&span, "name"))?,
+        to,
+    })
+}
+
+fn parse_template(pair: Pair<Rule>) -> Result<Template, CompileError> {
+    let span = pair.as_span();
+    let mut soydoc_params = vec![];
+    let mut body = None;
+    let mut name = None;
+    let mut attributes = TemplateAttributes::default();
+    let mut tag_start = None;
+    let mut location = Location::default();
+
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::template_tag => tag_start = Some(p.as_span().start_pos()),
+            Rule::soydoc => {
+                soydoc_params = p
+                    .into_inner()
+                    .map(parse_soydoc_param)
+                    .collect::<Result<_, _>>()?
+            }
+            Rule::template_name => {
+                // From the start of the tag to the end of the name, e.g. `{template .foo`
+                let start = tag_start.unwrap_or_else(|| p.as_span().start_pos());
+                location = Location(span_location(&start.span(&p.as_span().end_pos())));
+                let p = first_inner(p)?;
+                name = Some(match p.as_rule() {
+                    Rule::partial_name => first_inner(p)?.as_str().to_owned(),
+                    // Resolved against the namespace by `parse_soyfile`
+                    Rule::global_name => p.as_str().to_owned(),
+                    _ => return Err(unexpected(&p)),
+                });
+            }
+            Rule::attributes => attributes = parse_template_attributes(p)?,
+            Rule::template_body => {
+                let block = p.into_inner().find(|p| p.as_rule() == Rule::template_block);
+                body = block.map(parse_template_block).transpose()?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Template {
+        name: name.ok_or_else(|| missing(&span, "template name"))?,
+        attributes,
+        body: body.ok_or_else(|| missing(&span, "template body"))?,
+        soydoc_params,
+        location,
+    })
+}
+
+fn parse_template_attributes(pair: Pair<Rule>) -> Result<TemplateAttributes, CompileError> {
+    let mut attributes = TemplateAttributes::default();
+    for attr in pair.into_inner() {
+        let span = attr.as_span();
+        let (name, value) = parse_attribute(attr)?;
+        let invalid = || invalid_attribute(name.clone(), value.clone(), &span);
+        match name.as_str() {
+            "visibility" => {
+                attributes.visibility = match value.as_str() {
+                    "public" => Visibility::Public,
+                    "private" => Visibility::Private,
+                    _ => return Err(invalid()),
+                }
+            }
+            "kind" => attributes.kind = parse_content_kind(&value).ok_or_else(invalid)?,
+            "autoescape" => {
+                attributes.autoescape = Some(parse_autoescape(&value).ok_or_else(invalid)?)
+            }
+            "requirecss" => attributes.require_css = parse_require_css(&value),
+            "cssbase" => attributes.css_base = Some(value),
+            "stricthtml" => {
+                attributes.strict_html = match value.as_str() {
+                    "true" => true,
+                    "false" => false,
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => return Err(error_at(CompileErrorKind::UnknownAttribute(name), &span)),
+        }
+    }
+    Ok(attributes)
+}
+
+fn parse_content_kind(value: &str) -> Option<ContentKind> {
+    match value {
+        "html" => Some(ContentKind::Html),
+        "text" => Some(ContentKind::Text),
+        "attributes" => Some(ContentKind::Attributes),
+        "uri" => Some(ContentKind::Uri),
+        "trusted_resource_uri" => Some(ContentKind::TrustedResourceUri),
+        "css" => Some(ContentKind::Css),
+        "js" => Some(ContentKind::Js),
+        _ => None,
+    }
+}
+
+fn parse_autoescape(value: &str) -> Option<AutoescapeMode> {
+    match value {
+        "strict" => Some(AutoescapeMode::Strict),
+        "deprecated-contextual" => Some(AutoescapeMode::Contextual),
+        "deprecated-noncontextual" => Some(AutoescapeMode::NonContextual),
+        _ => None,
+    }
+}
+
+fn parse_require_css(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|css| !css.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+fn invalid_attribute(name: String, value: String, span: &Span) -> CompileError {
+    error_at(CompileErrorKind::InvalidAttributeValue(name, value), span)
+}
+
+fn parse_attribute(pair: Pair<Rule>) -> Result<(String, String), CompileError> {
+    let span = pair.as_span();
+    let mut attr = pair.into_inner();
+    let name = next_inner(&mut attr, &span, "attribute name")?;
+    let value = next_inner(&mut attr, &span, "attribute value")?;
+    Ok((name.as_str().to_owned(), parse_quoted_string(value)?))
+}
+
+fn parse_soydoc_param(pair: Pair<Rule>) -> Result<SoydocParam, CompileError> {
+    let span = pair.as_span();
+    let mut name = None;
+    let mut required = None;
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::name => name = Some(p.as_str().to_owned()),
+            Rule::soydoc_required => required = Some(true),
+            Rule::soydoc_optional => required = Some(false),
+            _ => return Err(unexpected(&p)),
+        }
+    }
+    Ok(SoydocParam {
+        name: name.ok_or_else(|| missing(&span, "parameter name"))?,
+        required: required.ok_or_else(|| missing(&span, "@param or @param?"))?,
+    })
+}
+
+fn parse_template_block(pair: Pair<Rule>) -> Result<TemplateBlock, CompileError> {
+    let mut nodes = vec![];
+    // Text is accumulated (skipping comments) until the next tag, then line joined.
+    let mut text = String::new();
+    for p in pair.into_inner() {
+        let node = match p.as_rule() {
+            Rule::raw_text => {
+                text.push_str(p.as_str());
+                continue;
+            }
+            Rule::inner_comment | Rule::multiline_comment => continue,
+            Rule::statement => Some(TemplateNode::Statement(parse_command(first_inner(p)?)?)),
+            Rule::special => parse_special(p)?,
+            _ => return Err(unexpected(&p)),
+        };
+        push_raw_text(&mut nodes, &text);
+        text.clear();
+        // `{nil}` only separates the text on either side of it
+        nodes.extend(node);
+    }
+    push_raw_text(&mut nodes, &text);
+    Ok(nodes)
+}
+
+fn push_raw_text(nodes: &mut TemplateBlock, text: &str) {
+    let joined = join_lines(text);
+    if !joined.is_empty() {
+        nodes.push(TemplateNode::RawText(joined));
+    }
+}
+
+/// Applies Soy's line joining to a run of raw text (which is bordered on either side by
+/// a tag or the edge of the block). Whitespace at the start and end of each line is
+/// removed, blank lines are dropped, and the remaining lines are joined with a single
+/// space, unless the join is next to an HTML tag. Text on the same line as a neighbouring
+/// tag stays as is, including whitespace, but a join next to a soy tag adds no space.
+fn join_lines(text: &str) -> String {
+    let is_space = &[' ', '\t'][..];
+    let mut lines = text.split(&['\n', '\r'][..]).peekable();
+    let first = match lines.next() {
+        Some(first) if lines.peek().is_some() => first.trim_end_matches(is_space),
+        _ => return text.to_owned(),
+    };
+    let mut joined = first.to_owned();
+    while let Some(line) = lines.next() {
+        let line = if lines.peek().is_some() {
+            line.trim_matches(is_space)
+        } else {
+            line.trim_start_matches(is_space)
+        };
+        if line.is_empty() {
+            continue;
+        }
+        if !joined.is_empty() && !joined.ends_with('>') && !line.starts_with('<') {
+            joined.push(' ');
+        }
+        joined.push_str(line);
+    }
+    joined
+}
+
+/// Parses a special character command. `{nil}` produces no node at all.
+fn parse_special(pair: Pair<Rule>) -> Result<Option<TemplateNode>, CompileError> {
+    let p = first_inner(pair)?;
+    let c = match p.as_rule() {
+        Rule::special_sp => ' ',
+        Rule::special_nil => return Ok(None),
+        Rule::special_lb => '{',
+        Rule::special_rb => '}',
+        Rule::special_return => '\r',
+        Rule::special_newline => '\n',
+        Rule::special_tab => '\t',
+        Rule::special_nbsp => '\u{a0}',
+        Rule::special_unicode => {
+            let span = p.as_span();
+            let hex = first_inner(p)?;
+            u32::from_str_radix(hex.as_str(), 16)
+                .ok()
+                .and_then(std::char::from_u32)
+                .ok_or_else(|| {
+                    error_at(
+                        CompileErrorKind::InvalidCharacter(hex.as_str().to_owned()),
+                        &span,
+                    )
+                })?
+        }
+        _ => return Err(unexpected(&p)),
+    };
+    Ok(Some(TemplateNode::Special(c.to_string())))
+}
+
+fn parse_expression(pair: Pair<Rule>) -> Result<Expression, CompileError> {
+    let span = pair.as_span();
+    Ok(match pair.as_rule() {
+        Rule::expression => {
+            let mut p = pair.into_inner();
+            let condition = parse_expression(next_inner(&mut p, &span, "expression")?)?;
+            match p.next() {
+                Some(branches) => {
+                    let span = branches.as_span();
+                    let mut p = branches.into_inner();
+                    Expression::TernaryOperation {
+                        condition: Box::new(condition),
+                        if_true: Box::new(parse_expression(next_inner(
+                            &mut p,
+                            &span,
+                            "expression",
+                        )?)?),
+                        if_false: Box::new(parse_expression(next_inner(
+                            &mut p,
+                            &span,
+                            "expression",
+                        )?)?),
+                    }
+                }
+                None => condition,
+            }
+        }
+        Rule::null => Expression::Null,
+        Rule::boolean => Expression::Boolean(parse_number(&pair)?),
+        Rule::integer => Expression::Integer(parse_number(&pair)?),
+        Rule::float => Expression::Float(parse_number(&pair)?),
+        Rule::string => Expression::String(parse_quoted_string(first_inner(pair)?)?),
+        Rule::operation => {
+            let mut p = pair.into_inner();
+            let lhs = parse_expression(next_inner(&mut p, &span, "expression")?)?;
+            let mut ops = Vec::new();
+            while let Some(op) = p.next() {
+                let op = parse_binary_operator(&op)?;
+                let rhs = parse_expression(next_inner(&mut p, &span, "expression")?)?;
+                ops.push((op, rhs));
+            }
+            build_binary_operation(lhs, ops)
+        }
+        Rule::unary_operation => {
+            let mut p = pair.into_inner();
+            let op = next_inner(&mut p, &span, "operator")?;
+            Expression::UnaryOperation {
+                op: match op.as_rule() {
+                    Rule::op_minus => UnaryOperator::Minus,
+                    Rule::op_not => UnaryOperator::Not,
+                    _ => return Err(unexpected(&op)),
+                },
+                rhs: Box::new(parse_expression(next_inner(&mut p, &span, "expression")?)?),
+            }
+        }
+        Rule::reference => parse_reference(pair)?,
+        Rule::global_reference => Expression::GlobalReference(pair.as_str().to_owned()),
+        Rule::function => {
+            let mut p = pair.into_inner();
+            let name = next_inner(&mut p, &span, "function name")?
+                .as_str()
+                .to_owned();
+            let parameters = match p.next() {
+                Some(params) => params
+                    .into_inner()
+                    .map(parse_expression)
+                    .collect::<Result<_, _>>()?,
+                None => vec![],
+            };
+            Expression::Function { name, parameters }
+        }
+        Rule::list_literal => Expression::List(
+            pair.into_inner()
+                .map(parse_expression)
+                .collect::<Result<_, _>>()?,
+        ),
+        Rule::map_literal => {
+            let mut map = HashMap::new();
+            for entry in pair.into_inner() {
+                let span = entry.as_span();
+                let mut p = entry.into_inner();
+                let quoted_key = first_inner(next_inner(&mut p, &span, "map key")?)?;
+                let key = parse_quoted_string(first_inner(quoted_key)?)?;
+                let value =
+                    parse_expression(first_inner(next_inner(&mut p, &span, "map value")?)?)?;
+                map.insert(key, value);
+            }
+            Expression::Map(map)
+        }
+        _ => return Err(unexpected(&pair)),
+    })
+}
+
+fn parse_reference(pair: Pair<Rule>) -> Result<Expression, CompileError> {
+    let span = pair.as_span();
+    let mut referent = None;
+    let mut references = Vec::new();
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::variable => {
+                referent = Some(Referent::Variable(first_inner(p)?.as_str().to_owned()))
+            }
+            Rule::injected_data => {
+                referent = Some(Referent::Injected(first_inner(p)?.as_str().to_owned()))
+            }
+            Rule::bracketed_reference => {
+                references.push(Reference::Bracketed(parse_expression(first_inner(p)?)?))
+            }
+            Rule::dotted_reference => {
+                references.push(Reference::Dotted(parse_reference_key(first_inner(p)?)?))
+            }
+            Rule::question_bracketed_reference => references.push(Reference::QuestionBracketed(
+                parse_expression(first_inner(p)?)?,
+            )),
+            Rule::question_dotted_reference => references.push(Reference::QuestionDotted(
+                parse_reference_key(first_inner(p)?)?,
+            )),
+            _ => return Err(unexpected(&p)),
+        }
+    }
+    Ok(Expression::DataReference {
+        referent: referent.ok_or_else(|| missing(&span, "variable"))?,
+        references,
+    })
+}
+
+fn parse_reference_key(pair: Pair<Rule>) -> Result<ReferenceKey, CompileError> {
+    let p = first_inner(pair)?;
+    Ok(match p.as_rule() {
+        Rule::whole_number => ReferenceKey::Number(parse_number(&p)?),
+        Rule::name => ReferenceKey::Name(p.as_str().to_owned()),
+        _ => return Err(unexpected(&p)),
+    })
+}
+
+fn parse_binary_operator(pair: &Pair<Rule>) -> Result<BinaryOperator, CompileError> {
+    Ok(match pair.as_rule() {
+        Rule::op_minus => BinaryOperator::Minus,
+        Rule::op_plus => BinaryOperator::Plus,
+        Rule::op_times => BinaryOperator::Times,
+        Rule::op_divide => BinaryOperator::Divide,
+        Rule::op_modulo => BinaryOperator::Modulo,
+        Rule::op_less => BinaryOperator::Less,
+        Rule::op_le => BinaryOperator::LessEquals,
+        Rule::op_greater => BinaryOperator::Greater,
+        Rule::op_ge => BinaryOperator::GreaterEquals,
+        Rule::op_equals => BinaryOperator::Equals,
+        Rule::op_ne => BinaryOperator::NotEquals,
+        Rule::op_and => BinaryOperator::And,
+        Rule::op_or => BinaryOperator::Or,
+        Rule::op_elvis => BinaryOperator::Elvis,
+        _ => return Err(unexpected(pair)),
+    })
+}
+
+fn build_binary_operation(lhs: Expression, ops: Vec<(BinaryOperator, Expression)>) -> Expression {
+    use lazy_static::lazy_static;
+    use std::collections::HashMap;
+    lazy_static! {
+        static ref ORDER_OF_OPS: HashMap<BinaryOperator, u8> = vec![
+            (BinaryOperator::Times, 0),
+            (BinaryOperator::Divide, 0),
+            (BinaryOperator::Modulo, 0),
+            (BinaryOperator::Plus, 1),
+            (BinaryOperator::Minus, 1),
+            (BinaryOperator::Less, 2),
+            (BinaryOperator::Greater, 2),
+            (BinaryOperator::LessEquals, 2),
+            (BinaryOperator::GreaterEquals, 2),
+            (BinaryOperator::Equals, 3),
+            (BinaryOperator::NotEquals, 3),
+            (BinaryOperator::And, 4),
+            (BinaryOperator::Or, 5),
+            (BinaryOperator::Elvis, 6),
+        ]
+        .into_iter()
+        .collect();
+    }
+    let mut lhs = lhs;
+    let mut ops = ops.into_iter().map(Some).collect::<Vec<_>>();
+    while !ops.is_empty() {
+        let (index, _) = ops
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, val)| ORDER_OF_OPS.get(&val.as_ref().unwrap().0).unwrap_or(&7))
+            .unwrap();
+        let (op, rhs) = ops.remove(index).unwrap();
+        if index == 0 {
+            lhs = Expression::BinaryOperation {
+                lhs: Box::new(lhs),
+                op,
+                rhs: Box::new(rhs),
+            };
+        } else {
+            let (left_op, left_expr) = ops[index - 1].take().unwrap();
+            ops[index - 1] = Some((
+                left_op,
+                Expression::BinaryOperation {
+                    lhs: Box::new(left_expr),
+                    op,
+                    rhs: Box::new(rhs),
+                },
+            ));
+        }
+    }
+    lhs
+}
+
+fn parse_quoted_string(pair: Pair<Rule>) -> Result<String, CompileError> {
+    Ok(first_inner(pair)?.as_str().to_owned())
+}
+
+/// Parses a numeric (or boolean) literal, which may still fail if it is out of range.
+fn parse_number<T: std::str::FromStr>(pair: &Pair<Rule>) -> Result<T, CompileError> {
+    pair.as_str().parse().map_err(|_| {
+        error_at(
+            CompileErrorKind::InvalidNumber(pair.as_str().to_owned()),
+            &pair.as_span(),
+        )
+    })
+}
+
+fn parse_command(pair: Pair<Rule>) -> Result<Command, CompileError> {
+    let span = pair.as_span();
+    Ok(match pair.as_rule() {
+        Rule::msg_statement => parse_msg(pair)?,
+        Rule::literal_statement => Command::Literal(first_inner(pair)?.as_str().to_owned()),
+        Rule::if_statement => parse_if(pair)?,
+        Rule::css_statement => {
+            let mut base = None;
+            let mut name = None;
+            for p in pair.into_inner() {
+                match p.as_rule() {
+                    Rule::css_tag => {}
+                    Rule::css_base => base = Some(parse_expression(first_inner(p)?)?),
+                    Rule::css_name => name = Some(p.as_str().to_owned()),
+                    _ => return Err(unexpected(&p)),
+                }
+            }
+            Command::Css {
+                base,
+                name: name.ok_or_else(|| missing(&span, "css name"))?,
+            }
+        }
+        Rule::xid_statement => {
+            let mut p = pair.into_inner();
+            next_inner(&mut p, &span, "{xid")?;
+            Command::Xid(next_inner(&mut p, &span, "xid name")?.as_str().to_owned())
+        }
+        Rule::call_statement => Command::Call(parse_call(pair)?),
+        Rule::print_statement => {
+            let mut p = pair.into_inner();
+            next_inner(&mut p, &span, "{print")?;
+            let expression = parse_expression(next_inner(&mut p, &span, "expression")?)?;
+            let mut next = next_inner(&mut p, &span, "print directives")?;
+            let mut phname = None;
+            if next.as_rule() == Rule::print_phname {
+                let name_span = next.as_span();
+                let name = parse_quoted_string(first_inner(next)?)?;
+                if !msg::is_valid_phname(&name) {
+                    return Err(invalid_attribute("phname".to_owned(), name, &name_span));
+                }
+                phname = Some(name);
+                next = next_inner(&mut p, &span, "print directives")?;
+            }
+            let mut directives = vec![];
+            for pd in next.into_inner() {
+                let span = pd.as_span();
+                let mut pd = pd.into_inner();
+                directives.push(PrintDirective {
+                    name: next_inner(&mut pd, &span, "directive name")?
+                        .as_str()
+                        .to_owned(),
+                    arguments: match pd.next() {
+                        None => vec![],
+                        Some(args) => args
+                            .into_inner()
+                            .map(parse_expression)
+                            .collect::<Result<_, _>>()?,
+                    },
+                });
+            }
+            Command::Print {
+                expression,
+                directives,
+                phname,
+            }
+        }
+        _ => return Err(unexpected(&pair)),
+    })
+}
+
+/// Parses a call, leaving a partial target name (e.g. `.foo`) as is, to be resolved against
+/// the file's namespace by `parse_file_template`.
+fn parse_call(pair: Pair<Rule>) -> Result<Call, CompileError> {
+    let span = pair.as_span();
+    let mut target = None;
+    let mut data = None;
+    let mut params = vec![];
+    let mut phname = None;
+    let mut location = Location::default();
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::call_tag => {}
+            Rule::call_target => {
+                // From the start of the tag to the end of the name, e.g. `{call .foo`
+                location = Location(span_location(
+                    &span.start_pos().span(&p.as_span().end_pos()),
+                ));
+                target = Some(p.as_str().to_owned());
+            }
+            Rule::call_data => {
+                let p = first_inner(p)?;
+                data = Some(match p.as_rule() {
+                    Rule::call_data_all => CallData::All,
+                    _ => CallData::Expression(parse_expression(p)?),
+                });
+            }
+            Rule::attributes => {
+                for attr in p.into_inner() {
+                    let span = attr.as_span();
+                    let (name, value) = parse_attribute(attr)?;
+                    match name.as_str() {
+                        "phname" if msg::is_valid_phname(&value) => phname = Some(value),
+                        "phname" => return Err(invalid_attribute(name, value, &span)),
+                        _ => return Err(error_at(CompileErrorKind::UnknownAttribute(name), &span)),
+                    }
+                }
+            }
+            Rule::call_param => params.push(parse_call_param(p)?),
+            Rule::inner_comment | Rule::multiline_comment => {}
+            _ => return Err(unexpected(&p)),
+        }
+    }
+    Ok(Call {
+        target: target.ok_or_else(|| missing(&span, "template name"))?,
+        data,
+        params,
+        phname,
+        location,
+    })
+}
+
+fn parse_call_param(pair: Pair<Rule>) -> Result<CallParam, CompileError> {
+    let span = pair.as_span();
+    let mut p = pair.into_inner();
+    next_inner(&mut p, &span, "{param")?;
+    let name = next_inner(&mut p, &span, "param name")?.as_str().to_owned();
+    let value = next_inner(&mut p, &span, "param value")?;
+    let value = match value.as_rule() {
+        Rule::param_value => CallParamValue::Expression(parse_expression(first_inner(value)?)?),
+        Rule::param_block => {
+            let span = value.as_span();
+            let mut p = value.into_inner();
+            let mut kind = ContentKind::Html;
+            for attr in next_inner(&mut p, &span, "attributes")?.into_inner() {
+                let span = attr.as_span();
+                let (name, value) = parse_attribute(attr)?;
+                match name.as_str() {
+                    "kind" => {
+                        kind = parse_content_kind(&value)
+                            .ok_or_else(|| invalid_attribute(name.clone(), value.clone(), &span))?
+                    }
+                    _ => return Err(error_at(CompileErrorKind::UnknownAttribute(name), &span)),
+                }
+            }
+            CallParamValue::Block {
+                kind,
+                block: parse_template_block(next_inner(&mut p, &span, "param block")?)?,
+            }
+        }
+        _ => return Err(unexpected(&value)),
+    };
+    Ok(CallParam { name, value })
+}
+
+fn parse_if(pair: Pair<Rule>) -> Result<Command, CompileError> {
+    let span = pair.as_span();
+    let mut if_block = None;
+    let mut else_ifs = vec![];
+    let mut else_block = None;
+    for p in pair.into_inner() {
+        let span = p.as_span();
+        match p.as_rule() {
+            Rule::if_block => {
+                let mut p = p.into_inner();
+                let expression = parse_expression(next_inner(&mut p, &span, "condition")?)?;
+                let block = parse_template_block(next_inner(&mut p, &span, "block")?)?;
+                if_block = Some(ConditionalBlock { expression, block });
+            }
+            Rule::elseif_block => {
+                let mut p = p.into_inner();
+                next_inner(&mut p, &span, "{elseif")?;
+                let expression = parse_expression(next_inner(&mut p, &span, "condition")?)?;
+                let block = parse_template_block(next_inner(&mut p, &span, "block")?)?;
+                else_ifs.push(ConditionalBlock { expression, block });
+            }
+            Rule::else_block => {
+                let mut p = p.into_inner();
+                next_inner(&mut p, &span, "{else")?;
+                else_block = Some(parse_template_block(next_inner(&mut p, &span, "block")?)?);
+            }
+            Rule::if_open => {} // ignored
+            _ => return Err(unexpected(&p)),
+        }
+    }
+    Ok(Command::If {
+        if_block: if_block.ok_or_else(|| missing(&span, "if block"))?,
+        else_ifs,
+        else_block,
+    })
+}
+
+fn parse_msg(pair: Pair<Rule>) -> Result<Command, CompileError> {
+    let span = pair.as_span();
+    let mut it = pair.into_inner();
+    next_inner(&mut it, &span, "{msg")?;
+    let mut desc = None;
+    let mut meaning = None;
+    for attr in next_inner(&mut it, &span, "attributes")?.into_inner() {
+        let span = attr.as_span();
+        let (name, value) = parse_attribute(attr)?;
+        match name.as_str() {
+            "desc" => desc = Some(value),
+            "meaning" => meaning = Some(value),
+            "hidden" => {}
+            _ => return Err(error_at(CompileErrorKind::UnknownAttribute(name), &span)),
+        }
+    }
+    let body = parse_msg_body(next_inner(&mut it, &span, "plural or block")?)?;
+    let message = Message::new(&body);
+    if let Some(name) = message.conflicting_name() {
+        return Err(invalid_attribute(
+            "phname".to_owned(),
+            name.to_owned(),
+            &span,
+        ));
+    }
+    let id = msg::compute_id(&message.parts, meaning.as_deref());
+    Ok(Command::Msg {
+        id,
+        desc,
+        meaning,
+        body,
+    })
+}
+
+fn parse_msg_body(p: Pair<Rule>) -> Result<MsgBody, CompileError> {
+    Ok(match p.as_rule() {
+        Rule::template_block => MsgBody::Block(parse_template_block(p)?),
+        Rule::msg_plural => {
+            let span = p.as_span();
+            let mut expr = None;
+            let mut offset = 0;
+            let mut cases = vec![];
+            let mut default = None;
+            for p in p.into_inner() {
+                match p.as_rule() {
+                    Rule::expression => expr = Some(parse_expression(p)?),
+                    Rule::plural_offset => {
+                        let span = p.as_span();
+                        let value = parse_quoted_string(first_inner(p)?)?;
+                        offset = match value.parse() {
+                            Ok(offset) if offset > 0 => offset,
+                            _ => return Err(invalid_attribute("offset".to_owned(), value, &span)),
+                        };
+                    }
+                    Rule::plural_case => cases.push(parse_plural_case(p)?),
+                    Rule::plural_default => default = Some(parse_msg_body(first_inner(p)?)?),
+                    _ => {}
+                }
+            }
+            let mut default = default.ok_or_else(|| missing(&span, "{default}"))?;
+            for body in cases
+                .iter_mut()
+                .map(|case: &mut PluralCase| &mut case.body)
+                .chain(std::iter::once(&mut default))
+            {
+                replace_remainder(body, offset);
+            }
+            MsgBody::Plural {
+                expression: expr.ok_or_else(|| missing(&span, "plural expression"))?,
+                offset,
+                cases,
+                default: Box::new(default),
+            }
+        }
+        Rule::msg_select => {
+            let span = p.as_span();
+            let mut expr = None;
+            let mut cases = vec![];
+            let mut default = None;
+            for p in p.into_inner() {
+                match p.as_rule() {
+                    Rule::expression => expr = Some(parse_expression(p)?),
+                    Rule::select_case => cases.push(parse_select_case(p)?),
+                    Rule::select_default => default = Some(parse_msg_body(first_inner(p)?)?),
+                    _ => {}
+                }
+            }
+            MsgBody::Select {
+                expression: expr.ok_or_else(|| missing(&span, "select expression"))?,
+                cases,
+                default: Box::new(default.ok_or_else(|| missing(&span, "{default}"))?),
+            }
+        }
+        _ => return Err(unexpected(&p)),
+    })
+}
+
+/// Replaces `{remainder($n)}` in a plural's cases with `{$n - offset}`.
+fn replace_remainder(body: &mut MsgBody, offset: i64) {
+    match body {
+        MsgBody::Block(block) => {
+            for node in block {
+                if let TemplateNode::Statement(Command::Print { expression, .. }) = node {
+                    if let Expression::Function { name, parameters } = expression {
+                        if name == "remainder" && parameters.len() == 1 {
+                            *expression = Expression::BinaryOperation {
+                                lhs: Box::new(parameters.remove(0)),
+                                op: BinaryOperator::Minus,
+                                rhs: Box::new(Expression::Integer(offset)),
+                            };
+                        }
+                    }
+                }
+            }
+        }
+        MsgBody::Select { cases, default, .. } => {
+            for case in cases {
+                replace_remainder(&mut case.body, offset);
+            }
+            replace_remainder(default, offset);
+        }
+        // A nested plural has its own offset
+        MsgBody::Plural { .. } => {}
+    }
+}
+
+fn parse_plural_case(pair: Pair<Rule>) -> Result<PluralCase, CompileError> {
+    let span = pair.as_span();
+    let mut expr = None;
+    let mut body = None;
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::expression => {
+                let span = p.as_span();
+                let expression = parse_expression(p)?;
+                match &expression {
+                    Expression::Integer(_) => {}
+                    // A plural category, e.g. `{case 'few'}`
+                    Expression::String(category)
+                        if PluralCategory::from_name(category).is_some() => {}
+                    _ => {
+                        return Err(invalid_attribute(
+                            "case".to_owned(),
+                            span.as_str().to_owned(),
+                            &span,
+                        ))
+                    }
+                }
+                expr = Some(expression);
+            }
+            _ => body = Some(parse_msg_body(p)?),
+        };
+    }
+    Ok(PluralCase {
+        expression: expr.ok_or_else(|| missing(&span, "case expression"))?,
+        body: body.ok_or_else(|| missing(&span, "case body"))?,
+    })
+}
+
+fn parse_select_case(pair: Pair<Rule>) -> Result<SelectCase, CompileError> {
+    let span = pair.as_span();
+    let mut value = None;
+    let mut body = None;
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::expression => {
+                let span = p.as_span();
+                match parse_expression(p)? {
+                    Expression::String(s) => value = Some(s),
+                    _ => {
+                        return Err(invalid_attribute(
+                            "case".to_owned(),
+                            span.as_str().to_owned(),
+                            &span,
+                        ))
+                    }
+                }
+            }
+            _ => body = Some(parse_msg_body(p)?),
+        };
+    }
+    Ok(SelectCase {
+        value: value.ok_or_else(|| missing(&span, "case value"))?,
+        body: body.ok_or_else(|| missing(&span, "case body"))?,
+    })
+}
+
+fn error_at(kind: CompileErrorKind, span: &Span) -> CompileError {
+    CompileError {
+        kind,
+        location: Some(Box::new(span_location(span))),
+        cause: None,
+    }
+}
+
+/// The error for input that the grammar accepted but the parser doesn't understand.
+fn unexpected(pair: &Pair<Rule>) -> CompileError {
+    error_at(
+        CompileErrorKind::Malformed(format!("unexpected {:?}", pair.as_rule())),
+        &pair.as_span(),
+    )
+}
+
+fn missing(span: &Span, expected: &str) -> CompileError {
+    error_at(
+        CompileErrorKind::Malformed(format!("expecting {}", expected)),
+        span,
+    )
+}
+
+fn first_inner(pair: Pair<Rule>) -> Result<Pair<Rule>, CompileError> {
+    let span = pair.as_span();
+    let rule = pair.as_rule();
+    pair.into_inner()
+        .next()
+        .ok_or_else(|| missing(&span, &format!("contents of {:?}", rule)))
+}
+
+fn next_inner<'i>(
+    pairs: &mut Pairs<'i, Rule>,
+    span: &Span,
+    expected: &str,
+) -> Result<Pair<'i, Rule>, CompileError> {
+    pairs.next().ok_or_else(|| missing(span, expected))
+}
@@ -0,0 +1,305 @@
+use super::*;
+
+fn first_error(filename: Option<&str>, input: &str) -> CompileError {
+    let mut diagnostics = Diagnostics::default();
+    assert!(parse_file(filename, input, &mut diagnostics).is_none());
+    diagnostics.into_iter().next().expect("should fail")
+}
+
+#[test]
+fn test_parse_error_location() {
+    let input = "{namespace foo}\n/** */\n{template .bar}\n  {if}{/if}\n{/template}";
+    let err = first_error(Some("foo.soy"), input);
+    let location = err.location.as_ref().expect("should have a location");
+    assert_eq!(location.filename, Some("foo.soy".to_owned()));
+    assert_eq!((location.line_number, location.column), (4, 6));
+    assert_eq!(location.snippet, Some("  {if}{/if}".to_owned()));
+    assert!(err.cause.is_none());
+}
+
+#[test]
+fn test_error_display() {
+    let input = "{namespace foo}\n/** */\n{template .bar}\n  {if}{/if}\n{/template}";
+    let err = first_error(Some("foo.soy"), input);
+    let message = match &err.kind {
+        CompileErrorKind::Parse(message) => message.clone(),
+        kind => panic!("unexpected error kind: {:?}", kind),
+    };
+    assert_eq!(
+        format!("{}", err),
+        format!(
+            "error[E0001]: {}\n --> foo.soy:4:6\n  |\n4 |   {{if}}{{/if}}\n  |      ^",
+            message
+        )
+    );
+}
+
+#[test]
+fn test_location_display() {
+    let location = TemplateLocation {
+        filename: None,
+        template_name: Some("foo.bar".to_owned()),
+        line_number: 12,
+        column: 3,
+        length: 4,
+        snippet: Some("\t {$baz}".to_owned()),
+    };
+    assert_eq!(
+        format!("{}", location),
+        "  --> <input>:12:3 (in foo.bar)\n   |\n12 | \t {$baz}\n   | \t ^^^^"
+    );
+}
+
+#[test]
+fn test_parameter_diagnostics() {
+    let input = "{namespace foo}\n/**\n * @param bar\n * @param? baz\n */\n{template .qux}\n  {$bar}{$quux}\n{/template}";
+    let mut diagnostics = Diagnostics::default();
+    parse_file(Some("foo.soy"), input, &mut diagnostics).expect("should parse");
+    let diagnostics: Vec<_> = diagnostics
+        .iter()
+        .map(|d| {
+            let location = d.location.as_ref().unwrap();
+            (
+                d.kind.code(),
+                d.severity(),
+                location.line_number,
+                location.column,
+                location.template_name.as_ref().unwrap().as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        diagnostics,
+        vec![
+            ("E0002", Severity::Error, 7, 10, "foo.qux"),
+            ("W0001", Severity::Warning, 4, 12, "foo.qux"),
+        ]
+    );
+}
+
+#[test]
+fn test_unknown_function_and_directive() {
+    let input = "{namespace foo}\n/** */\n{template .bar}\n  {frobnicate(1)}{1 |frobnicate}\n\
+                 {msg desc=\"\"}{plural 1 offset=\"1\"}{default}{remainder(1)}{/plural}{/msg}\n\
+                 {remainder(1)}\n{/template}";
+    let mut diagnostics = Diagnostics::default();
+    parse_file(Some("foo.soy"), input, &mut diagnostics).expect("should parse");
+    let diagnostics: Vec<_> = diagnostics
+        .iter()
+        .map(|d| {
+            let location = d.location.as_ref().unwrap();
+            (
+                d.to_string().lines().next().unwrap().to_owned(),
+                location.line_number,
+                location.column,
+                location.template_name.as_ref().unwrap().as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        diagnostics,
+        vec![
+            (
+                "error[E0014]: Unknown function: frobnicate".to_owned(),
+                4,
+                4,
+                "foo.bar"
+            ),
+            (
+                "error[E0015]: Unknown print directive: |frobnicate".to_owned(),
+                4,
+                21,
+                "foo.bar"
+            ),
+            (
+                "error[E0014]: Unknown function: remainder".to_owned(),
+                6,
+                2,
+                "foo.bar"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_errors_in_every_template_are_reported() {
+    let input = "{namespace foo}\n\
+                 /** */{template .a}{99999999999999999999}{/template}\n\
+                 /** */{template bar.b}{/template}\n\
+                 /** */{template .c}c{/template}\n\
+                 /** */{template .d}{css %d}{/template}";
+    let mut diagnostics = Diagnostics::default();
+    let file = parse_file(None, input, &mut diagnostics).expect("should parse");
+    let errors: Vec<_> = diagnostics
+        .errors()
+        .map(|e| (e.kind.code(), e.location.as_ref().unwrap().line_number))
+        .collect();
+    assert_eq!(errors, vec![("E0007", 2), ("E0012", 3), ("E0013", 5)]);
+    let names: Vec<_> = file.templates.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, vec!["c"]);
+}
+
+#[test]
+fn test_parse_returns_first_error() {
+    let input = "{namespace foo}\n/** */\n{template .bar}{$a}{$b}{/template}";
+    match parse(input).expect_err("should fail").kind {
+        CompileErrorKind::UndeclaredParameter(name) => assert_eq!(name, "a"),
+        kind => panic!("unexpected error kind: {:?}", kind),
+    }
+}
+
+#[test]
+fn test_malformed_input_errors() {
+    let cases: &[(&str, &str, (usize, usize))] = &[
+        ("{template bar.baz}{/template}", "E0012", (3, 11)),
+        ("{template foo.bar.baz}{/template}", "E0012", (3, 11)),
+        (
+            "{template .bar}{99999999999999999999}{/template}",
+            "E0007",
+            (3, 17),
+        ),
+        (
+            "{template .bar}{$a.99999999999999999999}{/template}",
+            "E0007",
+            (3, 20),
+        ),
+        ("{template .bar}{\\uD800}{/template}", "E0009", (3, 16)),
+        (
+            "{template .bar}<b>{css %baz}</b>{/template}",
+            "E0013",
+            (3, 19),
+        ),
+        ("{template .bar}{\\u110000}{/template}", "E0009", (3, 16)),
+        ("{template .bar foo=\"bar\"}{/template}", "E0010", (3, 16)),
+        ("{template .bar kind=\"xml\"}{/template}", "E0011", (3, 16)),
+        (
+            "{template .bar visibility=\"protected\"}{/template}",
+            "E0011",
+            (3, 16),
+        ),
+        (
+            "{template .bar}{call .baz foo=\"1\" /}{/template}",
+            "E0010",
+            (3, 27),
+        ),
+        (
+            "{template .bar}{call .baz}{param x kind=\"xml\"}{/param}{/call}{/template}",
+            "E0011",
+            (3, 36),
+        ),
+    ];
+    for (template, code, position) in cases {
+        let input = format!("{{namespace foo}}\n/** */\n{}", template);
+        let err = parse(&input).expect_err("should fail");
+        let location = err.location.as_ref().expect("should have a location");
+        assert_eq!(err.kind.code(), *code, "{}", input);
+        assert_eq!(
+            (location.line_number, location.column),
+            *position,
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_namespace_attribute_errors() {
+    let cases = &[
+        ("{namespace foo kind=\"text\"}", "E0010"),
+        ("{namespace foo autoescape=\"yes\"}", "E0011"),
+    ];
+    for (namespace, code) in cases {
+        let input = format!("{}\n/** */\n{{template .bar}}{{/template}}", namespace);
+        let err = parse(&input).expect_err("should fail");
+        assert_eq!(err.kind.code(), *code, "{}", input);
+        let location = err.location.expect("should have a location");
+        assert_eq!((location.line_number, location.column), (1, 16));
+    }
+}
+
+#[test]
+fn test_nesting_depth() {
+    let nested = |open: &str, close: &str, depth| {
+        format!(
+            "{{namespace foo}}\n/** */\n{{template .bar}}{}{}{{/template}}",
+            open.repeat(depth),
+            close.repeat(depth)
+        )
+    };
+    assert!(parse(&nested("{if true}", "{/if}", 50)).is_ok());
+    assert!(parse(&nested(
+        "{if true}{msg}{plural 1}{case 1}",
+        "{default}{/plural}{/msg}{/if}",
+        30
+    ))
+    .is_ok());
+    assert!(
+        parse(&nested("(", ")", 5000)).is_ok(),
+        "raw text isn't counted"
+    );
+    for input in &[
+        nested("{if true}", "{/if}", 5000),
+        nested("{(", ")}", 5000),
+        nested("{[", "]}", 5000),
+        nested("{if true}", "{/if}", 90).replacen(
+            "{/if}",
+            "{((((((((((((((((1))))))))))))))))}{/if}",
+            1,
+        ),
+        // Chains of operators nest as deeply as they are long
+        nested("{", "}", 1).replace("{}", &format!("{{{}1}}", "-".repeat(1000))),
+        nested("{", "}", 1).replace("{}", &format!("{{{}true}}", "not ".repeat(1000))),
+        nested("{", "}", 1).replace("{}", &format!("{{1{}}}", " + 1".repeat(1000))),
+        nested("{", "}", 1).replace("{}", &format!("{{{}1}}", "true ? 1 : ".repeat(1000))),
+    ] {
+        match parse(input).expect_err("should fail").kind {
+            CompileErrorKind::TooDeeplyNested(_) => {}
+            kind => panic!("unexpected error kind: {:?}", kind),
+        }
+    }
+}
+
+#[test]
+fn test_nesting_limit_fits_small_stacks() {
+    // The deepest chains and brackets accepted, parsed on a thread with the default stack
+    // size for spawned threads
+    let expressions = [
+        "-".repeat(98) + "1",
+        "not ".repeat(98) + "true",
+        "-(".repeat(33) + "1" + &")".repeat(33),
+        "(".repeat(49) + "1" + &")".repeat(49),
+    ];
+    std::thread::Builder::new()
+        .stack_size(2 << 20)
+        .spawn(move || {
+            for expression in &expressions {
+                let input = format!(
+                    "{{namespace foo}}\n/** */\n{{template .bar}}{{{}}}{{/template}}",
+                    expression
+                );
+                assert!(parse(&input).is_ok(), "{}", expression);
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn test_no_exponential_backtracking() {
+    // Each of these used to double the parse time with every level of nesting.
+    let parse_expression = |expression: String| {
+        parse(&format!(
+            "{{namespace foo}}\n/** */\n{{template .bar}}{{{}}}{{/template}}",
+            expression
+        ))
+    };
+    assert!(parse_expression("(".repeat(45) + "1" + &")".repeat(45)).is_ok());
+    match parse_expression("(".repeat(45) + "1")
+        .expect_err("should fail")
+        .kind
+    {
+        CompileErrorKind::Parse(_) => {}
+        kind => panic!("unexpected error kind: {:?}", kind),
+    }
+}
@@ -8,13 +8,13 @@ macro_rules! assert_matches {
                 .expect(&format!("{:?} should parse:\n{:?}, ", $rule, $input))
                 .last()
                 .unwrap()
-                .into_span()
+                .as_span()
                 .end(),
             $input.len(),
             "{:?} should consume all input: {:?}",
             $rule,
             $input
-        );
+        )
     };
 }
 
@@ -36,7 +36,7 @@ macro_rules! assert_fails {
             "{:?} should fail: {:?}",
             $reason,
             $input
-        );
+        )
     };
 }
 
@@ -101,6 +101,12 @@ fn test_specials() {
     assert_matches!(special_return, "{\\r}");
     assert_matches!(special_newline, "{\\n}");
     assert_matches!(special_tab, "{\\t}");
+    assert_matches!(special_nbsp, "{nbsp}");
+    assert_matches!(special_unicode, "{\\u00a0}");
+    assert_matches!(special_unicode, "{\\u10FFFF}");
+    assert_fails!(special_unicode, "{\\u00}", "too few digits");
+    assert_fails!(special_unicode, "{\\u00g0}", "not hex");
+    assert_fails!(special_unicode, "{\\u1234567}", "too many digits");
 }
 
 #[test]
@@ -213,6 +219,42 @@ fn test_plural() {
     );
 }
 
+#[test]
+fn test_css_and_xid() {
+    assert_matches!(css_statement, "{css foo}");
+    assert_matches!(css_statement, "{css foo-bar_baz }");
+    assert_matches!(css_statement, "{css %foo}");
+    assert_matches!(css_statement, "{css $base, foo}");
+    assert_matches!(css_statement, "{css $a.b ,foo-bar}");
+    assert_fails!(css_statement, "{css}", "missing name");
+    assert_fails!(css_statement, "{cssfoo}", "missing space");
+    assert_matches!(xid_statement, "{xid foo.bar-baz}");
+    assert_fails!(xid_statement, "{xid $foo}", "xids are names");
+    assert_matches!(print_statement, "{cssClass}");
+}
+
+#[test]
+fn test_call() {
+    assert_matches!(call_statement, "{call .foo /}");
+    assert_matches!(call_statement, "{call foo.bar data=\"all\"/}");
+    assert_matches!(call_statement, "{call .foo data=\"$bar.baz\"}{/call}");
+    assert_matches!(
+        call_statement,
+        "{call .foo}\n  {param bar: 1 + $x /}\n  // a comment\n  {param baz kind=\"text\"}{$x}{/param}\n{/call}"
+    );
+    assert_fails!(call_statement, "{call}{/call}", "missing template name");
+    assert_fails!(
+        call_statement,
+        "{call .foo}bar{param baz: 1 /}{/call}",
+        "text between params"
+    );
+    assert_fails!(
+        call_statement,
+        "{call .foo}{param bar /}{/call}",
+        "param without a value"
+    );
+}
+
 #[test]
 fn test_print() {
     assert_matches!(print_statement, "{$foo}");
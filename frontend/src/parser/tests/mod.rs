@@ -1,5 +1,8 @@
 pub use super::*;
 pub use crate::ast::*;
+pub use crate::error::*;
 
+mod errors;
 mod grammar;
 mod parser;
+mod whitespace;
@@ -0,0 +1,920 @@
+use super::{Rule::*, *};
+use pest::Parser;
+
+macro_rules! parse {
+    ($input:expr, ($rule:expr, $fn:ident)) => {
+        $fn(SoyParser::parse($rule, $input).unwrap().next().unwrap()).unwrap()
+    };
+}
+
+macro_rules! bin_op {
+    ($lhs:expr, $op:tt, $rhs:expr) => {
+        Expression::BinaryOperation {
+            lhs: Box::new($lhs),
+            op: BinaryOperator::$op,
+            rhs: Box::new($rhs),
+        }
+    };
+}
+
+macro_rules! variable {
+    ($name:expr) => {
+        Expression::DataReference {
+            referent: Referent::Variable($name.to_owned()),
+            references: vec![],
+        }
+    };
+}
+
+macro_rules! int {
+    ($val:expr) => {
+        Expression::Integer($val)
+    };
+}
+
+macro_rules! list {
+    ($($item:expr),*) => {
+        Expression::List(vec![$($item,)*])
+    };
+}
+
+macro_rules! map {
+    ($(($key:expr, $val:expr)),*) => {
+        Expression::Map(vec![$(($key.to_owned(), $val),)*].into_iter().collect())
+    };
+}
+
+macro_rules! raw_text {
+    ($text:expr) => {
+        TemplateNode::RawText($text.to_owned())
+    };
+}
+
+macro_rules! command {
+    ($command:expr) => {
+        TemplateNode::Statement($command)
+    };
+}
+
+#[test]
+fn test_soyfile() {
+    let cases = &[
+        (
+            "{namespace foo.baz}\n/** */{template foo.baz.bar}foo{/template}",
+            SoyFile {
+                namespace: Namespace {
+                    name: "foo.baz".to_owned(),
+                    attributes: NamespaceAttributes::default(),
+                },
+                delpackage: None,
+                aliases: vec![],
+                templates: vec![Template {
+                    name: "bar".to_owned(),
+                    attributes: TemplateAttributes::default(),
+                    body: vec![raw_text!("foo")],
+                    soydoc_params: vec![],
+                    location: Default::default(),
+                }],
+            },
+        ),
+        (
+            "{namespace foo}\n/** */{template .bar}foo{/template}",
+            SoyFile {
+                namespace: Namespace {
+                    name: "foo".to_owned(),
+                    attributes: NamespaceAttributes::default(),
+                },
+                delpackage: None,
+                aliases: vec![],
+                templates: vec![Template {
+                    name: "bar".to_owned(),
+                    attributes: TemplateAttributes::default(),
+                    body: vec![raw_text!("foo")],
+                    soydoc_params: vec![],
+                    location: Default::default(),
+                }],
+            },
+        ),
+        (
+            "// foo\n//bar\n\n{delpackage a}\n{namespace foo}\n{alias b}\n/** */{template .bar}foo{/template}",
+            SoyFile {
+                namespace: Namespace {
+                    name: "foo".to_owned(),
+                    attributes: NamespaceAttributes::default(),
+                },
+                delpackage: Some("a".to_owned()),
+                aliases: vec![Alias {
+                    from: "b".to_owned(),
+                    to: None,
+                }],
+                templates: vec![Template {
+                    name: "bar".to_owned(),
+                    attributes: TemplateAttributes::default(),
+                    body: vec![raw_text!("foo")],
+                    soydoc_params: vec![],
+                    location: Default::default(),
+                }],
+            },
+        ),
+    ];
+
+    cases.iter().for_each(|(input, expected)| {
+        assert_eq!(parse(input).unwrap(), *expected, "\n{}", input);
+    });
+}
+
+#[test]
+fn test_namespace() {
+    let cases = &[
+        (
+            "{namespace foo}\n",
+            Namespace {
+                name: "foo".to_owned(),
+                attributes: NamespaceAttributes::default(),
+            },
+        ),
+        (
+            "{namespace a.b.c autoescape='deprecated-contextual' requirecss=\"a.b, c\" cssbase=\"x\"}\n",
+            Namespace {
+                name: "a.b.c".to_owned(),
+                attributes: NamespaceAttributes {
+                    autoescape: Some(AutoescapeMode::Contextual),
+                    require_css: vec!["a.b".to_owned(), "c".to_owned()],
+                    css_base: Some("x".to_owned()),
+                },
+            },
+        ),
+    ];
+
+    cases.iter().for_each(|(input, expected)| {
+        assert_eq!(
+            parse!(input, (namespace, parse_namespace)),
+            *expected,
+            "\n{}",
+            input
+        );
+    });
+}
+
+#[test]
+fn test_alias() {
+    let cases = &[
+        (
+            "{alias foo.bar}\n",
+            Alias {
+                from: "foo.bar".to_owned(),
+                to: None,
+            },
+        ),
+        (
+            "{alias foo.bar as foobar}\n",
+            Alias {
+                from: "foo.bar".to_owned(),
+                to: Some("foobar".to_owned()),
+            },
+        ),
+    ];
+
+    cases.iter().for_each(|(input, expected)| {
+        assert_eq!(
+            parse!(input, (alias, parse_alias)),
+            *expected,
+            "\n{}",
+            input
+        );
+    });
+}
+
+#[test]
+fn test_expressions() {
+    let cases = &[
+        ("null", Expression::Null),
+        ("true", Expression::Boolean(true)),
+        ("57", int!(57)),
+        ("56.3", Expression::Float(56.3)),
+        ("4.1e27", Expression::Float(4.1e27)),
+        ("'foo'", Expression::String("foo".to_owned())),
+        ("$foo", variable!("foo")),
+        ("foobar", Expression::GlobalReference("foobar".to_owned())),
+        (
+            "$foo.bar",
+            Expression::DataReference {
+                referent: Referent::Variable("foo".to_owned()),
+                references: vec![Reference::Dotted(ReferenceKey::Name("bar".to_owned()))],
+            },
+        ),
+        (
+            "$ij.foo[3 * $baz]?.bar",
+            Expression::DataReference {
+                referent: Referent::Injected("foo".to_owned()),
+                references: vec![
+                    Reference::Bracketed(bin_op!(int!(3), Times, variable!("baz"))),
+                    Reference::QuestionDotted(ReferenceKey::Name("bar".to_owned())),
+                ],
+            },
+        ),
+        (
+            "5 * (4 - 3 / 1) * 2",
+            bin_op!(
+                bin_op!(
+                    int!(5),
+                    Times,
+                    bin_op!(int!(4), Minus, bin_op!(int!(3), Divide, int!(1)))
+                ),
+                Times,
+                int!(2)
+            ),
+        ),
+        (
+            "5 * $foo < 27 ? 'foo' : $baz + 'bar'",
+            Expression::TernaryOperation {
+                condition: Box::new(bin_op!(
+                    bin_op!(int!(5), Times, variable!("foo")),
+                    Less,
+                    int!(27)
+                )),
+                if_true: Box::new(Expression::String("foo".to_owned())),
+                if_false: Box::new(bin_op!(
+                    variable!("baz"),
+                    Plus,
+                    Expression::String("bar".to_owned())
+                )),
+            },
+        ),
+        (
+            "3 / -$bar",
+            bin_op!(
+                int!(3),
+                Divide,
+                Expression::UnaryOperation {
+                    op: UnaryOperator::Minus,
+                    rhs: Box::new(variable!("bar"))
+                }
+            ),
+        ),
+        (
+            "[4,$foo, 5*7]",
+            list!(int!(4), variable!("foo"), bin_op!(int!(5), Times, int!(7))),
+        ),
+        (
+            "['foo': 4, 'bar': [5, $baz]]",
+            map!(("foo", int!(4)), ("bar", list!(int!(5), variable!("baz")))),
+        ),
+        (
+            "foobar(5, $baz * 2)",
+            Expression::Function {
+                name: "foobar".to_owned(),
+                parameters: vec![int!(5), bin_op!(variable!("baz"), Times, int!(2))],
+            },
+        ),
+    ];
+
+    cases.iter().for_each(|(input, expected)| {
+        assert_eq!(
+            parse!(input, (expression, parse_expression)),
+            *expected,
+            "\n{}",
+            input
+        );
+    });
+}
+
+#[test]
+fn test_specials() {
+    let chars = |s: &str| Some(TemplateNode::Special(s.to_owned()));
+    let cases = &[
+        ("{sp}", chars(" ")),
+        ("{nil}", None),
+        ("{lb}", chars("{")),
+        ("{rb}", chars("}")),
+        ("{\\r}", chars("\r")),
+        ("{\\n}", chars("\n")),
+        ("{\\t}", chars("\t")),
+        ("{nbsp}", chars("\u{a0}")),
+        ("{\\u00A0}", chars("\u{a0}")),
+        ("{\\u263a}", chars("\u{263a}")),
+        ("{\\u1F600}", chars("\u{1f600}")),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(
+            parse!(input, (special, parse_special)),
+            *expected,
+            "\n{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_css_and_xid() {
+    let cases = &[
+        (
+            css_statement,
+            "{css foo-bar}",
+            Command::Css {
+                base: None,
+                name: "foo-bar".to_owned(),
+            },
+        ),
+        (
+            css_statement,
+            "{css %foo}",
+            Command::Css {
+                base: None,
+                name: "%foo".to_owned(),
+            },
+        ),
+        (
+            css_statement,
+            "{css $base, foo}",
+            Command::Css {
+                base: Some(variable!("base")),
+                name: "foo".to_owned(),
+            },
+        ),
+        (
+            xid_statement,
+            "{xid foo.bar}",
+            Command::Xid("foo.bar".to_owned()),
+        ),
+    ];
+    for (rule, input, expected) in cases {
+        assert_eq!(
+            parse!(input, (*rule, parse_command)),
+            *expected,
+            "\n{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_call() {
+    let cases = &[
+        (
+            "{call .foo /}",
+            Command::Call(Call {
+                target: ".foo".to_owned(),
+                data: None,
+                params: vec![],
+                phname: None,
+                location: Location::default(),
+            }),
+        ),
+        (
+            "{call foo.bar data=\"all\"}\n  {param baz: 1 /}\n  // qux\n  {param qux kind=\"text\"}q{/param}\n{/call}",
+            Command::Call(Call {
+                target: "foo.bar".to_owned(),
+                data: Some(CallData::All),
+                params: vec![
+                    CallParam {
+                        name: "baz".to_owned(),
+                        value: CallParamValue::Expression(int!(1)),
+                    },
+                    CallParam {
+                        name: "qux".to_owned(),
+                        value: CallParamValue::Block {
+                            kind: ContentKind::Text,
+                            block: vec![raw_text!("q")],
+                        },
+                    },
+                ],
+                phname: None,
+                location: Location::default(),
+            }),
+        ),
+        (
+            "{call .foo data=\"$bar\"}{param baz}{$x}{/param}{/call}",
+            Command::Call(Call {
+                target: ".foo".to_owned(),
+                data: Some(CallData::Expression(variable!("bar"))),
+                params: vec![CallParam {
+                    name: "baz".to_owned(),
+                    value: CallParamValue::Block {
+                        kind: ContentKind::Html,
+                        block: vec![command!(Command::Print {
+                            expression: variable!("x"),
+                            directives: vec![],
+                            phname: None,
+                        })],
+                    },
+                }],
+                phname: None,
+                location: Location::default(),
+            }),
+        ),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(
+            parse!(input, (call_statement, parse_command)),
+            *expected,
+            "\n{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_call_targets_are_resolved() {
+    let file = parse(
+        "{namespace foo.bar}\n{alias baz.qux}\n{alias a.b as c}\n\
+         /** */\n{template .t}{call .x /}{call qux.y /}{call c.z /}{call d.e /}{/template}",
+    )
+    .unwrap();
+    let targets: Vec<_> = calls(&file.templates[0].body)
+        .into_iter()
+        .map(|call| call.target.as_str())
+        .collect();
+    assert_eq!(targets, vec!["foo.bar.x", "baz.qux.y", "a.b.z", "d.e"]);
+}
+
+#[test]
+fn test_literal() {
+    assert_eq!(
+        parse!(
+            "{literal}foo{sp}\n// foo\n  bar{/literal}",
+            (literal_statement, parse_command)
+        ),
+        Command::Literal("foo{sp}\n// foo\n  bar".to_owned())
+    );
+}
+
+#[test]
+fn test_if() {
+    let cases = &[
+        (
+            "{if true}{/if}",
+            Command::If {
+                if_block: ConditionalBlock {
+                    expression: Expression::Boolean(true),
+                    block: vec![],
+                },
+                else_ifs: vec![],
+                else_block: None,
+            },
+        ),
+        (
+            "{if $baz}{$foo}{else}bar{/if}",
+            Command::If {
+                if_block: ConditionalBlock {
+                    expression: variable!("baz"),
+                    block: vec![command!(Command::Print {
+                        expression: variable!("foo"),
+                        directives: vec![],
+                        phname: None,
+                    })],
+                },
+                else_ifs: vec![],
+                else_block: Some(vec![raw_text!("bar")]),
+            },
+        ),
+        (
+            "{if $baz}{$foo}{elseif true}e{elseif false}f{else}bar{/if}",
+            Command::If {
+                if_block: ConditionalBlock {
+                    expression: variable!("baz"),
+                    block: vec![command!(Command::Print {
+                        expression: variable!("foo"),
+                        directives: vec![],
+                        phname: None,
+                    })],
+                },
+                else_ifs: vec![
+                    ConditionalBlock {
+                        expression: Expression::Boolean(true),
+                        block: vec![raw_text!("e")],
+                    },
+                    ConditionalBlock {
+                        expression: Expression::Boolean(false),
+                        block: vec![raw_text!("f")],
+                    },
+                ],
+                else_block: Some(vec![raw_text!("bar")]),
+            },
+        ),
+    ];
+    cases.iter().for_each(|(input, expected)| {
+        assert_eq!(
+            parse!(input, (if_statement, parse_command)),
+            *expected,
+            "\n{}",
+            input
+        );
+    });
+}
+
+#[test]
+fn test_msg() {
+    let msg = parse!(
+        "{msg desc=\"a foo\" meaning='noun'}{plural $foo}{case 5} foo{default}bar{/plural}{/msg}",
+        (msg_statement, parse_command)
+    );
+    match msg {
+        Command::Msg {
+            desc,
+            meaning,
+            body,
+            ..
+        } => {
+            assert_eq!(desc.as_deref(), Some("a foo"));
+            assert_eq!(meaning.as_deref(), Some("noun"));
+            assert_eq!(
+                body,
+                MsgBody::Plural {
+                    expression: variable!("foo"),
+                    offset: 0,
+                    cases: vec![PluralCase {
+                        expression: Expression::Integer(5),
+                        body: MsgBody::Block(vec![raw_text!(" foo")])
+                    }],
+                    default: Box::new(MsgBody::Block(vec![raw_text!("bar")]))
+                }
+            )
+        }
+        other => panic!("not a msg: {:?}", other),
+    }
+}
+
+#[test]
+fn test_msg_select() {
+    let msg = parse!(
+        "{msg desc=\"\"}\n  {select $gender}\n    {case 'female'}\n      {plural $n}{case 1}her file\
+         {default}her files{/plural}\n    {default}their files\n  {/select}\n{/msg}",
+        (msg_statement, parse_command)
+    );
+    let body = match msg {
+        Command::Msg { body, .. } => body,
+        other => panic!("not a msg: {:?}", other),
+    };
+    assert_eq!(
+        body,
+        MsgBody::Select {
+            expression: variable!("gender"),
+            cases: vec![SelectCase {
+                value: "female".to_owned(),
+                body: MsgBody::Plural {
+                    expression: variable!("n"),
+                    offset: 0,
+                    cases: vec![PluralCase {
+                        expression: int!(1),
+                        body: MsgBody::Block(vec![raw_text!("her file")]),
+                    }],
+                    default: Box::new(MsgBody::Block(vec![raw_text!("her files")])),
+                },
+            }],
+            default: Box::new(MsgBody::Block(vec![raw_text!("their files")])),
+        }
+    );
+    let pair = SoyParser::parse(
+        msg_statement,
+        "{msg desc=\"\"}{select $g}{case 1}x{default}y{/select}{/msg}",
+    )
+    .unwrap()
+    .next()
+    .unwrap();
+    assert_eq!(parse_command(pair).unwrap_err().kind.code(), "E0011");
+}
+
+#[test]
+fn test_plural_offset() {
+    let msg = parse!(
+        "{msg desc=\"\"}{plural $n offset=\"1\"}{case 1}Just you{case 'one'}You and one other\
+         {default}You and {remainder($n)} others{/plural}{/msg}",
+        (msg_statement, parse_command)
+    );
+    match msg {
+        Command::Msg {
+            body: MsgBody::Plural {
+                offset, default, ..
+            },
+            ..
+        } => {
+            assert_eq!(offset, 1);
+            let default = match *default {
+                MsgBody::Block(block) => block,
+                other => panic!("not a block: {:?}", other),
+            };
+            assert_eq!(
+                default[1],
+                command!(Command::Print {
+                    expression: bin_op!(variable!("n"), Minus, int!(1)),
+                    directives: vec![],
+                    phname: None,
+                })
+            );
+        }
+        other => panic!("not a plural msg: {:?}", other),
+    }
+    for input in &[
+        "{msg desc=\"\"}{plural $n offset=\"0\"}{default}x{/plural}{/msg}",
+        "{msg desc=\"\"}{plural $n offset=\"a\"}{default}x{/plural}{/msg}",
+        "{msg desc=\"\"}{plural $n}{case 'several'}y{default}x{/plural}{/msg}",
+        "{msg desc=\"\"}{plural $n}{case $m}y{default}x{/plural}{/msg}",
+    ] {
+        let pair = SoyParser::parse(msg_statement, input)
+            .unwrap()
+            .next()
+            .unwrap();
+        let err = parse_command(pair).expect_err(input);
+        assert_eq!(err.kind.code(), "E0011", "{}", input);
+    }
+}
+
+#[test]
+fn test_msg_ids() {
+    let id = |input: &str| match parse!(input, (msg_statement, parse_command)) {
+        Command::Msg { id, .. } => id,
+        other => panic!("not a msg: {:?}", other),
+    };
+    let hello = id("{msg desc=\"greeting\"}Hello {$name}{/msg}");
+    assert_eq!(
+        hello,
+        id("{msg desc=\"a different description\"}Hello {$name}{/msg}")
+    );
+    assert_eq!(hello, id("{msg desc=\"\"}\n  Hello {$name}\n{/msg}"));
+    assert_eq!(hello, id("{msg desc=\"\"}Hello {$a.name}{/msg}"));
+    assert_ne!(hello, id("{msg desc=\"\"}Hello {$userName}{/msg}"));
+    assert_ne!(hello, id("{msg desc=\"\"}Hello, {$name}{/msg}"));
+    assert_ne!(
+        hello,
+        id("{msg desc=\"\" meaning=\"x\"}Hello {$name}{/msg}")
+    );
+    assert!(hello < 1 << 63);
+    // Changing the algorithm would invalidate every existing translation
+    assert_eq!(hello, 1_782_895_116_599_745_495);
+    // The id Closure's SoyMsgIdComputer gives this message (as in its XLIFF examples), so
+    // that translations extracted by Closure's tools are found
+    assert_eq!(
+        id("{msg desc=\"Says hello to the user.\"}Hello {$name}!{/msg}"),
+        6_936_162_475_751_860_807
+    );
+}
+
+#[test]
+fn test_msg_placeholders() {
+    let cases: &[(&str, &[&str])] = &[
+        ("{msg desc=\"\"}Hi {$userName}!{/msg}", &["Hi ", "USER_NAME", "!"]),
+        (
+            "{msg desc=\"\"}{$a.name}{$b.name}{$a.name}{$ij.name2}{/msg}",
+            &["NAME_1", "NAME_3", "NAME_1", "NAME_2"],
+        ),
+        (
+            "{msg desc=\"\"}<a href=\"{$url}\" title='>'>Go</a> or <a href=\"/\">home</a>{/msg}",
+            &["START_LINK_1", "Go", "END_LINK", " or ", "START_LINK_2", "home", "END_LINK"],
+        ),
+        (
+            "{msg desc=\"\"}<br><img src=x><hr/><span class=x>1 < 2</span>{/msg}",
+            &["BREAK", "IMAGE", "HR", "START_SPAN", "1 < 2", "END_SPAN"],
+        ),
+        (
+            "{msg desc=\"\"}{$a phname=\"name\"}{$b.name}{$c phname=\"FOO_BAR\"}{if $x}x{/if}{/msg}",
+            &["NAME", "NAME_1", "FOO_BAR", "XXX"],
+        ),
+        (
+            "{msg desc=\"\"}<a phname=\"userLink\" href=\"/\">x</a>{/msg}",
+            &["USER_LINK", "x", "END_LINK"],
+        ),
+        (
+            "{msg desc=\"\"}Hi {call .name /}!{/msg}",
+            &["Hi ", "XXX", "!"],
+        ),
+        (
+            "{msg desc=\"\"}{call .a /} and {call .b data=\"all\" /}{/msg}",
+            &["XXX_1", " and ", "XXX_2"],
+        ),
+        (
+            "{msg desc=\"\"}{call .a phname=\"userName\"}{param x: 1 /}{/call}{/msg}",
+            &["USER_NAME"],
+        ),
+    ];
+    for (input, expected) in cases {
+        let body = match parse!(input, (msg_statement, parse_command)) {
+            Command::Msg { body, .. } => body,
+            other => panic!("not a msg: {:?}", other),
+        };
+        let message = Message::new(&body);
+        let parts: Vec<_> = message
+            .parts
+            .iter()
+            .map(|part| match part {
+                msg::MsgPart::RawText(text) | msg::MsgPart::Placeholder(text) => text.as_str(),
+                msg::MsgPart::Plural { .. } | msg::MsgPart::Select { .. } => "choice",
+            })
+            .collect();
+        assert_eq!(&parts, expected, "\n{}", input);
+    }
+    let message_with = |input: &str| {
+        let pair = SoyParser::parse(msg_statement, input)
+            .unwrap()
+            .next()
+            .unwrap();
+        parse_command(pair).map(|_| ())
+    };
+    assert!(message_with("{msg desc=\"\"}{$a phname=\"x\"}{$b phname=\"x\"}{/msg}").is_err());
+    assert!(message_with("{msg desc=\"\"}{$a phname=\"x\"}{$a phname=\"x\"}{/msg}").is_ok());
+    assert!(message_with("{msg desc=\"\"}{$a phname=\"1x\"}{/msg}").is_err());
+    assert!(message_with("{msg desc=\"\"}{call .a phname=\"1x\" /}{/msg}").is_err());
+}
+
+#[test]
+fn test_print() {
+    let cases = &[
+        (
+            "{$foo}",
+            Command::Print {
+                expression: variable!("foo"),
+                directives: vec![],
+                phname: None,
+            },
+        ),
+        (
+            "{print $foo}",
+            Command::Print {
+                expression: variable!("foo"),
+                directives: vec![],
+                phname: None,
+            },
+        ),
+        (
+            "{$foo.baz |changeNewlineToBr |truncate:8,false}",
+            Command::Print {
+                expression: Expression::DataReference {
+                    referent: Referent::Variable("foo".to_owned()),
+                    references: vec![Reference::Dotted(ReferenceKey::Name("baz".to_owned()))],
+                },
+                directives: vec![
+                    PrintDirective {
+                        name: "changeNewlineToBr".to_owned(),
+                        arguments: vec![],
+                    },
+                    PrintDirective {
+                        name: "truncate".to_owned(),
+                        arguments: vec![int!(8), Expression::Boolean(false)],
+                    },
+                ],
+                phname: None,
+            },
+        ),
+        (
+            "{$userName phname=\"name\" |escapeHtml}",
+            Command::Print {
+                expression: variable!("userName"),
+                directives: vec![PrintDirective {
+                    name: "escapeHtml".to_owned(),
+                    arguments: vec![],
+                }],
+                phname: Some("name".to_owned()),
+            },
+        ),
+    ];
+    cases.iter().for_each(|(input, expected)| {
+        assert_eq!(
+            parse!(input, (print_statement, parse_command)),
+            *expected,
+            "\n{}",
+            input
+        );
+    });
+}
+
+#[test]
+fn test_template() {
+    let cases = &[
+            (
+                "/** */{template .foo}{/template}",
+                Template {
+                    name: "foo".to_owned(),
+                    attributes: TemplateAttributes::default(),
+                    body: TemplateBlock::new(),
+                    soydoc_params: vec![],
+                    location: Default::default(),
+                },
+            ),
+            (
+                "/**\n * @param foo a foo\n * @param? bar\n */\n{template .foo}{$foo}{sp}{/template}",
+                Template {
+                    name: "foo".to_owned(),
+                    attributes: TemplateAttributes::default(),
+                    body: vec![
+                        command!(Command::Print {
+                            expression: variable!("foo"),
+                            directives: vec![],
+                            phname: None,
+                        }),
+                        TemplateNode::Special(" ".to_owned())
+                    ],
+                    soydoc_params: vec![
+                        SoydocParam {
+                            name: "foo".to_owned(),
+                            required: true,
+                        },
+                        SoydocParam {
+                            name: "bar".to_owned(),
+                            required: false,
+                        },
+                    ],
+                    location: Default::default(),
+                },
+            ),
+            (
+                // Demonstrating comments and whitespace stripping
+                "/** */{template .foo} First // comment \n  Second<br>\n\n  // A comment \n  <i>Third</i>\n{/template}",
+                Template {
+                    name: "foo".to_owned(),
+                    attributes: TemplateAttributes::default(),
+                    body: vec![raw_text!(" First Second<br><i>Third</i>")],
+                    soydoc_params: vec![],
+                    location: Default::default(),
+                },
+            ),
+            (
+                // Demonstrating various forms of comments
+                "/** */{template .foo}Foo // foooo\n Bar /* comment \n foo */\n /* lks */ Baz{/template}",
+                Template {
+                    name: "foo".to_owned(),
+                    attributes: TemplateAttributes::default(),
+                    body: vec![raw_text!("Foo Bar Baz")],
+                    soydoc_params: vec![],
+                    location: Default::default(),
+                },
+            ),
+        ];
+
+    cases.iter().for_each(|(input, expected)| {
+        assert_eq!(
+            parse!(input, (template, parse_template)),
+            *expected,
+            "\n{}",
+            input
+        );
+    });
+}
+
+#[test]
+fn test_template_attributes() {
+    let cases = &[
+        ("", TemplateAttributes::default()),
+        (
+            " visibility=\"private\" kind=\"text\"",
+            TemplateAttributes {
+                visibility: Visibility::Private,
+                kind: ContentKind::Text,
+                ..Default::default()
+            },
+        ),
+        (
+            " autoescape=\"deprecated-contextual\" stricthtml=\"false\"",
+            TemplateAttributes {
+                autoescape: Some(AutoescapeMode::Contextual),
+                strict_html: false,
+                ..Default::default()
+            },
+        ),
+        (
+            " requirecss=\"foo.bar, baz\" kind='trusted_resource_uri'",
+            TemplateAttributes {
+                kind: ContentKind::TrustedResourceUri,
+                require_css: vec!["foo.bar".to_owned(), "baz".to_owned()],
+                ..Default::default()
+            },
+        ),
+    ];
+    for (attrs, expected) in cases {
+        let input = format!("/** */{{template .foo{}}}{{/template}}", attrs);
+        assert_eq!(
+            parse!(&input, (template, parse_template)).attributes,
+            *expected,
+            "\n{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_template_attributes_inherit_from_namespace() {
+    let file = parse(
+        "{namespace foo autoescape=\"deprecated-noncontextual\" requirecss=\"a, b\" cssbase=\"x\"}\n\
+         /** */{template .bar}{/template}\n\
+         /** */{template .baz autoescape=\"strict\" requirecss=\"b, c\" cssbase=\"y\"}{/template}",
+    )
+    .unwrap();
+    let css = |names: &[&str]| names.iter().map(|&n| n.to_owned()).collect::<Vec<_>>();
+    assert_eq!(
+        file.templates[0].attributes,
+        TemplateAttributes {
+            autoescape: Some(AutoescapeMode::NonContextual),
+            require_css: css(&["a", "b"]),
+            css_base: Some("x".to_owned()),
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        file.templates[1].attributes,
+        TemplateAttributes {
+            autoescape: Some(AutoescapeMode::Strict),
+            require_css: css(&["a", "b", "c"]),
+            css_base: Some("y".to_owned()),
+            ..Default::default()
+        }
+    );
+}
@@ -0,0 +1,207 @@
+use super::*;
+use pest::Parser;
+
+/// Parses a template body and flattens it back to a string, with tags other than
+/// specials and literals shown as placeholders.
+fn join(input: &str) -> String {
+    let pair = SoyParser::parse(Rule::template_block, input)
+        .expect("should parse")
+        .next()
+        .unwrap();
+    assert_eq!(
+        pair.as_span().end(),
+        input.len(),
+        "should consume all input"
+    );
+    flatten(&parse_template_block(pair).unwrap())
+}
+
+fn flatten(block: &TemplateBlock) -> String {
+    block
+        .iter()
+        .map(|node| match node {
+            TemplateNode::RawText(text) | TemplateNode::Special(text) => text.clone(),
+            TemplateNode::Statement(Command::Literal(text)) => text.clone(),
+            TemplateNode::Statement(Command::If {
+                if_block,
+                else_block,
+                ..
+            }) => format!(
+                "[if]{}[else]{}[/if]",
+                flatten(&if_block.block),
+                else_block.as_ref().map_or(String::new(), flatten)
+            ),
+            TemplateNode::Statement(_) => "[print]".to_owned(),
+        })
+        .collect()
+}
+
+#[test]
+fn test_text_within_a_line_is_unchanged() {
+    assert_eq!(join("Hello  world"), "Hello  world");
+    assert_eq!(join("  Hello world  "), "  Hello world  ");
+    assert_eq!(join("a\tb"), "a\tb");
+}
+
+#[test]
+fn test_lines_are_joined_with_a_space() {
+    assert_eq!(join("\n  Hello\n  world\n"), "Hello world");
+    assert_eq!(join("Hello\nworld"), "Hello world");
+    assert_eq!(join("\tHello\t\n\tworld\t"), "\tHello world\t");
+    assert_eq!(join("\n  one\n\n\n  two\n   \n  three\n"), "one two three");
+}
+
+#[test]
+fn test_line_terminators() {
+    assert_eq!(join("Hello\r\nworld"), "Hello world");
+    assert_eq!(join("Hello\rworld"), "Hello world");
+    assert_eq!(join("\r\n  Hello\r\n\r\n  world\r\n"), "Hello world");
+}
+
+#[test]
+fn test_no_space_next_to_html_tags() {
+    assert_eq!(join("\n  <div>\n    Hello\n  </div>\n"), "<div>Hello</div>");
+    assert_eq!(join("<br>\nHello"), "<br>Hello");
+    assert_eq!(join("Hello\n<br>"), "Hello<br>");
+    assert_eq!(join("<a\n    href=\"#\">"), "<a href=\"#\">");
+}
+
+#[test]
+fn test_no_space_next_to_soy_tags() {
+    assert_eq!(join("\n  Hello {$name}\n  !\n"), "Hello [print]!");
+    assert_eq!(join("\n  {$a}\n  {$b}\n"), "[print][print]");
+    assert_eq!(join("\n  foo\n  {$a}\n  bar\n"), "foo[print]bar");
+    assert_eq!(join("\n  foo{sp}\n  bar\n"), "foo bar");
+    assert_eq!(join("foo{nil}\nbar"), "foobar");
+    assert_eq!(join("foo{\\n}\nbar"), "foo\nbar");
+    assert_eq!(join("foo  \n  {lb}"), "foo{");
+}
+
+#[test]
+fn test_whitespace_next_to_soy_tags_on_the_same_line_is_kept() {
+    assert_eq!(join("{$a} {$b}"), "[print] [print]");
+    assert_eq!(
+        join("\n  Hello, {$name} and {$other}.\n"),
+        "Hello, [print] and [print]."
+    );
+    assert_eq!(join("{$a}  bar\n  baz"), "[print]  bar baz");
+    assert_eq!(join("foo {$a}"), "foo [print]");
+}
+
+#[test]
+fn test_comments() {
+    assert_eq!(join("foo // comment\n  bar"), "foo bar");
+    assert_eq!(join("\n  // comment\n  foo\n"), "foo");
+    assert_eq!(join("foo\n// comment\nbar"), "foo bar");
+    assert_eq!(
+        join("\n  foo\n  /* comment\n   * more comment\n   */\n  bar\n"),
+        "foo bar"
+    );
+    assert_eq!(join("foo /* comment */ bar"), "foo  bar");
+    assert_eq!(join("foo\n  /* comment */ bar"), "foo bar");
+    // `//` is only a comment after whitespace
+    assert_eq!(join("http://example.com"), "http://example.com");
+    assert_eq!(join("{$url}//foo"), "[print]//foo");
+    assert_eq!(join("<a href=\"x // y\">"), "<a href=\"x // y\">");
+}
+
+#[test]
+fn test_nested_blocks() {
+    assert_eq!(
+        join("\n  {if $x}\n    foo\n    bar\n  {else}\n    baz\n  {/if}\n"),
+        "[if]foo bar[else]baz[/if]"
+    );
+    assert_eq!(join("{if $x} foo {/if}"), "[if] foo [else][/if]");
+    assert_eq!(
+        join("{if $x}\n  <b>\n    bold\n  </b>\n{/if}"),
+        "[if]<b>bold</b>[else][/if]"
+    );
+}
+
+#[test]
+fn test_literal_is_not_joined() {
+    assert_eq!(
+        join("\n  {literal}\n    keep   this\n  // and this\n{/literal}\n"),
+        "\n    keep   this\n  // and this\n"
+    );
+}
+
+#[test]
+fn test_msg_blocks() {
+    let input = "{msg desc=\"greeting\"}\n  Hello\n  world\n{/msg}";
+    let msg = parse_command(
+        SoyParser::parse(Rule::msg_statement, input)
+            .unwrap()
+            .next()
+            .unwrap(),
+    );
+    match msg.unwrap() {
+        Command::Msg { body, .. } => assert_eq!(
+            body,
+            MsgBody::Block(vec![TemplateNode::RawText("Hello world".to_owned())])
+        ),
+        other => panic!("not a msg: {:?}", other),
+    }
+}
+
+// The cases below are ported from Closure Templates' `TemplateParserTest`, which
+// parses each body as `{template .t}\n` + body + `\n{/template}`:
+//
+// - `test_closure_raw_text`: the body of `testParseRawText`.
+// - `test_closure_comments`: the body of `testParseComments`.
+// - `test_closure_recognizes_raw_text`: the `<` and `>` case of `testRecognizeRawText`.
+// - `test_closure_special_characters`: `{sp}`, `{nil}`, `{\n}`, `{\r}`, `{\t}`, `{lb}`
+//   and `{rb}` together.
+// - `test_closure_literal`: the `{literal}` case of `testRecognizeRawText`.
+//
+// The expected text is the same as upstream, but upstream checks each raw text node,
+// while `join` flattens the nodes into a string, so where one node ends isn't checked.
+// Cases using double-brace tags (`{{sp}}`) aren't ported, since the grammar doesn't
+// have them.
+
+fn closure(body: &str) -> String {
+    join(&format!("\n{}\n", body))
+}
+
+#[test]
+fn test_closure_raw_text() {
+    assert_eq!(
+        closure(
+            "  {sp} aaa bbb  \n  ccc {lb}{rb} ddd {\\n}\n  eee <br>\n  fff\n  \
+             {literal}ggg\nhhh }{  {/literal}  \n  \u{2222}\u{EEEE}\u{9EC4}\u{607A}\n"
+        ),
+        "  aaa bbb ccc {} ddd \neee <br>fffggg\nhhh }{  \u{2222}\u{EEEE}\u{9EC4}\u{607A}"
+    );
+}
+
+#[test]
+fn test_closure_comments() {
+    assert_eq!(
+        closure(
+            "  {sp}  // {sp}\n  /* {sp} {sp} */  // {sp}\n  /* {sp} */{sp}/* {sp} */\n  \
+             /* {sp}\n  {sp} */{sp}\n  // {sp} /* {sp} */\n  http://www.google.com\n"
+        ),
+        "   http://www.google.com"
+    );
+}
+
+#[test]
+fn test_closure_recognizes_raw_text() {
+    assert_eq!(
+        closure("blah>blah<blah<blah>blah>blah>blah>blah<blah"),
+        "blah>blah<blah<blah>blah>blah>blah>blah<blah"
+    );
+}
+
+#[test]
+fn test_closure_special_characters() {
+    assert_eq!(closure("{sp}{nil}{\\n}{\\r}{\\t}{lb}{rb}"), " \n\r\t{}");
+}
+
+#[test]
+fn test_closure_literal() {
+    assert_eq!(
+        closure("blah{literal}{ {{{ } }{ {}} { }}}}}}}\n}}}}}}}}}{ { {{/literal}blah"),
+        "blah{ {{{ } }{ {}} { }}}}}}}\n}}}}}}}}}{ { {blah"
+    );
+}
@@ -0,0 +1,434 @@
+//! Plural category selection using the CLDR plural rules
+//! (<https://unicode.org/reports/tr35/tr35-numbers.html#Language_Plural_Rules>).
+
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+
+use crate::value::SoyValue;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluralCategory {
+    Zero,
+    One,
+    Two,
+    Few,
+    Many,
+    Other,
+}
+
+impl PluralCategory {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "zero" => PluralCategory::Zero,
+            "one" => PluralCategory::One,
+            "two" => PluralCategory::Two,
+            "few" => PluralCategory::Few,
+            "many" => PluralCategory::Many,
+            "other" => PluralCategory::Other,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PluralCategory::Zero => "zero",
+            PluralCategory::One => "one",
+            PluralCategory::Two => "two",
+            PluralCategory::Few => "few",
+            PluralCategory::Many => "many",
+            PluralCategory::Other => "other",
+        }
+    }
+}
+
+/// The cardinal plural rules of each language, in CLDR syntax. Languages that aren't
+/// listed (such as Japanese or Chinese) only have `other`.
+static RULES: &[(&str, &[(&str, &str)])] = &[
+    (
+        "ast ca de en et fi fy gl ia io it ji lij nl sc scn sv sw ur yi",
+        &[("one", "i = 1 and v = 0")],
+    ),
+    (
+        "af an asa az bal bem bez bg brx ce cgg chr ckb dv ee el eo es eu fo fur gsw ha haw \
+         hu jgo jmc ka kaj kcg kk kkj kl ks ksb ku ky lb lg mas mgo ml mn mr nah nb nd ne nn \
+         nnh no nr ny nyn om or os pap ps rm rof rwk saq sd sdh seh sn so sq ss ssy st syr ta \
+         te teo tig tk tn tr ts ug uz ve vo vun wae xh xog",
+        &[("one", "n = 1")],
+    ),
+    (
+        "am as bn doi fa gu hi kn pcm zu",
+        &[("one", "i = 0 or n = 1")],
+    ),
+    ("ff hy kab", &[("one", "i = 0,1")]),
+    ("fr", &[("one", "i = 0,1")]),
+    ("pt", &[("one", "i = 0..1")]),
+    ("pt_pt", &[("one", "i = 1 and v = 0")]),
+    ("da", &[("one", "n = 1 or t != 0 and i = 0,1")]),
+    (
+        "is",
+        &[("one", "t = 0 and i % 10 = 1 and i % 100 != 11 or t != 0")],
+    ),
+    (
+        "mk",
+        &[(
+            "one",
+            "v = 0 and i % 10 = 1 and i % 100 != 11 or f % 10 = 1 and f % 100 != 11",
+        )],
+    ),
+    (
+        "ceb fil tl",
+        &[(
+            "one",
+            "v = 0 and i = 1,2,3 or v = 0 and i % 10 != 4,6,9 or v != 0 and f % 10 != 4,6,9",
+        )],
+    ),
+    (
+        "lv prg",
+        &[
+            (
+                "zero",
+                "n % 10 = 0 or n % 100 = 11..19 or v = 2 and f % 100 = 11..19",
+            ),
+            (
+                "one",
+                "n % 10 = 1 and n % 100 != 11 or v = 2 and f % 10 = 1 and f % 100 != 11 \
+                 or v != 2 and f % 10 = 1",
+            ),
+        ],
+    ),
+    ("lag", &[("zero", "n = 0"), ("one", "i = 0,1 and n != 0")]),
+    ("ksh", &[("zero", "n = 0"), ("one", "n = 1")]),
+    (
+        "iu naq sat se sma smi smj smn sms",
+        &[("one", "n = 1"), ("two", "n = 2")],
+    ),
+    ("shi", &[("one", "i = 0 or n = 1"), ("few", "n = 2..10")]),
+    (
+        "mo ro",
+        &[
+            ("one", "i = 1 and v = 0"),
+            ("few", "v != 0 or n = 0 or n % 100 = 2..19"),
+        ],
+    ),
+    (
+        "bs hr sh sr",
+        &[
+            (
+                "one",
+                "v = 0 and i % 10 = 1 and i % 100 != 11 or f % 10 = 1 and f % 100 != 11",
+            ),
+            (
+                "few",
+                "v = 0 and i % 10 = 2..4 and i % 100 != 12..14 \
+                 or f % 10 = 2..4 and f % 100 != 12..14",
+            ),
+        ],
+    ),
+    (
+        "gd",
+        &[
+            ("one", "n = 1,11"),
+            ("two", "n = 2,12"),
+            ("few", "n = 3..10,13..19"),
+        ],
+    ),
+    (
+        "sl",
+        &[
+            ("one", "v = 0 and i % 100 = 1"),
+            ("two", "v = 0 and i % 100 = 2"),
+            ("few", "v = 0 and i % 100 = 3..4 or v != 0"),
+        ],
+    ),
+    (
+        "dsb hsb",
+        &[
+            ("one", "v = 0 and i % 100 = 1 or f % 100 = 1"),
+            ("two", "v = 0 and i % 100 = 2 or f % 100 = 2"),
+            ("few", "v = 0 and i % 100 = 3..4 or f % 100 = 3..4"),
+        ],
+    ),
+    (
+        "he iw",
+        &[
+            ("one", "i = 1 and v = 0"),
+            ("two", "i = 2 and v = 0"),
+            ("many", "v = 0 and n != 0..10 and n % 10 = 0"),
+        ],
+    ),
+    (
+        "cs sk",
+        &[
+            ("one", "i = 1 and v = 0"),
+            ("few", "i = 2..4 and v = 0"),
+            ("many", "v != 0"),
+        ],
+    ),
+    (
+        "pl",
+        &[
+            ("one", "i = 1 and v = 0"),
+            ("few", "v = 0 and i % 10 = 2..4 and i % 100 != 12..14"),
+            (
+                "many",
+                "v = 0 and i != 1 and i % 10 = 0..1 or v = 0 and i % 10 = 5..9 \
+                 or v = 0 and i % 100 = 12..14",
+            ),
+        ],
+    ),
+    (
+        "be",
+        &[
+            ("one", "n % 10 = 1 and n % 100 != 11"),
+            ("few", "n % 10 = 2..4 and n % 100 != 12..14"),
+            ("many", "n % 10 = 0 or n % 10 = 5..9 or n % 100 = 11..14"),
+        ],
+    ),
+    (
+        "lt",
+        &[
+            ("one", "n % 10 = 1 and n % 100 != 11..19"),
+            ("few", "n % 10 = 2..9 and n % 100 != 11..19"),
+            ("many", "f != 0"),
+        ],
+    ),
+    (
+        "ru uk",
+        &[
+            ("one", "v = 0 and i % 10 = 1 and i % 100 != 11"),
+            ("few", "v = 0 and i % 10 = 2..4 and i % 100 != 12..14"),
+            (
+                "many",
+                "v = 0 and i % 10 = 0 or v = 0 and i % 10 = 5..9 or v = 0 and i % 100 = 11..14",
+            ),
+        ],
+    ),
+    (
+        "mt",
+        &[
+            ("one", "n = 1"),
+            ("few", "n = 0 or n % 100 = 2..10"),
+            ("many", "n % 100 = 11..19"),
+        ],
+    ),
+    (
+        "ga",
+        &[
+            ("one", "n = 1"),
+            ("two", "n = 2"),
+            ("few", "n = 3..6"),
+            ("many", "n = 7..10"),
+        ],
+    ),
+    (
+        "gv",
+        &[
+            ("one", "v = 0 and i % 10 = 1"),
+            ("two", "v = 0 and i % 10 = 2"),
+            ("few", "v = 0 and i % 100 = 0,20,40,60,80"),
+            ("many", "v != 0"),
+        ],
+    ),
+    (
+        "br",
+        &[
+            ("one", "n % 10 = 1 and n % 100 != 11,71,91"),
+            ("two", "n % 10 = 2 and n % 100 != 12,72,92"),
+            ("few", "n % 10 = 3..4,9 and n % 100 != 10..19,70..79,90..99"),
+            ("many", "n != 0 and n % 1000000 = 0"),
+        ],
+    ),
+    (
+        "ar ars",
+        &[
+            ("zero", "n = 0"),
+            ("one", "n = 1"),
+            ("two", "n = 2"),
+            ("few", "n % 100 = 3..10"),
+            ("many", "n % 100 = 11..99"),
+        ],
+    ),
+    (
+        "cy",
+        &[
+            ("zero", "n = 0"),
+            ("one", "n = 1"),
+            ("two", "n = 2"),
+            ("few", "n = 3"),
+            ("many", "n = 6"),
+        ],
+    ),
+];
+
+/// The operands of a number that plural rules are written in terms of.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Operands {
+    /// The absolute value.
+    n: f64,
+    /// The integer digits.
+    i: u64,
+    /// The number of visible fraction digits (with trailing zeros).
+    v: u64,
+    /// The visible fraction digits (with trailing zeros).
+    f: u64,
+    /// The visible fraction digits without trailing zeros.
+    t: u64,
+}
+
+impl Operands {
+    fn new(value: &SoyValue) -> Option<Operands> {
+        let (n, text) = match value {
+            SoyValue::Int(i) => (i.unsigned_abs() as f64, i.unsigned_abs().to_string()),
+            SoyValue::Float(f) if f.is_finite() => (f.abs(), SoyValue::Float(f.abs()).to_string()),
+            _ => return None,
+        };
+        let (integer, fraction) = match text.find('.') {
+            Some(dot) => (&text[..dot], &text[dot + 1..]),
+            None => (text.as_str(), ""),
+        };
+        let trimmed = fraction.trim_end_matches('0');
+        Some(Operands {
+            n,
+            i: integer.parse().unwrap_or(u64::MAX),
+            v: fraction.len() as u64,
+            f: fraction.parse().unwrap_or(0),
+            t: trimmed.parse().unwrap_or(0),
+        })
+    }
+
+    fn get(&self, operand: Operand) -> f64 {
+        match operand {
+            Operand::N => self.n,
+            Operand::I => self.i as f64,
+            Operand::V => self.v as f64,
+            Operand::F => self.f as f64,
+            Operand::T => self.t as f64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    N,
+    I,
+    V,
+    F,
+    T,
+}
+
+/// A relation such as `i % 10 = 2..4`.
+#[derive(Debug)]
+struct Relation {
+    operand: Operand,
+    modulus: Option<f64>,
+    negated: bool,
+    ranges: Vec<(f64, f64)>,
+}
+
+impl Relation {
+    fn matches(&self, operands: &Operands) -> bool {
+        let mut value = operands.get(self.operand);
+        if let Some(modulus) = self.modulus {
+            value %= modulus;
+        }
+        // Ranges only contain integers, so `n = 1..3` doesn't match 1.5
+        let in_ranges = self
+            .ranges
+            .iter()
+            .any(|&(low, high)| value.fract() == 0.0 && value >= low && value <= high);
+        in_ranges != self.negated
+    }
+}
+
+/// A condition: relations joined by `and`, which are joined by `or`.
+type Condition = Vec<Vec<Relation>>;
+
+fn parse_condition(rule: &str) -> Condition {
+    rule.split(" or ")
+        .map(|and| and.split(" and ").map(parse_relation).collect())
+        .collect()
+}
+
+fn parse_relation(relation: &str) -> Relation {
+    let (lhs, negated, rhs) = match relation.find("!=") {
+        Some(at) => (&relation[..at], true, &relation[at + 2..]),
+        None => {
+            let at = relation.find('=').expect("relation without an operator");
+            (&relation[..at], false, &relation[at + 1..])
+        }
+    };
+    let mut lhs = lhs.split('%');
+    let operand = match lhs.next().map(str::trim) {
+        Some("n") => Operand::N,
+        Some("i") => Operand::I,
+        Some("v") => Operand::V,
+        Some("f") => Operand::F,
+        Some("t") => Operand::T,
+        other => panic!("unknown plural operand: {:?}", other),
+    };
+    let number = |s: &str| {
+        s.trim()
+            .parse::<f64>()
+            .expect("invalid number in plural rule")
+    };
+    let modulus = lhs.next().map(number);
+    let ranges = rhs
+        .split(',')
+        .map(|range| match range.find("..") {
+            Some(at) => (number(&range[..at]), number(&range[at + 2..])),
+            None => (number(range), number(range)),
+        })
+        .collect();
+    Relation {
+        operand,
+        modulus,
+        negated,
+        ranges,
+    }
+}
+
+lazy_static! {
+    static ref RULES_BY_LANGUAGE: HashMap<&'static str, Vec<(PluralCategory, Condition)>> = {
+        let mut rules = HashMap::new();
+        for (languages, categories) in RULES {
+            for language in languages.split_whitespace() {
+                let conditions = categories
+                    .iter()
+                    .map(|(category, rule)| {
+                        let category =
+                            PluralCategory::from_name(category).expect("unknown plural category");
+                        (category, parse_condition(rule))
+                    })
+                    .collect();
+                rules.insert(language, conditions);
+            }
+        }
+        rules
+    };
+}
+
+/// The plural category of a number in a locale (e.g. `en`, `pt-PT` or `sr_Latn`), which
+/// is `other` for values that aren't numbers.
+pub fn category(locale: &str, value: &SoyValue) -> PluralCategory {
+    let operands = match Operands::new(value) {
+        Some(operands) => operands,
+        None => return PluralCategory::Other,
+    };
+    let locale = locale.replace('-', "_").to_ascii_lowercase();
+    let language = locale.split('_').next().unwrap_or("");
+    let rules = RULES_BY_LANGUAGE
+        .get(locale.as_str())
+        .or_else(|| RULES_BY_LANGUAGE.get(language));
+    rules
+        .and_then(|rules| {
+            rules.iter().find(|(_, condition)| {
+                condition
+                    .iter()
+                    .any(|relations| relations.iter().all(|r| r.matches(&operands)))
+            })
+        })
+        .map_or(PluralCategory::Other, |(category, _)| *category)
+}
@@ -0,0 +1,80 @@
+use super::*;
+
+fn categories(locale: &str, values: &[SoyValue]) -> Vec<&'static str> {
+    values.iter().map(|v| category(locale, v).name()).collect()
+}
+
+#[test]
+fn test_operands() {
+    let operands = |value: SoyValue| Operands::new(&value).unwrap();
+    assert_eq!(
+        operands(SoyValue::Int(-12)),
+        Operands {
+            n: 12.0,
+            i: 12,
+            v: 0,
+            f: 0,
+            t: 0
+        }
+    );
+    assert_eq!(
+        operands(SoyValue::Float(1.25)),
+        Operands {
+            n: 1.25,
+            i: 1,
+            v: 2,
+            f: 25,
+            t: 25
+        }
+    );
+    assert_eq!(operands(SoyValue::Float(3.0)).v, 0);
+    assert_eq!(Operands::new(&SoyValue::from("1")), None);
+    assert_eq!(Operands::new(&SoyValue::Float(f64::NAN)), None);
+}
+
+#[test]
+fn test_categories() {
+    let ints = |values: &[i64]| values.iter().map(|&i| SoyValue::Int(i)).collect::<Vec<_>>();
+    assert_eq!(
+        categories("en", &ints(&[0, 1, 2])),
+        vec!["other", "one", "other"]
+    );
+    assert_eq!(categories("en-US", &[SoyValue::Float(1.5)]), vec!["other"]);
+    assert_eq!(
+        categories("fr", &ints(&[0, 1, 2])),
+        vec!["one", "one", "other"]
+    );
+    assert_eq!(categories("fr", &[SoyValue::Float(1.5)]), vec!["one"]);
+    assert_eq!(
+        categories("ru", &ints(&[1, 2, 5, 11, 21, 22, 112])),
+        vec!["one", "few", "many", "many", "one", "few", "many"]
+    );
+    assert_eq!(categories("ru", &[SoyValue::Float(1.5)]), vec!["other"]);
+    assert_eq!(
+        categories("pl", &ints(&[1, 2, 5, 12, 22, 101])),
+        vec!["one", "few", "many", "many", "few", "many"]
+    );
+    assert_eq!(
+        categories("ar", &ints(&[0, 1, 2, 3, 11, 100])),
+        vec!["zero", "one", "two", "few", "many", "other"]
+    );
+    assert_eq!(
+        categories("cy", &ints(&[0, 1, 2, 3, 6, 7])),
+        vec!["zero", "one", "two", "few", "many", "other"]
+    );
+    assert_eq!(categories("pt", &ints(&[0, 1])), vec!["one", "one"]);
+    assert_eq!(categories("pt_PT", &ints(&[0, 1])), vec!["other", "one"]);
+    assert_eq!(categories("sr-Latn", &ints(&[21, 23])), vec!["one", "few"]);
+    assert_eq!(categories("ja", &ints(&[0, 1, 2])), vec!["other"; 3]);
+    assert_eq!(categories("xx", &ints(&[1])), vec!["other"]);
+    assert_eq!(categories("en", &[SoyValue::Null]), vec!["other"]);
+}
+
+#[test]
+fn test_rules_parse() {
+    // Every rule is valid and every category is known
+    assert!(RULES_BY_LANGUAGE.len() > 100);
+    for rules in RULES_BY_LANGUAGE.values() {
+        assert!(!rules.is_empty());
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ast::ContentKind;
+
+/// A value passed to a template as a parameter, or produced by evaluating an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SoyValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    List(Vec<SoyValue>),
+    Map(HashMap<String, SoyValue>),
+    /// Content that is known to be safe to use in a particular context, so isn't escaped
+    /// when printed there.
+    Sanitized(SanitizedContent),
+}
+
+/// Content of a particular kind, such as HTML, that is trusted not to need escaping
+/// when printed in a context of the same kind.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SanitizedContent {
+    kind: ContentKind,
+    content: String,
+}
+
+impl SanitizedContent {
+    /// Content of a kind, such as HTML, that is printed as is in templates of that kind
+    /// rather than being escaped. `SoyValue` has a constructor for each kind too, such as
+    /// `SoyValue::trusted_html`.
+    ///
+    /// # Safety
+    ///
+    /// Nothing checks the content. It must come from a trusted source or have been
+    /// sanitized, since anything it contains (such as a script) ends up in the page. This
+    /// isn't memory safety, but calling it is as deliberate as an `unsafe` block.
+    pub unsafe fn from_trusted_unchecked<S: Into<String>>(kind: ContentKind, content: S) -> Self {
+        SanitizedContent {
+            kind,
+            content: content.into(),
+        }
+    }
+
+    pub fn kind(&self) -> ContentKind {
+        self.kind
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+}
+
+/// Constructors for trusted content of each kind. They're `unsafe` for the same reason as
+/// `SanitizedContent::from_trusted_unchecked`, which they call.
+impl SoyValue {
+    /// HTML that is printed as is in HTML templates.
+    ///
+    /// # Safety
+    ///
+    /// See `SanitizedContent::from_trusted_unchecked`.
+    pub unsafe fn trusted_html<S: Into<String>>(content: S) -> SoyValue {
+        SanitizedContent::from_trusted_unchecked(ContentKind::Html, content).into()
+    }
+
+    /// HTML attributes, such as `dir="rtl"`, printed as they are in attribute templates.
+    ///
+    /// # Safety
+    ///
+    /// See `SanitizedContent::from_trusted_unchecked`.
+    pub unsafe fn trusted_attributes<S: Into<String>>(content: S) -> SoyValue {
+        SanitizedContent::from_trusted_unchecked(ContentKind::Attributes, content).into()
+    }
+
+    /// JavaScript that is printed as is in JS templates, rather than as a string literal.
+    ///
+    /// # Safety
+    ///
+    /// See `SanitizedContent::from_trusted_unchecked`.
+    pub unsafe fn trusted_js<S: Into<String>>(content: S) -> SoyValue {
+        SanitizedContent::from_trusted_unchecked(ContentKind::Js, content).into()
+    }
+
+    /// CSS that is printed as is in CSS templates, rather than being filtered.
+    ///
+    /// # Safety
+    ///
+    /// See `SanitizedContent::from_trusted_unchecked`.
+    pub unsafe fn trusted_css<S: Into<String>>(content: S) -> SoyValue {
+        SanitizedContent::from_trusted_unchecked(ContentKind::Css, content).into()
+    }
+
+    /// A URI that is printed as is in URI templates, rather than being percent-encoded.
+    ///
+    /// # Safety
+    ///
+    /// See `SanitizedContent::from_trusted_unchecked`.
+    pub unsafe fn trusted_uri<S: Into<String>>(content: S) -> SoyValue {
+        SanitizedContent::from_trusted_unchecked(ContentKind::Uri, content).into()
+    }
+
+    /// A URI that is trusted to load resources such as scripts from, which can be printed
+    /// in both URI and trusted resource URI templates.
+    ///
+    /// # Safety
+    ///
+    /// See `SanitizedContent::from_trusted_unchecked`.
+    pub unsafe fn trusted_resource_uri<S: Into<String>>(content: S) -> SoyValue {
+        SanitizedContent::from_trusted_unchecked(ContentKind::TrustedResourceUri, content).into()
+    }
+}
+
+impl SoyValue {
+    pub fn is_null(&self) -> bool {
+        *self == SoyValue::Null
+    }
+
+    /// Whether the value counts as true in a condition. `null`, `false`, zero, `NaN` and
+    /// the empty string are false; everything else (including empty lists and maps) is true.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            SoyValue::Null => false,
+            SoyValue::Bool(b) => *b,
+            SoyValue::Int(i) => *i != 0,
+            SoyValue::Float(f) => *f != 0.0 && !f.is_nan(),
+            SoyValue::String(s) => !s.is_empty(),
+            SoyValue::Sanitized(sanitized) => !sanitized.content.is_empty(),
+            SoyValue::List(_) | SoyValue::Map(_) => true,
+        }
+    }
+
+    /// The value as a float, if it is a number.
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            SoyValue::Int(i) => Some(*i as f64),
+            SoyValue::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            SoyValue::Null => "null",
+            SoyValue::Bool(_) => "bool",
+            SoyValue::Int(_) => "int",
+            SoyValue::Float(_) => "float",
+            SoyValue::String(_) => "string",
+            SoyValue::List(_) => "list",
+            SoyValue::Map(_) => "map",
+            SoyValue::Sanitized(sanitized) => match sanitized.kind {
+                ContentKind::Html => "html",
+                ContentKind::Text => "text",
+                ContentKind::Attributes => "attributes",
+                ContentKind::Uri => "uri",
+                ContentKind::TrustedResourceUri => "trusted_resource_uri",
+                ContentKind::Css => "css",
+                ContentKind::Js => "js",
+            },
+        }
+    }
+}
+
+/// Formats the value the way it is printed by a template.
+impl fmt::Display for SoyValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SoyValue::Null => write!(f, "null"),
+            SoyValue::Bool(b) => write!(f, "{}", b),
+            SoyValue::Int(i) => write!(f, "{}", i),
+            SoyValue::Float(n) if n.is_infinite() => {
+                write!(f, "{}Infinity", if *n < 0.0 { "-" } else { "" })
+            }
+            SoyValue::Float(n) => write!(f, "{}", n),
+            SoyValue::String(s) => write!(f, "{}", s),
+            SoyValue::Sanitized(sanitized) => write!(f, "{}", sanitized.content),
+            SoyValue::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            SoyValue::Map(entries) => {
+                // Sorted so the output is stable
+                let mut entries: Vec<_> = entries.iter().collect();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Parameters for a template, which can be set on a renderer all at once with
+/// `Renderer::params`. Usually derived, with `#[derive(SoyParams)]`, which also converts
+/// the struct to a map so it can be passed as a parameter itself.
+pub trait SoyParams {
+    /// The values of the parameters, by name.
+    fn into_params(self) -> HashMap<String, SoyValue>;
+}
+
+impl From<bool> for SoyValue {
+    fn from(value: bool) -> Self {
+        SoyValue::Bool(value)
+    }
+}
+
+impl From<i32> for SoyValue {
+    fn from(value: i32) -> Self {
+        SoyValue::Int(value.into())
+    }
+}
+
+impl From<i64> for SoyValue {
+    fn from(value: i64) -> Self {
+        SoyValue::Int(value)
+    }
+}
+
+impl From<f64> for SoyValue {
+    fn from(value: f64) -> Self {
+        SoyValue::Float(value)
+    }
+}
+
+impl From<&str> for SoyValue {
+    fn from(value: &str) -> Self {
+        SoyValue::String(value.to_owned())
+    }
+}
+
+impl From<String> for SoyValue {
+    fn from(value: String) -> Self {
+        SoyValue::String(value)
+    }
+}
+
+impl From<SanitizedContent> for SoyValue {
+    fn from(value: SanitizedContent) -> Self {
+        SoyValue::Sanitized(value)
+    }
+}
+
+impl<T: Into<SoyValue>> From<Option<T>> for SoyValue {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(SoyValue::Null, Into::into)
+    }
+}
+
+impl<T: Into<SoyValue>> From<Vec<T>> for SoyValue {
+    fn from(value: Vec<T>) -> Self {
+        SoyValue::List(value.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<SoyValue>> From<HashMap<String, T>> for SoyValue {
+    fn from(value: HashMap<String, T>) -> Self {
+        SoyValue::Map(value.into_iter().map(|(k, v)| (k, v.into())).collect())
+    }
+}
@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// Compiling a template also checks and lowers what was parsed, which must not panic on any
+// input either.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(input) = std::str::from_utf8(data) {
+        let _ = soy::Tofu::with_string_template(input);
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// The parser must report a `CompileError` (rather than panic or overflow the stack) on
+// any input.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(input) = std::str::from_utf8(data) {
+        let _ = soy_frontend::parser::parse(input);
+    }
+});
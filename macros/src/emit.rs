@@ -0,0 +1,552 @@
+//! Writes parsed templates as Rust expressions that recreate them, so that they don't have
+//! to be parsed at run time.
+
+use std::collections::HashMap;
+
+use soy_frontend::ast::*;
+use soy_frontend::error::TemplateLocation;
+
+const AST: &str = "::soy::runtime::ast";
+
+pub(crate) trait Emit {
+    /// Writes an expression for the value.
+    fn emit(&self, code: &mut String);
+}
+
+/// Writes a struct expression, or a struct-like enum variant, e.g. `Command::If { .. }`.
+fn emit_struct(code: &mut String, name: &str, fields: &[(&str, &dyn Emit)]) {
+    emit_struct_at(code, &format!("{}::{}", AST, name), fields);
+}
+
+/// Writes a struct expression for a type outside of the AST, given by its full path.
+fn emit_struct_at(code: &mut String, path: &str, fields: &[(&str, &dyn Emit)]) {
+    code.push_str(path);
+    code.push_str(" { ");
+    for (field, value) in fields {
+        code.push_str(field);
+        code.push_str(": ");
+        value.emit(code);
+        code.push_str(", ");
+    }
+    code.push('}');
+}
+
+/// Writes a tuple-like enum variant, or a unit variant if there are no values.
+fn emit_variant(code: &mut String, name: &str, values: &[&dyn Emit]) {
+    code.push_str(&format!("{}::{}", AST, name));
+    if !values.is_empty() {
+        code.push('(');
+        for value in values {
+            value.emit(code);
+            code.push_str(", ");
+        }
+        code.push(')');
+    }
+}
+
+impl Emit for String {
+    fn emit(&self, code: &mut String) {
+        code.push_str(&format!("::std::string::String::from({:?})", self));
+    }
+}
+
+impl Emit for bool {
+    fn emit(&self, code: &mut String) {
+        code.push_str(&self.to_string());
+    }
+}
+
+impl Emit for i64 {
+    fn emit(&self, code: &mut String) {
+        code.push_str(&format!("{}i64", self));
+    }
+}
+
+impl Emit for u64 {
+    fn emit(&self, code: &mut String) {
+        code.push_str(&format!("{}u64", self));
+    }
+}
+
+impl Emit for usize {
+    fn emit(&self, code: &mut String) {
+        code.push_str(&format!("{}usize", self));
+    }
+}
+
+impl Emit for f64 {
+    fn emit(&self, code: &mut String) {
+        // Written as bits so that every value round trips
+        code.push_str(&format!("f64::from_bits({:#x})", self.to_bits()));
+    }
+}
+
+impl<T: Emit> Emit for Option<T> {
+    fn emit(&self, code: &mut String) {
+        match self {
+            Some(value) => {
+                code.push_str("::std::option::Option::Some(");
+                value.emit(code);
+                code.push(')');
+            }
+            None => code.push_str("::std::option::Option::None"),
+        }
+    }
+}
+
+impl<T: Emit> Emit for Box<T> {
+    fn emit(&self, code: &mut String) {
+        code.push_str("::std::boxed::Box::new(");
+        (**self).emit(code);
+        code.push(')');
+    }
+}
+
+impl<T: Emit> Emit for Vec<T> {
+    fn emit(&self, code: &mut String) {
+        code.push_str("::std::vec![");
+        for item in self {
+            item.emit(code);
+            code.push_str(", ");
+        }
+        code.push(']');
+    }
+}
+
+impl<T: Emit> Emit for HashMap<String, T> {
+    fn emit(&self, code: &mut String) {
+        // Sorted so that the output is the same on every build
+        let mut entries: Vec<_> = self.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        code.push_str("::std::vec![");
+        for (key, value) in entries {
+            code.push('(');
+            key.emit(code);
+            code.push_str(", ");
+            value.emit(code);
+            code.push_str("), ");
+        }
+        code.push_str("].into_iter().collect()");
+    }
+}
+
+impl Emit for SoyFile {
+    fn emit(&self, code: &mut String) {
+        emit_struct(
+            code,
+            "SoyFile",
+            &[
+                ("namespace", &self.namespace),
+                ("aliases", &self.aliases),
+                ("delpackage", &self.delpackage),
+                ("templates", &self.templates),
+            ],
+        );
+    }
+}
+
+impl Emit for Namespace {
+    fn emit(&self, code: &mut String) {
+        emit_struct(
+            code,
+            "Namespace",
+            &[("name", &self.name), ("attributes", &self.attributes)],
+        );
+    }
+}
+
+impl Emit for NamespaceAttributes {
+    fn emit(&self, code: &mut String) {
+        emit_struct(
+            code,
+            "NamespaceAttributes",
+            &[
+                ("autoescape", &self.autoescape),
+                ("require_css", &self.require_css),
+                ("css_base", &self.css_base),
+            ],
+        );
+    }
+}
+
+impl Emit for Alias {
+    fn emit(&self, code: &mut String) {
+        emit_struct(code, "Alias", &[("from", &self.from), ("to", &self.to)]);
+    }
+}
+
+impl Emit for Template {
+    fn emit(&self, code: &mut String) {
+        emit_struct(
+            code,
+            "Template",
+            &[
+                ("name", &self.name),
+                ("attributes", &self.attributes),
+                ("body", &self.body),
+                ("soydoc_params", &self.soydoc_params),
+                ("location", &self.location),
+            ],
+        );
+    }
+}
+
+impl Emit for Location {
+    fn emit(&self, code: &mut String) {
+        emit_variant(code, "Location", &[&self.0]);
+    }
+}
+
+impl Emit for TemplateLocation {
+    fn emit(&self, code: &mut String) {
+        emit_struct_at(
+            code,
+            "::soy::error::TemplateLocation",
+            &[
+                ("filename", &self.filename),
+                ("template_name", &self.template_name),
+                ("line_number", &self.line_number),
+                ("column", &self.column),
+                ("length", &self.length),
+                ("snippet", &self.snippet),
+            ],
+        );
+    }
+}
+
+impl Emit for TemplateAttributes {
+    fn emit(&self, code: &mut String) {
+        emit_struct(
+            code,
+            "TemplateAttributes",
+            &[
+                ("visibility", &self.visibility),
+                ("kind", &self.kind),
+                ("autoescape", &self.autoescape),
+                ("require_css", &self.require_css),
+                ("css_base", &self.css_base),
+                ("strict_html", &self.strict_html),
+            ],
+        );
+    }
+}
+
+impl Emit for Visibility {
+    fn emit(&self, code: &mut String) {
+        emit_variant(code, &format!("Visibility::{:?}", self), &[]);
+    }
+}
+
+impl Emit for ContentKind {
+    fn emit(&self, code: &mut String) {
+        emit_variant(code, &format!("ContentKind::{:?}", self), &[]);
+    }
+}
+
+impl Emit for AutoescapeMode {
+    fn emit(&self, code: &mut String) {
+        emit_variant(code, &format!("AutoescapeMode::{:?}", self), &[]);
+    }
+}
+
+impl Emit for TemplateNode {
+    fn emit(&self, code: &mut String) {
+        match self {
+            TemplateNode::RawText(text) => emit_variant(code, "TemplateNode::RawText", &[text]),
+            TemplateNode::Statement(command) => {
+                emit_variant(code, "TemplateNode::Statement", &[command])
+            }
+            TemplateNode::Special(text) => emit_variant(code, "TemplateNode::Special", &[text]),
+        }
+    }
+}
+
+impl Emit for SoydocParam {
+    fn emit(&self, code: &mut String) {
+        emit_struct(
+            code,
+            "SoydocParam",
+            &[("name", &self.name), ("required", &self.required)],
+        );
+    }
+}
+
+impl Emit for Command {
+    fn emit(&self, code: &mut String) {
+        match self {
+            Command::If {
+                if_block,
+                else_ifs,
+                else_block,
+            } => emit_struct(
+                code,
+                "Command::If",
+                &[
+                    ("if_block", if_block),
+                    ("else_ifs", else_ifs),
+                    ("else_block", else_block),
+                ],
+            ),
+            Command::Msg {
+                id,
+                desc,
+                meaning,
+                body,
+            } => emit_struct(
+                code,
+                "Command::Msg",
+                &[
+                    ("id", id),
+                    ("desc", desc),
+                    ("meaning", meaning),
+                    ("body", body),
+                ],
+            ),
+            Command::Print {
+                expression,
+                directives,
+                phname,
+            } => emit_struct(
+                code,
+                "Command::Print",
+                &[
+                    ("expression", expression),
+                    ("directives", directives),
+                    ("phname", phname),
+                ],
+            ),
+            Command::Literal(text) => emit_variant(code, "Command::Literal", &[text]),
+            Command::Css { base, name } => {
+                emit_struct(code, "Command::Css", &[("base", base), ("name", name)])
+            }
+            Command::Xid(name) => emit_variant(code, "Command::Xid", &[name]),
+            Command::Call(call) => emit_variant(code, "Command::Call", &[call]),
+        }
+    }
+}
+
+impl Emit for Call {
+    fn emit(&self, code: &mut String) {
+        emit_struct(
+            code,
+            "Call",
+            &[
+                ("target", &self.target),
+                ("data", &self.data),
+                ("params", &self.params),
+                ("phname", &self.phname),
+                ("location", &self.location),
+            ],
+        );
+    }
+}
+
+impl Emit for CallData {
+    fn emit(&self, code: &mut String) {
+        match self {
+            CallData::All => emit_variant(code, "CallData::All", &[]),
+            CallData::Expression(expression) => {
+                emit_variant(code, "CallData::Expression", &[expression])
+            }
+        }
+    }
+}
+
+impl Emit for CallParam {
+    fn emit(&self, code: &mut String) {
+        emit_struct(
+            code,
+            "CallParam",
+            &[("name", &self.name), ("value", &self.value)],
+        );
+    }
+}
+
+impl Emit for CallParamValue {
+    fn emit(&self, code: &mut String) {
+        match self {
+            CallParamValue::Expression(expression) => {
+                emit_variant(code, "CallParamValue::Expression", &[expression])
+            }
+            CallParamValue::Block { kind, block } => emit_struct(
+                code,
+                "CallParamValue::Block",
+                &[("kind", kind), ("block", block)],
+            ),
+        }
+    }
+}
+
+impl Emit for ConditionalBlock {
+    fn emit(&self, code: &mut String) {
+        emit_struct(
+            code,
+            "ConditionalBlock",
+            &[("expression", &self.expression), ("block", &self.block)],
+        );
+    }
+}
+
+impl Emit for MsgBody {
+    fn emit(&self, code: &mut String) {
+        match self {
+            MsgBody::Plural {
+                expression,
+                offset,
+                cases,
+                default,
+            } => emit_struct(
+                code,
+                "MsgBody::Plural",
+                &[
+                    ("expression", expression),
+                    ("offset", offset),
+                    ("cases", cases),
+                    ("default", default),
+                ],
+            ),
+            MsgBody::Select {
+                expression,
+                cases,
+                default,
+            } => emit_struct(
+                code,
+                "MsgBody::Select",
+                &[
+                    ("expression", expression),
+                    ("cases", cases),
+                    ("default", default),
+                ],
+            ),
+            MsgBody::Block(block) => emit_variant(code, "MsgBody::Block", &[block]),
+        }
+    }
+}
+
+impl Emit for PluralCase {
+    fn emit(&self, code: &mut String) {
+        emit_struct(
+            code,
+            "PluralCase",
+            &[("expression", &self.expression), ("body", &self.body)],
+        );
+    }
+}
+
+impl Emit for SelectCase {
+    fn emit(&self, code: &mut String) {
+        emit_struct(
+            code,
+            "SelectCase",
+            &[("value", &self.value), ("body", &self.body)],
+        );
+    }
+}
+
+impl Emit for PrintDirective {
+    fn emit(&self, code: &mut String) {
+        emit_struct(
+            code,
+            "PrintDirective",
+            &[("name", &self.name), ("arguments", &self.arguments)],
+        );
+    }
+}
+
+impl Emit for Expression {
+    fn emit(&self, code: &mut String) {
+        match self {
+            Expression::Null => emit_variant(code, "Expression::Null", &[]),
+            Expression::Boolean(b) => emit_variant(code, "Expression::Boolean", &[b]),
+            Expression::Float(f) => emit_variant(code, "Expression::Float", &[f]),
+            Expression::Integer(i) => emit_variant(code, "Expression::Integer", &[i]),
+            Expression::String(s) => emit_variant(code, "Expression::String", &[s]),
+            Expression::List(items) => emit_variant(code, "Expression::List", &[items]),
+            Expression::Map(entries) => emit_variant(code, "Expression::Map", &[entries]),
+            Expression::Function { name, parameters } => emit_struct(
+                code,
+                "Expression::Function",
+                &[("name", name), ("parameters", parameters)],
+            ),
+            Expression::DataReference {
+                referent,
+                references,
+            } => emit_struct(
+                code,
+                "Expression::DataReference",
+                &[("referent", referent), ("references", references)],
+            ),
+            Expression::GlobalReference(name) => {
+                emit_variant(code, "Expression::GlobalReference", &[name])
+            }
+            Expression::BinaryOperation { lhs, op, rhs } => emit_struct(
+                code,
+                "Expression::BinaryOperation",
+                &[("lhs", lhs), ("op", op), ("rhs", rhs)],
+            ),
+            Expression::UnaryOperation { op, rhs } => emit_struct(
+                code,
+                "Expression::UnaryOperation",
+                &[("op", op), ("rhs", rhs)],
+            ),
+            Expression::TernaryOperation {
+                condition,
+                if_true,
+                if_false,
+            } => emit_struct(
+                code,
+                "Expression::TernaryOperation",
+                &[
+                    ("condition", condition),
+                    ("if_true", if_true),
+                    ("if_false", if_false),
+                ],
+            ),
+        }
+    }
+}
+
+impl Emit for BinaryOperator {
+    fn emit(&self, code: &mut String) {
+        emit_variant(code, &format!("BinaryOperator::{:?}", self), &[]);
+    }
+}
+
+impl Emit for UnaryOperator {
+    fn emit(&self, code: &mut String) {
+        emit_variant(code, &format!("UnaryOperator::{:?}", self), &[]);
+    }
+}
+
+impl Emit for Referent {
+    fn emit(&self, code: &mut String) {
+        match self {
+            Referent::Variable(name) => emit_variant(code, "Referent::Variable", &[name]),
+            Referent::Injected(name) => emit_variant(code, "Referent::Injected", &[name]),
+        }
+    }
+}
+
+impl Emit for Reference {
+    fn emit(&self, code: &mut String) {
+        match self {
+            Reference::Dotted(key) => emit_variant(code, "Reference::Dotted", &[key]),
+            Reference::QuestionDotted(key) => {
+                emit_variant(code, "Reference::QuestionDotted", &[key])
+            }
+            Reference::Bracketed(key) => emit_variant(code, "Reference::Bracketed", &[key]),
+            Reference::QuestionBracketed(key) => {
+                emit_variant(code, "Reference::QuestionBracketed", &[key])
+            }
+        }
+    }
+}
+
+impl Emit for ReferenceKey {
+    fn emit(&self, code: &mut String) {
+        match self {
+            ReferenceKey::Number(i) => emit_variant(code, "ReferenceKey::Number", &[i]),
+            ReferenceKey::Name(name) => emit_variant(code, "ReferenceKey::Name", &[name]),
+        }
+    }
+}
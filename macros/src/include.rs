@@ -0,0 +1,136 @@
+use std::collections::hash_map::{Entry, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use soy_frontend::ast::SoyFile;
+use soy_frontend::error::{CompileError, CompileErrorKind, Diagnostics, TemplateLocation};
+use soy_frontend::link::Linker;
+use soy_frontend::parser;
+
+use crate::emit::Emit;
+
+/// The code for `include_templates!`: an expression creating a `Tofu` from the files
+/// matching a pattern, or the errors found in them.
+pub(crate) fn include_templates(pattern: &str, root: &Path) -> Result<String, Vec<String>> {
+    let (paths, files) = parse_files(pattern, root)?;
+    let mut code = format!("{{ {} ::soy::runtime::tofu(", track_files(&paths));
+    files.emit(&mut code);
+    code.push_str(") }");
+    Ok(code)
+}
+
+/// Parses the soy files matching a glob pattern, relative to `root`, returning their
+/// paths and contents, or the errors found in them.
+pub(crate) fn parse_files(
+    pattern: &str,
+    root: &Path,
+) -> Result<(Vec<PathBuf>, Vec<SoyFile>), Vec<String>> {
+    let full_pattern = root.join(pattern);
+    let paths = glob::glob(&full_pattern.to_string_lossy())
+        .map_err(|e| vec![format!("invalid pattern `{}`: {}", pattern, e)])?;
+    let mut paths = paths
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| vec![e.to_string()])?;
+    if paths.is_empty() {
+        return Err(vec![format!("no soy files match `{}`", pattern)]);
+    }
+    paths.sort();
+
+    let mut diagnostics = Diagnostics::default();
+    let mut files = vec![];
+    // Where each template was defined, by fully-qualified name
+    let mut definitions: HashMap<String, TemplateLocation> = HashMap::new();
+    let mut linker = Linker::default();
+    for path in &paths {
+        let filename = path.strip_prefix(root).unwrap_or(path).to_string_lossy();
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                diagnostics.push(CompileError {
+                    kind: CompileErrorKind::Io(filename.into_owned(), e),
+                    location: None,
+                    cause: None,
+                });
+                continue;
+            }
+        };
+        if let Some(file) = parser::parse_file(Some(&filename), &content, &mut diagnostics) {
+            for template in &file.templates {
+                let name = format!("{}.{}", file.namespace.name, template.name);
+                match definitions.entry(name) {
+                    Entry::Occupied(entry) => diagnostics.push(CompileError {
+                        kind: CompileErrorKind::DuplicateTemplate(
+                            entry.key().clone(),
+                            Box::new(entry.get().clone()),
+                        ),
+                        location: Some(Box::new(template.location.0.clone())),
+                        cause: None,
+                    }),
+                    Entry::Vacant(entry) => {
+                        entry.insert(template.location.0.clone());
+                    }
+                }
+            }
+            linker.add_file(&file);
+            files.push(file);
+        }
+    }
+    linker.check(&mut diagnostics);
+    if diagnostics.has_errors() {
+        return Err(diagnostics.errors().map(message).collect());
+    }
+    Ok((paths, files))
+}
+
+/// The message for an error found in a template. Macros can only report errors at the
+/// tokens they were given, so the error's location in the template comes first, as
+/// `path:line:column`, where editors and terminals look for it.
+fn message(error: &CompileError) -> String {
+    match &error.location {
+        Some(location) => format!(
+            "{}:{}:{}: {} [{}]\n{}",
+            location.filename.as_deref().unwrap_or("<input>"),
+            location.line_number,
+            location.column,
+            error.kind,
+            error.kind.code(),
+            location
+        ),
+        None => format!("{} [{}]", error.kind, error.kind.code()),
+    }
+}
+
+/// An item that includes the files, which makes the compiler rebuild the crate when they
+/// change.
+pub(crate) fn track_files(paths: &[PathBuf]) -> String {
+    let mut code = String::from("const _: &[&str] = &[");
+    for path in paths {
+        code.push_str(&format!("include_str!({:?}), ", path.to_string_lossy()));
+    }
+    code.push_str("];");
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_start_with_template_location() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let errors = parse_files("tests/invalid/*.soy", root).expect_err("should fail");
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(
+            errors[0].starts_with(
+                "tests/invalid/unknown.soy:3:17: Unknown function: frobnicate [E0014]\n"
+            ),
+            "{}",
+            errors[0]
+        );
+        assert!(
+            errors[1].starts_with("tests/invalid/unknown.soy:3:34: "),
+            "{}",
+            errors[1]
+        );
+    }
+}
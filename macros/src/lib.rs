@@ -0,0 +1,53 @@
+//! Macros for soy, which are re-exported from it (e.g. `soy::include_templates!` and
+//! `#[derive(soy::SoyParams)]`).
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use std::env;
+use std::path::Path;
+use syn::{parse_macro_input, DeriveInput, LitStr};
+
+mod emit;
+mod include;
+mod params;
+
+/// Compiles the soy files matching a glob pattern (relative to the crate's manifest) into
+/// a `Tofu`, e.g. `soy::include_templates!("templates/**/*.soy")`.
+///
+/// The files are parsed when the crate is built, so any errors in them are reported as
+/// compiler errors (at the pattern, but starting with the error's `path:line:column`), and
+/// the `Tofu` is created without reading or parsing anything. The crate is rebuilt when the
+/// files change, but not when files are added, since the compiler can only track files it
+/// has been given.
+#[proc_macro]
+pub fn include_templates(input: TokenStream) -> TokenStream {
+    let pattern = parse_macro_input!(input as LitStr);
+    let root = env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".into());
+    match include::include_templates(&pattern.value(), Path::new(&root)) {
+        Ok(code) => code.parse().expect("generated code is valid"),
+        Err(errors) => {
+            let errors = errors
+                .into_iter()
+                .map(|error| syn::Error::new(pattern.span(), error).to_compile_error());
+            // In a block, since the macro is used as an expression
+            quote::quote!({ #(#errors)* }).into()
+        }
+    }
+}
+
+/// Implements `SoyParams` for a struct, passing each field as the parameter of the same
+/// name in lower camel case (e.g. `avatar_url` as `avatarUrl`), or the name given with
+/// `#[soy(rename = "...")]`. Fields are converted with `Into<SoyValue>`.
+///
+/// With `#[soy(template = "...", path = "...")]` on the struct, the fields are checked
+/// against the parameters the template declares, where the path is a glob pattern for
+/// the soy files it is in, as for `include_templates!`.
+#[proc_macro_derive(SoyParams, attributes(soy))]
+pub fn derive_soy_params(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let root = env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".into());
+    params::derive(&input, Path::new(&root))
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
@@ -0,0 +1,245 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use soy_frontend::ast::Template;
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Error, Fields, LitStr, Type};
+
+use crate::include;
+
+/// A field of the struct, and the parameter it is passed as.
+struct Param<'a> {
+    field: &'a syn::Field,
+    name: String,
+}
+
+/// The template the struct is checked against, given with
+/// `#[soy(template = "...", path = "...")]`.
+struct Target {
+    template: LitStr,
+    path: LitStr,
+}
+
+/// The code for `#[derive(SoyParams)]`.
+pub(crate) fn derive(input: &DeriveInput, root: &Path) -> Result<TokenStream, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(not_supported(input)),
+        },
+        _ => return Err(not_supported(input)),
+    };
+    let mut params = vec![];
+    for field in fields {
+        params.push(Param {
+            field,
+            name: param_name(field)?,
+        });
+    }
+    let tracked = match target(input)? {
+        Some(target) => check(&target, &params, input.ident.span(), root)?,
+        None => String::new(),
+    }
+    .parse::<TokenStream>()
+    .expect("tracked files are valid");
+
+    let ident = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let names = params.iter().map(|param| &param.name);
+    let fields = params.iter().map(|param| &param.field.ident);
+    Ok(quote! {
+        #tracked
+
+        impl #impl_generics ::soy::SoyParams for #ident #type_generics #where_clause {
+            fn into_params(
+                self,
+            ) -> ::std::collections::HashMap<::std::string::String, ::soy::SoyValue> {
+                let mut params = ::std::collections::HashMap::new();
+                #(
+                    params.insert(
+                        ::std::string::String::from(#names),
+                        ::std::convert::Into::<::soy::SoyValue>::into(self.#fields),
+                    );
+                )*
+                params
+            }
+        }
+
+        impl #impl_generics ::std::convert::From<#ident #type_generics> for ::soy::SoyValue
+        #where_clause
+        {
+            fn from(params: #ident #type_generics) -> Self {
+                ::soy::SoyValue::Map(::soy::SoyParams::into_params(params))
+            }
+        }
+    })
+}
+
+fn not_supported(input: &DeriveInput) -> Error {
+    Error::new(
+        input.ident.span(),
+        "SoyParams can only be derived for structs with named fields",
+    )
+}
+
+/// The parameter a field is passed as: its name in lower camel case, as parameters are
+/// usually named, unless it is renamed with `#[soy(rename = "...")]`.
+fn param_name(field: &syn::Field) -> Result<String, Error> {
+    let mut name = None;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("soy"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                name = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("unknown soy attribute, expected `rename`"))
+            }
+        })?;
+    }
+    let ident = field.ident.as_ref().expect("fields are named");
+    Ok(name.unwrap_or_else(|| lower_camel_case(&ident.to_string())))
+}
+
+fn target(input: &DeriveInput) -> Result<Option<Target>, Error> {
+    let (mut template, mut path) = (None, None);
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("soy"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("template") {
+                template = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("path") {
+                path = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unknown soy attribute, expected `template` or `path`"))
+            }
+        })?;
+    }
+    match (template, path) {
+        (Some(template), Some(path)) => Ok(Some(Target { template, path })),
+        (None, None) => Ok(None),
+        (Some(template), None) => Err(Error::new(
+            template.span(),
+            "`template` needs a `path` to the soy files it is in",
+        )),
+        (None, Some(path)) => Err(Error::new(
+            path.span(),
+            "`path` needs the name of a `template` to check against",
+        )),
+    }
+}
+
+/// Checks that the fields match the parameters the template declares, returning the code
+/// that tracks the files it was found in.
+fn check(target: &Target, params: &[Param], span: Span, root: &Path) -> Result<String, Error> {
+    let (paths, files) = include::parse_files(&target.path.value(), root).map_err(|errors| {
+        let mut errors = errors
+            .into_iter()
+            .map(|error| Error::new(target.path.span(), error));
+        let mut combined = errors.next().expect("errors are reported");
+        combined.extend(errors);
+        combined
+    })?;
+    let name = target.template.value();
+    let template = files
+        .iter()
+        .flat_map(|file| {
+            file.templates.iter().map(move |template| {
+                (
+                    format!("{}.{}", file.namespace.name, template.name),
+                    template,
+                )
+            })
+        })
+        .find(|(qualified_name, _)| *qualified_name == name)
+        .map(|(_, template)| template)
+        .ok_or_else(|| {
+            Error::new(
+                target.template.span(),
+                format!("no template named `{}` in `{}`", name, target.path.value()),
+            )
+        })?;
+
+    let mut errors = vec![];
+    let names: HashSet<_> = params.iter().map(|param| param.name.as_str()).collect();
+    for param in params {
+        match declared(template, &param.name) {
+            None => errors.push(Error::new(
+                param.field.span(),
+                format!("`{}` is not a parameter of `{}`", param.name, name),
+            )),
+            Some(true) if is_option(&param.field.ty) => errors.push(Error::new(
+                param.field.ty.span(),
+                format!(
+                    "`{}` is required by `{}`, so can't be optional",
+                    param.name, name
+                ),
+            )),
+            Some(_) => {}
+        }
+    }
+    for param in &template.soydoc_params {
+        if param.required && !names.contains(param.name.as_str()) {
+            errors.push(Error::new(
+                span,
+                format!("missing `{}`, which is required by `{}`", param.name, name),
+            ));
+        }
+    }
+    let mut errors = errors.into_iter();
+    match errors.next() {
+        Some(mut combined) => {
+            combined.extend(errors);
+            Err(combined)
+        }
+        None => Ok(include::track_files(&paths)),
+    }
+}
+
+/// Whether a template declares a parameter, and if so whether it is required.
+fn declared(template: &Template, name: &str) -> Option<bool> {
+    template
+        .soydoc_params
+        .iter()
+        .find(|param| param.name == name)
+        .map(|param| param.required)
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+/// Converts a name in snake case to lower camel case, e.g. `avatar_url` to `avatarUrl`.
+fn lower_camel_case(name: &str) -> String {
+    let name = name.trim_start_matches("r#");
+    let mut camel = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = !camel.is_empty();
+        } else if upper {
+            camel.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            camel.push(c);
+        }
+    }
+    camel
+}
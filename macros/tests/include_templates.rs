@@ -0,0 +1,47 @@
+use soy::{SoyValue, Tofu};
+
+#[test]
+fn test_include_templates() {
+    let tofu = soy::include_templates!("tests/templates/**/*.soy");
+    let output = tofu
+        .renderer("tests.greeting.hello")
+        .param("name", "<World>")
+        .param("count", 3)
+        .render_to_string()
+        .unwrap();
+    assert_eq!(output, "Hello &lt;World&gt;!3 new messages");
+    let output = tofu
+        .renderer("tests.list.list")
+        .param("items", SoyValue::from(vec!["a"]))
+        .render_to_string()
+        .unwrap();
+    assert_eq!(output, "<ul><li>a</li><li>none</li><li>1.5</li></ul>");
+    let output = tofu
+        .renderer("tests.list.greetFirst")
+        .param("items", SoyValue::from(vec!["<a>"]))
+        .render_to_string()
+        .unwrap();
+    assert_eq!(output, "<p>Hello &lt;a&gt;!</p>");
+}
+
+#[test]
+fn test_included_templates_match_parsed_templates() {
+    let included = soy::include_templates!("tests/templates/greeting.soy");
+    let parsed = Tofu::builder()
+        .add_file(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/templates/greeting.soy"
+        ))
+        .build()
+        .unwrap();
+    for count in 0..3 {
+        let render = |tofu: &Tofu| {
+            tofu.renderer("tests.greeting.hello")
+                .param("name", "Alex")
+                .param("count", count)
+                .render_to_string()
+                .unwrap()
+        };
+        assert_eq!(render(&included), render(&parsed));
+    }
+}
@@ -0,0 +1,60 @@
+use soy::{SoyParams, SoyValue};
+use std::collections::HashMap;
+
+#[derive(SoyParams)]
+#[soy(template = "tests.greeting.hello", path = "tests/templates/*.soy")]
+struct Hello {
+    name: String,
+    count: Option<i64>,
+}
+
+#[derive(SoyParams)]
+struct UserCard {
+    display_name: &'static str,
+    #[soy(rename = "avatarUrl")]
+    avatar: Option<String>,
+    r#type: i32,
+}
+
+#[test]
+fn test_into_params() {
+    let params = UserCard {
+        display_name: "Alex",
+        avatar: None,
+        r#type: 2,
+    }
+    .into_params();
+    let mut expected = HashMap::new();
+    expected.insert("displayName".to_owned(), SoyValue::from("Alex"));
+    expected.insert("avatarUrl".to_owned(), SoyValue::Null);
+    expected.insert("type".to_owned(), SoyValue::from(2));
+    assert_eq!(params, expected);
+}
+
+#[test]
+fn test_params_as_a_value() {
+    let card = UserCard {
+        display_name: "Alex",
+        avatar: Some("/alex.png".to_owned()),
+        r#type: 1,
+    };
+    let value = SoyValue::from(vec![card]);
+    assert_eq!(
+        value.to_string(),
+        "[{avatarUrl: /alex.png, displayName: Alex, type: 1}]"
+    );
+}
+
+#[test]
+fn test_render_with_params() {
+    let tofu = soy::include_templates!("tests/templates/*.soy");
+    let output = tofu
+        .renderer("tests.greeting.hello")
+        .params(Hello {
+            name: "Alex".to_owned(),
+            count: Some(1),
+        })
+        .render_to_string()
+        .unwrap();
+    assert_eq!(output, "Hello Alex!One new message");
+}
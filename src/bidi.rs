@@ -0,0 +1,222 @@
+//! Bidirectional text support: estimating the direction of text, and marking or wrapping
+//! it so that it displays correctly in a page of the opposite direction. The heuristics
+//! are the same as Closure's `goog.i18n.bidi`.
+
+#[cfg(test)]
+mod tests;
+
+/// A text direction, such as the overall direction of a page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BidiDir {
+    #[default]
+    Ltr,
+    Rtl,
+}
+
+impl BidiDir {
+    /// The direction of a locale's script, e.g. `Rtl` for `ar` or `he-IL`.
+    pub fn for_locale(locale: &str) -> BidiDir {
+        let locale = locale.replace('-', "_").to_ascii_lowercase();
+        let mut subtags = locale.split('_');
+        let language = subtags.next().unwrap_or("");
+        let script = subtags.find(|subtag| subtag.len() == 4);
+        let rtl = match script {
+            Some(script) => matches!(script, "arab" | "hebr" | "thaa" | "nkoo" | "tfng"),
+            None => matches!(
+                language,
+                "ar" | "ckb" | "dv" | "fa" | "he" | "iw" | "ji" | "ps" | "sd" | "ug" | "ur" | "yi"
+            ),
+        };
+        if rtl {
+            BidiDir::Rtl
+        } else {
+            BidiDir::Ltr
+        }
+    }
+
+    /// `1` for left to right, `-1` for right to left, as returned by `bidiGlobalDir()`.
+    pub(crate) fn value(self) -> i64 {
+        match self {
+            BidiDir::Ltr => 1,
+            BidiDir::Rtl => -1,
+        }
+    }
+
+    pub(crate) fn start_edge(self) -> &'static str {
+        match self {
+            BidiDir::Ltr => "left",
+            BidiDir::Rtl => "right",
+        }
+    }
+
+    pub(crate) fn end_edge(self) -> &'static str {
+        match self {
+            BidiDir::Ltr => "right",
+            BidiDir::Rtl => "left",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            BidiDir::Ltr => "ltr",
+            BidiDir::Rtl => "rtl",
+        }
+    }
+}
+
+const LRM: &str = "\u{200E}";
+const RLM: &str = "\u{200F}";
+const LRE: &str = "\u{202A}";
+const RLE: &str = "\u{202B}";
+const PDF: &str = "\u{202C}";
+
+fn is_rtl_char(c: char) -> bool {
+    matches!(c, '\u{0591}'..='\u{06EF}'
+        | '\u{06FA}'..='\u{07FF}'
+        | '\u{200F}'
+        | '\u{FB1D}'..='\u{FDFF}'
+        | '\u{FE70}'..='\u{FEFC}')
+}
+
+fn is_ltr_char(c: char) -> bool {
+    matches!(c, 'A'..='Z'
+        | 'a'..='z'
+        | '\u{00C0}'..='\u{00D6}'
+        | '\u{00D8}'..='\u{00F6}'
+        | '\u{00F8}'..='\u{02B8}'
+        | '\u{0300}'..='\u{0590}'
+        | '\u{0800}'..='\u{1FFF}'
+        | '\u{200E}'
+        | '\u{2C00}'..='\u{FB1C}'
+        | '\u{FE00}'..='\u{FE6F}'
+        | '\u{FEFD}'..='\u{FFFF}')
+}
+
+/// Removes HTML tags and entities, which don't affect the direction of the text.
+fn strip_html(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        let end = match c {
+            '<' => text[i..].find('>'),
+            '&' => text[i..].find(';'),
+            _ => None,
+        };
+        match end {
+            Some(end) => {
+                let resume = i + end;
+                for _ in chars.by_ref().take_while(|(j, _)| *j < resume) {}
+            }
+            None => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+/// The direction of the first (or, from the end, last) strongly directional character.
+fn first_strong_dir(mut chars: impl Iterator<Item = char>) -> Option<BidiDir> {
+    chars.find_map(|c| {
+        if is_rtl_char(c) {
+            Some(BidiDir::Rtl)
+        } else if is_ltr_char(c) {
+            Some(BidiDir::Ltr)
+        } else {
+            None
+        }
+    })
+}
+
+/// Estimates the direction of text from the proportion of its words that start with a
+/// right to left character, or `None` if the text has no directional characters.
+pub(crate) fn estimate_dir(text: &str, is_html: bool) -> Option<BidiDir> {
+    let stripped;
+    let text = if is_html {
+        stripped = strip_html(text);
+        &stripped
+    } else {
+        text
+    };
+    let mut rtl_count = 0;
+    let mut total_count = 0;
+    let mut has_weakly_ltr = false;
+    for word in text.split_whitespace() {
+        if first_strong_dir(word.chars()) == Some(BidiDir::Rtl) {
+            rtl_count += 1;
+            total_count += 1;
+        } else if word.starts_with("http://") {
+            has_weakly_ltr = true;
+        } else if word.chars().any(is_ltr_char) {
+            total_count += 1;
+        } else if word.chars().any(|c| c.is_ascii_digit()) {
+            has_weakly_ltr = true;
+        }
+    }
+    if total_count == 0 {
+        if has_weakly_ltr {
+            Some(BidiDir::Ltr)
+        } else {
+            None
+        }
+    } else if rtl_count as f64 / total_count as f64 > 0.4 {
+        Some(BidiDir::Rtl)
+    } else {
+        Some(BidiDir::Ltr)
+    }
+}
+
+/// The mark to put after text of the given direction so that what follows it keeps the
+/// global direction: LRM or RLM, or nothing if it isn't needed.
+pub(crate) fn mark_after(
+    global: BidiDir,
+    dir: Option<BidiDir>,
+    text: &str,
+    is_html: bool,
+) -> &'static str {
+    let stripped;
+    let text = if is_html {
+        stripped = strip_html(text);
+        &stripped
+    } else {
+        text
+    };
+    let exit_dir = first_strong_dir(text.chars().rev());
+    match global {
+        BidiDir::Ltr if dir == Some(BidiDir::Rtl) || exit_dir == Some(BidiDir::Rtl) => LRM,
+        BidiDir::Rtl if dir == Some(BidiDir::Ltr) || exit_dir == Some(BidiDir::Ltr) => RLM,
+        _ => "",
+    }
+}
+
+/// A `dir` attribute for an element containing the text, if its direction differs from
+/// the global direction. It is unquoted, so that it is unchanged by HTML escaping.
+pub(crate) fn dir_attr(global: BidiDir, text: &str, is_html: bool) -> String {
+    match estimate_dir(text, is_html) {
+        Some(dir) if dir != global => format!("dir={}", dir.name()),
+        _ => String::new(),
+    }
+}
+
+/// Wraps HTML in a span with a `dir` attribute if its direction differs from the global
+/// direction, followed by a mark if needed.
+pub(crate) fn span_wrap(global: BidiDir, html: &str) -> String {
+    let dir = estimate_dir(html, true);
+    let mut wrapped = match dir {
+        Some(dir) if dir != global => format!("<span dir=\"{}\">{}</span>", dir.name(), html),
+        _ => html.to_owned(),
+    };
+    wrapped.push_str(mark_after(global, dir, html, true));
+    wrapped
+}
+
+/// Wraps text in Unicode embedding characters if its direction differs from the global
+/// direction, followed by a mark if needed.
+pub(crate) fn unicode_wrap(global: BidiDir, text: &str, is_html: bool) -> String {
+    let dir = estimate_dir(text, is_html);
+    let mut wrapped = match dir {
+        Some(BidiDir::Rtl) if global == BidiDir::Ltr => format!("{}{}{}", RLE, text, PDF),
+        Some(BidiDir::Ltr) if global == BidiDir::Rtl => format!("{}{}{}", LRE, text, PDF),
+        _ => text.to_owned(),
+    };
+    wrapped.push_str(mark_after(global, dir, text, is_html));
+    wrapped
+}
@@ -0,0 +1,57 @@
+use super::*;
+
+const HEBREW: &str = "\u{05E9}\u{05DC}\u{05D5}\u{05DD}";
+
+#[test]
+fn test_for_locale() {
+    assert_eq!(BidiDir::for_locale("en"), BidiDir::Ltr);
+    assert_eq!(BidiDir::for_locale("ar"), BidiDir::Rtl);
+    assert_eq!(BidiDir::for_locale("he-IL"), BidiDir::Rtl);
+    assert_eq!(BidiDir::for_locale("az_Arab"), BidiDir::Rtl);
+    assert_eq!(BidiDir::for_locale("pa-Guru-IN"), BidiDir::Ltr);
+}
+
+#[test]
+fn test_estimate_dir() {
+    assert_eq!(estimate_dir("hello world", false), Some(BidiDir::Ltr));
+    assert_eq!(estimate_dir(HEBREW, false), Some(BidiDir::Rtl));
+    assert_eq!(estimate_dir("123", false), Some(BidiDir::Ltr));
+    assert_eq!(estimate_dir("!?", false), None);
+    // Two of three words are right to left
+    let mixed = format!("{} {} hello", HEBREW, HEBREW);
+    assert_eq!(estimate_dir(&mixed, false), Some(BidiDir::Rtl));
+    let mixed = format!("{} one two three", HEBREW);
+    assert_eq!(estimate_dir(&mixed, false), Some(BidiDir::Ltr));
+    // Tags and entities are ignored in HTML
+    let html = format!("<b class=\"x\">{}</b>&nbsp;", HEBREW);
+    assert_eq!(estimate_dir(&html, true), Some(BidiDir::Rtl));
+    assert_eq!(estimate_dir(&html, false), Some(BidiDir::Ltr));
+}
+
+#[test]
+fn test_mark_after() {
+    let rtl = Some(BidiDir::Rtl);
+    let ltr = Some(BidiDir::Ltr);
+    assert_eq!(mark_after(BidiDir::Ltr, rtl, HEBREW, false), LRM);
+    assert_eq!(mark_after(BidiDir::Ltr, ltr, "hello", false), "");
+    assert_eq!(mark_after(BidiDir::Rtl, ltr, "hello", false), RLM);
+    // Left to right text ending with a right to left word still needs a mark
+    let text = format!("one two {}", HEBREW);
+    assert_eq!(mark_after(BidiDir::Ltr, ltr, &text, false), LRM);
+}
+
+#[test]
+fn test_wrapping() {
+    assert_eq!(dir_attr(BidiDir::Ltr, HEBREW, false), "dir=rtl");
+    assert_eq!(dir_attr(BidiDir::Rtl, HEBREW, false), "");
+    assert_eq!(
+        span_wrap(BidiDir::Ltr, HEBREW),
+        format!("<span dir=\"rtl\">{}</span>{}", HEBREW, LRM)
+    );
+    assert_eq!(span_wrap(BidiDir::Ltr, "hello"), "hello");
+    assert_eq!(
+        unicode_wrap(BidiDir::Rtl, "hello", false),
+        format!("{}hello{}{}", LRE, PDF, RLM)
+    );
+    assert_eq!(unicode_wrap(BidiDir::Rtl, HEBREW, false), HEBREW);
+}
@@ -0,0 +1,107 @@
+use soy::MsgExtractor;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+
+static USAGE: &str = "\
+Usage: soy <command> [options]
+
+Commands:
+  extract [options] <file>...   Extracts the messages in soy files to XLIFF
+  js [options] <path>...        Compiles soy files (or directories of them) to JavaScript
+
+Options for extract:
+  -o, --output <file>           Writes to a file instead of stdout
+  --source-locale <locale>      The language of the messages (default: en)
+  --target-locale <locale>      The language to translate the messages into
+
+Options for js:
+  -o, --output <file>           Writes to a file instead of stdout
+";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("extract") => extract(&args[1..]),
+        Some("js") => js(&args[1..]),
+        Some("-h") | Some("--help") | Some("help") => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        Some(command) => Err(format!("unknown command: {}\n\n{}", command, USAGE)),
+        None => Err(USAGE.to_owned()),
+    };
+    if let Err(message) = result {
+        eprint!("{}", message);
+        if !message.ends_with('\n') {
+            eprintln!();
+        }
+        process::exit(1);
+    }
+}
+
+fn extract(args: &[String]) -> Result<(), String> {
+    let mut extractor = MsgExtractor::new();
+    let mut output = None;
+    let mut files = 0;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("{} requires a value\n\n{}", arg, USAGE))
+        };
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(value()?.clone()),
+            "--source-locale" => extractor = extractor.source_locale(value()?),
+            "--target-locale" => extractor = extractor.target_locale(value()?),
+            option if option.starts_with('-') => {
+                return Err(format!("unknown option: {}\n\n{}", option, USAGE))
+            }
+            file => {
+                extractor = extractor.add_file(file);
+                files += 1;
+            }
+        }
+    }
+    if files == 0 {
+        return Err(format!("no soy files given\n\n{}", USAGE));
+    }
+    let xliff = extractor.extract().map_err(|e| e.to_string())?;
+    write_output(output, &xliff)
+}
+
+fn js(args: &[String]) -> Result<(), String> {
+    let mut output = None;
+    let mut paths = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("{} requires a value\n\n{}", arg, USAGE))?;
+                output = Some(value.clone());
+            }
+            option if option.starts_with('-') => {
+                return Err(format!("unknown option: {}\n\n{}", option, USAGE))
+            }
+            path => paths.push(path),
+        }
+    }
+    if paths.is_empty() {
+        return Err(format!("no soy files given\n\n{}", USAGE));
+    }
+    let code = soy::build::compile_js(&paths).map_err(|e| e.to_string())?;
+    write_output(output, &code)
+}
+
+/// Writes to a file if one was given with `--output`, or to stdout.
+fn write_output(output: Option<String>, content: &str) -> Result<(), String> {
+    match output {
+        Some(path) => fs::write(&path, content).map_err(|e| format!("{}: {}", path, e)),
+        None => io::stdout()
+            .write_all(content.as_bytes())
+            .map_err(|e| e.to_string()),
+    }
+}
@@ -0,0 +1,120 @@
+//! Compiles templates to Rust in build scripts, so that they're checked when the crate is
+//! built and rendered without being parsed or interpreted at run time.
+//!
+//! In `build.rs`:
+//!
+//! ```no_run
+//! soy::build::compile_dir("templates").unwrap();
+//! ```
+//!
+//! Then in the crate, where the templates are wanted:
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/soy_templates.rs"));
+//! ```
+//!
+//! Each namespace becomes a module (`{namespace foo.barBaz}` becomes `foo::bar_baz`), with
+//! a function for each template that renders it to a writer, and a struct of its
+//! parameters, e.g. `fn hello_world<W: Write>(writer: &mut W, params: &HelloWorldParams)`.
+//!
+//! The same templates can be compiled to an ES module with [`compile_js`], so that they
+//! render the same in the browser (also available as `soy js` on the command line).
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::codegen;
+use crate::error::{CompileError, CompileErrorKind, CompileErrors, Diagnostics};
+use crate::tofu::Source;
+
+/// The name of the generated file in `OUT_DIR`.
+const OUTPUT_FILENAME: &str = "soy_templates.rs";
+
+/// Compiles the `.soy` files in a directory (and its subdirectories) to Rust, writing the
+/// code to `soy_templates.rs` in `OUT_DIR`, and returns the path it was written to.
+///
+/// Cargo is told to rerun the build script when the directory changes, and warnings are
+/// passed on to it to be shown to the user.
+pub fn compile_dir<P: AsRef<Path>>(dir: P) -> Result<PathBuf, CompileErrors> {
+    let dir = dir.as_ref();
+    let mut diagnostics = Diagnostics::default();
+    println!("cargo:rerun-if-changed={}", dir.display());
+    let mut paths = vec![];
+    if let Err(e) = find_soy_files(dir, &mut paths) {
+        diagnostics.push(io_error(dir, e));
+        return Err(CompileErrors { diagnostics });
+    }
+    paths.sort();
+    let files = paths
+        .into_iter()
+        .filter_map(|path| Source::File(path).parse(&mut diagnostics))
+        .collect();
+    let code = codegen::rust::generate(files, &mut diagnostics);
+    for warning in diagnostics.warnings() {
+        println!("cargo:warning={}", warning.to_string().replace('\n', " "));
+    }
+    if diagnostics.has_errors() {
+        return Err(CompileErrors { diagnostics });
+    }
+    let path = match env::var_os("OUT_DIR") {
+        Some(out_dir) => PathBuf::from(out_dir).join(OUTPUT_FILENAME),
+        None => {
+            let e = io::Error::new(io::ErrorKind::NotFound, "OUT_DIR is not set");
+            diagnostics.push(io_error(Path::new(OUTPUT_FILENAME), e));
+            return Err(CompileErrors { diagnostics });
+        }
+    };
+    if let Err(e) = fs::write(&path, code) {
+        diagnostics.push(io_error(&path, e));
+        return Err(CompileErrors { diagnostics });
+    }
+    Ok(path)
+}
+
+/// Compiles the `.soy` files given (or found in the directories given) to JavaScript,
+/// returning the code of an ES module that exports a function for each public template.
+pub fn compile_js<P: AsRef<Path>>(paths: &[P]) -> Result<String, CompileErrors> {
+    let mut diagnostics = Diagnostics::default();
+    let mut soy_files = vec![];
+    for path in paths {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            soy_files.push(path.to_owned());
+        } else if let Err(e) = find_soy_files(path, &mut soy_files) {
+            diagnostics.push(io_error(path, e));
+        }
+    }
+    soy_files.sort();
+    soy_files.dedup();
+    let files = soy_files
+        .into_iter()
+        .filter_map(|path| Source::File(path).parse(&mut diagnostics))
+        .collect();
+    let code = codegen::js::generate(files, &mut diagnostics);
+    if diagnostics.has_errors() {
+        return Err(CompileErrors { diagnostics });
+    }
+    Ok(code)
+}
+
+pub(crate) fn find_soy_files(dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_soy_files(&path, paths)?;
+        } else if path.extension().is_some_and(|extension| extension == "soy") {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn io_error(path: &Path, e: io::Error) -> CompileError {
+    CompileError {
+        kind: CompileErrorKind::Io(path.display().to_string(), e),
+        location: None,
+        cause: None,
+    }
+}
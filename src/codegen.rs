@@ -0,0 +1,4 @@
+//! Generates code from templates, so that they can be rendered without interpreting them.
+
+pub(crate) mod js;
+pub(crate) mod rust;
@@ -0,0 +1,702 @@
+//! Generates an ES module from a set of soy files, with a function for each template that
+//! takes its parameters as an object and returns the rendered output as a string.
+//!
+//! The module starts with a small runtime (`js/runtime.js`) that evaluates expressions
+//! and print directives the same way as the interpreter, so that templates render the same
+//! on the server and the client. As with the Rust backend, the functions render with the
+//! default options: no CSS or id renaming, a left to right page, and messages as written.
+//! Numbers are JavaScript numbers, so integers beyond 2^53 lose precision.
+//!
+//! Public templates are exported as nested objects, one for each namespace, so that
+//! `foo.bar.hello` is called as `foo.bar.hello({name: 'World'})`, as with Closure.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use crate::ast::{
+    BinaryOperator, Call, CallData, CallParamValue, Command, ContentKind, Expression, MsgBody,
+    Reference, ReferenceKey, Referent, SoyFile, Template, TemplateNode, UnaryOperator, Visibility,
+};
+use crate::error::{CompileError, CompileErrorKind, Diagnostics, TemplateLocation};
+use crate::link::Linker;
+use crate::msg::{Message, MsgPart, PlaceholderPiece, PluralCaseSpec};
+use crate::tofu::{self, optimize, DirectiveKind, Function};
+
+#[cfg(test)]
+mod tests;
+
+const RUNTIME: &str = include_str!("js/runtime.js");
+
+/// Generates the code for the templates in a set of files, reporting any templates that
+/// can't be compiled to `diagnostics`.
+pub(crate) fn generate(files: Vec<SoyFile>, diagnostics: &mut Diagnostics) -> String {
+    let mut root = Namespace::default();
+    let mut functions = String::new();
+    let globals = Default::default();
+    // Where each template was defined, by fully-qualified name
+    let mut defined = HashMap::new();
+    let mut linker = Linker::default();
+    for file in &files {
+        linker.add_file(file);
+    }
+    linker.check(diagnostics);
+    // The kind of each template, by fully-qualified name, for its callers to escape
+    let mut kinds = HashMap::new();
+    for file in &files {
+        for template in &file.templates {
+            let qualified_name = format!("{}.{}", file.namespace.name, template.name);
+            kinds
+                .entry(qualified_name)
+                .or_insert(template.attributes.kind);
+        }
+    }
+    for file in files {
+        let segments: Vec<_> = file.namespace.name.split('.').collect();
+        let namespace = segments.iter().fold(&mut root, |namespace, segment| {
+            namespace.children.entry(segment.to_string()).or_default()
+        });
+        for mut template in file.templates {
+            let qualified_name = format!("{}.{}", file.namespace.name, template.name);
+            if let Some(first) = defined.get(&qualified_name) {
+                diagnostics.push(CompileError {
+                    kind: CompileErrorKind::DuplicateTemplate(qualified_name, Box::clone(first)),
+                    location: Some(Box::new(template.location.0.clone())),
+                    cause: None,
+                });
+                continue;
+            }
+            defined.insert(
+                qualified_name.clone(),
+                Box::new(template.location.0.clone()),
+            );
+            optimize::optimize(&mut template, &globals);
+            let function_name = qualified_name.replace('.', "$");
+            let generator = TemplateGenerator {
+                template: &template,
+                qualified_name: &qualified_name,
+                kind: template.attributes.kind,
+                kinds: &kinds,
+                code: String::new(),
+                indent: 1,
+                blocks: 0,
+                diagnostics: &mut *diagnostics,
+            };
+            functions.push('\n');
+            functions.push_str(&generator.generate(&function_name));
+            if template.attributes.visibility == Visibility::Public {
+                namespace.templates.insert(template.name, function_name);
+            }
+        }
+    }
+    let mut code = String::from("// Generated by soy from soy templates. Do not edit.\n\n");
+    code.push_str(RUNTIME);
+    code.push_str(&functions);
+    for (name, namespace) in &root.children {
+        if namespace.is_empty() {
+            continue;
+        }
+        write!(code, "\nexport const {} = ", binding(name)).unwrap();
+        namespace.write(&mut code, name, 0, diagnostics);
+        code.push_str(";\n");
+    }
+    code
+}
+
+#[derive(Default)]
+struct Namespace {
+    children: BTreeMap<String, Namespace>,
+    /// The function of each public template, by name.
+    templates: BTreeMap<String, String>,
+}
+
+impl Namespace {
+    /// Whether there are no public templates in the namespace, or any namespace in it.
+    fn is_empty(&self) -> bool {
+        self.templates.is_empty() && self.children.values().all(Namespace::is_empty)
+    }
+
+    /// Writes the namespace as an object literal, with a property for each template and
+    /// each namespace in it.
+    fn write(&self, code: &mut String, name: &str, depth: usize, diagnostics: &mut Diagnostics) {
+        let indent = "  ".repeat(depth + 1);
+        code.push_str("{\n");
+        for (template, function) in &self.templates {
+            writeln!(code, "{}{}: {},", indent, property(template), function).unwrap();
+        }
+        for (child_name, child) in &self.children {
+            if child.is_empty() {
+                continue;
+            }
+            if self.templates.contains_key(child_name) {
+                diagnostics.push(CompileError {
+                    kind: CompileErrorKind::Unsupported(format!(
+                        "template {}.{} with the same name as a namespace",
+                        name, child_name
+                    )),
+                    location: None,
+                    cause: None,
+                });
+                continue;
+            }
+            write!(code, "{}{}: ", indent, property(child_name)).unwrap();
+            child.write(
+                code,
+                &format!("{}.{}", name, child_name),
+                depth + 1,
+                diagnostics,
+            );
+            code.push_str(",\n");
+        }
+        write!(code, "{}}}", "  ".repeat(depth)).unwrap();
+    }
+}
+
+struct TemplateGenerator<'a> {
+    template: &'a Template,
+    qualified_name: &'a str,
+    /// The kind of content being generated, which printed values are escaped for. It is
+    /// the template's kind, except in the params of a call.
+    kind: ContentKind,
+    /// The kinds of the templates that can be called.
+    kinds: &'a HashMap<String, ContentKind>,
+    code: String,
+    indent: usize,
+    /// The number of blocks rendered into constants so far, which are numbered to be unique.
+    blocks: usize,
+    diagnostics: &'a mut Diagnostics,
+}
+
+impl<'a> TemplateGenerator<'a> {
+    fn generate(mut self, function_name: &str) -> String {
+        let mut code = String::new();
+        writeln!(code, "/** Renders `{}`. */", self.qualified_name).unwrap();
+        writeln!(
+            code,
+            "function {}(params = {{}}, ij = {{}}) {{",
+            function_name
+        )
+        .unwrap();
+        let template = self.template;
+        self.line("let out = '';");
+        self.block(&template.body);
+        self.line("return out;");
+        code.push_str(&self.code);
+        code.push_str("}\n");
+        code
+    }
+
+    fn line(&mut self, line: &str) {
+        for _ in 0..self.indent {
+            self.code.push_str("  ");
+        }
+        self.code.push_str(line);
+        self.code.push('\n');
+    }
+
+    fn error(&mut self, feature: String) {
+        self.diagnostics.push(CompileError {
+            kind: CompileErrorKind::Unsupported(feature),
+            location: Some(Box::new(TemplateLocation {
+                template_name: Some(self.qualified_name.to_owned()),
+                ..Default::default()
+            })),
+            cause: None,
+        });
+    }
+
+    fn block(&mut self, block: &[TemplateNode]) {
+        for node in block {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &TemplateNode) {
+        match node {
+            TemplateNode::RawText(text) | TemplateNode::Special(text) => self.text(text),
+            TemplateNode::Statement(command) => self.command(command),
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.line(&format!("out += {};", string(text)));
+        }
+    }
+
+    fn command(&mut self, command: &Command) {
+        match command {
+            Command::If {
+                if_block,
+                else_ifs,
+                else_block,
+            } => {
+                for (i, conditional) in std::iter::once(if_block).chain(else_ifs).enumerate() {
+                    let condition = self.expression(&conditional.expression);
+                    let keyword = if i == 0 { "if" } else { "} else if" };
+                    self.line(&format!("{} ($$truthy({})) {{", keyword, condition));
+                    self.indented_block(&conditional.block);
+                }
+                if let Some(block) = else_block {
+                    self.line("} else {");
+                    self.indented_block(block);
+                }
+                self.line("}");
+            }
+            Command::Msg { body, .. } => self.msg(body),
+            Command::Print {
+                expression,
+                directives,
+                ..
+            } => {
+                let (kind, autoescape) = (self.kind, tofu::autoescape(self.kind));
+                let value = self.expression(expression);
+                let mut applied = vec![];
+                for directive in directives {
+                    if let DirectiveKind::Unknown(name) = DirectiveKind::from_name(&directive.name)
+                    {
+                        self.error(format!("unknown print directive |{}", name));
+                        continue;
+                    }
+                    applied.push(format!(
+                        "[{}, [{}]]",
+                        string(&directive.name),
+                        self.expressions(&directive.arguments)
+                    ));
+                }
+                self.line(&format!(
+                    "out += $$print({}, '{}', {}, [{}]);",
+                    value,
+                    content_kind(kind),
+                    autoescape,
+                    applied.join(", ")
+                ));
+            }
+            Command::Literal(text) => self.text(text),
+            Command::Css { base, name } => match (base, name.strip_prefix('%')) {
+                (Some(base), _) => {
+                    let base = self.expression(base);
+                    self.line(&format!("out += $$cssClass({}, {});", base, string(name)));
+                }
+                (None, Some(suffix)) => match &self.template.attributes.css_base {
+                    Some(css_base) => self.text(&format!("{}-{}", css_base, suffix)),
+                    None => self.error(format!("{{css {}}} without a cssbase", name)),
+                },
+                (None, None) => self.text(name),
+            },
+            Command::Xid(name) => self.text(name),
+            Command::Call(call) => self.call(call),
+        }
+    }
+
+    /// Calls the function of a template, with the data and params passed to it. Blocks
+    /// passed as params are rendered first, into constants in a block of their own.
+    fn call(&mut self, call: &Call) {
+        let scoped = call
+            .params
+            .iter()
+            .any(|param| matches!(param.value, CallParamValue::Block { .. }));
+        if scoped {
+            self.line("{");
+            self.indent += 1;
+        }
+        let mut entries = vec![];
+        match &call.data {
+            None => {}
+            Some(CallData::All) => entries.push("...params".to_owned()),
+            Some(CallData::Expression(data)) => {
+                let data = self.expression(data);
+                entries.push(format!("...$$callData({}, {})", data, string(&call.target)));
+            }
+        }
+        for param in &call.params {
+            let value = match &param.value {
+                CallParamValue::Expression(expression) => self.expression(expression),
+                CallParamValue::Block { kind, block } => {
+                    self.blocks += 1;
+                    let name = format!("$$block{}", self.blocks);
+                    self.line(&format!("const {} = (() => {{", name));
+                    self.indent += 1;
+                    self.line("let out = '';");
+                    let outer = std::mem::replace(&mut self.kind, *kind);
+                    self.block(block);
+                    self.kind = outer;
+                    self.line(&format!(
+                        "return new SanitizedContent('{}', out);",
+                        content_kind(*kind)
+                    ));
+                    self.indent -= 1;
+                    self.line("})();");
+                    name
+                }
+            };
+            // A computed key, as in map literals
+            entries.push(format!("[{}]: {}", string(&param.name), value));
+        }
+        let output = format!(
+            "{}({{{}}}, ij)",
+            call.target.replace('.', "$"),
+            entries.join(", ")
+        );
+        match self.kinds.get(&call.target) {
+            // The callee's output is trusted only as its own kind
+            Some(&kind) if kind != self.kind => self.line(&format!(
+                "out += $$print(new SanitizedContent('{}', {}), '{}', {}, []);",
+                content_kind(kind),
+                output,
+                content_kind(self.kind),
+                tofu::autoescape(self.kind)
+            )),
+            _ => self.line(&format!("out += {};", output)),
+        }
+        if scoped {
+            self.indent -= 1;
+            self.line("}");
+        }
+    }
+
+    fn indented_block(&mut self, block: &[TemplateNode]) {
+        self.indent += 1;
+        self.block(block);
+        self.indent -= 1;
+    }
+
+    /// Renders a message as written, since translations aren't compiled in.
+    fn msg(&mut self, body: &MsgBody) {
+        let message = Message::new(body);
+        self.msg_parts(&message, &message.parts);
+    }
+
+    fn msg_parts(&mut self, message: &Message, parts: &[MsgPart]) {
+        for part in parts {
+            match part {
+                MsgPart::RawText(text) => self.text(text),
+                MsgPart::Placeholder(name) => {
+                    for piece in message.placeholder(name).unwrap_or_default() {
+                        match piece {
+                            PlaceholderPiece::Text(text) => self.text(text),
+                            PlaceholderPiece::Node(node) => self.node(node),
+                        }
+                    }
+                }
+                MsgPart::Plural { var, offset, cases } => {
+                    let value = self.msg_variable(message, var);
+                    self.line("{");
+                    self.indent += 1;
+                    self.line(&format!("const value = {};", value));
+                    // Explicit cases are checked first, then the category of the value
+                    let mut closing = 0;
+                    for (spec, parts) in cases {
+                        if let PluralCaseSpec::Explicit(n) = spec {
+                            self.line(&format!("if (value === {}) {{", number(*n as f64)));
+                            self.indented_msg_parts(message, parts);
+                            self.line("} else {");
+                            self.indent += 1;
+                            closing += 1;
+                        }
+                    }
+                    self.line(&format!("switch ($$pluralCategory(value, {})) {{", offset));
+                    self.indent += 1;
+                    for (spec, parts) in cases {
+                        if let PluralCaseSpec::Category(category) = spec {
+                            self.switch_case(category, message, parts);
+                        }
+                    }
+                    self.indent -= 1;
+                    self.line("}");
+                    for _ in 0..closing {
+                        self.indent -= 1;
+                        self.line("}");
+                    }
+                    self.indent -= 1;
+                    self.line("}");
+                }
+                MsgPart::Select { var, cases } => {
+                    let value = self.msg_variable(message, var);
+                    self.line(&format!("switch ($$str({})) {{", value));
+                    self.indent += 1;
+                    for (value, parts) in cases {
+                        self.switch_case(value, message, parts);
+                    }
+                    self.indent -= 1;
+                    self.line("}");
+                }
+            }
+        }
+    }
+
+    /// A case of a `switch`, where `other` is the default.
+    fn switch_case(&mut self, value: &str, message: &Message, parts: &[MsgPart]) {
+        if value == "other" {
+            self.line("default: {");
+        } else {
+            self.line(&format!("case {}: {{", string(value)));
+        }
+        self.indent += 1;
+        self.msg_parts(message, parts);
+        self.line("break;");
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    fn indented_msg_parts(&mut self, message: &Message, parts: &[MsgPart]) {
+        self.indent += 1;
+        self.msg_parts(message, parts);
+        self.indent -= 1;
+    }
+
+    fn msg_variable(&mut self, message: &Message, var: &str) -> String {
+        match message.variable(var) {
+            Some(expression) => self.expression(expression),
+            None => "null".to_owned(),
+        }
+    }
+
+    fn expressions(&mut self, expressions: &[Expression]) -> String {
+        expressions
+            .iter()
+            .map(|expression| self.expression(expression))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The code for an expression, which evaluates to a value and may throw a `TypeError`.
+    fn expression(&mut self, expression: &Expression) -> String {
+        match expression {
+            Expression::Null => "null".to_owned(),
+            Expression::Boolean(b) => b.to_string(),
+            Expression::Integer(i) if *i < 0 => format!("({})", i),
+            Expression::Integer(i) => i.to_string(),
+            Expression::Float(f) => number(*f),
+            Expression::String(s) => string(s),
+            Expression::List(items) => format!("[{}]", self.expressions(items)),
+            Expression::Map(entries) => {
+                // Sorted so the output is stable
+                let mut entries: Vec<_> = entries.iter().collect();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                let entries: Vec<_> = entries
+                    .into_iter()
+                    .map(|(key, value)| {
+                        // A computed key, so that `__proto__` is an ordinary property
+                        format!("[{}]: {}", string(key), self.expression(value))
+                    })
+                    .collect();
+                format!("{{{}}}", entries.join(", "))
+            }
+            Expression::Function { name, parameters } => match Function::from_name(name) {
+                Some(_) => format!("$$functions.{}({})", name, self.expressions(parameters)),
+                None => {
+                    self.error(format!("unknown function {}()", name));
+                    "null".to_owned()
+                }
+            },
+            Expression::DataReference {
+                referent,
+                references,
+            } => self.reference(referent, references),
+            Expression::GlobalReference(name) => {
+                self.error(format!("unknown global {}", name));
+                "null".to_owned()
+            }
+            Expression::BinaryOperation { lhs, op, rhs } => {
+                let (lhs, rhs) = (self.expression(lhs), self.expression(rhs));
+                match op {
+                    BinaryOperator::And => format!("($$truthy({}) && $$truthy({}))", lhs, rhs),
+                    BinaryOperator::Or => format!("($$truthy({}) || $$truthy({}))", lhs, rhs),
+                    BinaryOperator::Elvis => {
+                        format!("((value) => $$isNull(value) ? {} : value)({})", rhs, lhs)
+                    }
+                    BinaryOperator::Plus => format!("$$plus({}, {})", lhs, rhs),
+                    BinaryOperator::Minus => format!("$$minus({}, {})", lhs, rhs),
+                    BinaryOperator::Times => format!("$$times({}, {})", lhs, rhs),
+                    BinaryOperator::Divide => format!("$$divide({}, {})", lhs, rhs),
+                    BinaryOperator::Modulo => format!("$$modulo({}, {})", lhs, rhs),
+                    BinaryOperator::Equals => format!("$$equals({}, {})", lhs, rhs),
+                    BinaryOperator::NotEquals => format!("!$$equals({}, {})", lhs, rhs),
+                    op => format!("$$compare('{:?}', {}, {})", op, lhs, rhs),
+                }
+            }
+            Expression::UnaryOperation { op, rhs } => {
+                let rhs = self.expression(rhs);
+                match op {
+                    UnaryOperator::Not => format!("!$$truthy({})", rhs),
+                    UnaryOperator::Minus => format!("$$negate({})", rhs),
+                }
+            }
+            Expression::TernaryOperation {
+                condition,
+                if_true,
+                if_false,
+            } => format!(
+                "($$truthy({}) ? {} : {})",
+                self.expression(condition),
+                self.expression(if_true),
+                self.expression(if_false)
+            ),
+        }
+    }
+
+    /// The code for a data reference, which follows the references in a function so that
+    /// null safe references can stop early.
+    fn reference(&mut self, referent: &Referent, references: &[Reference]) -> String {
+        let root = match referent {
+            Referent::Variable(name) => format!("$$param(params, {})", string(name)),
+            Referent::Injected(name) => format!("$$param(ij, {})", string(name)),
+        };
+        if references.is_empty() {
+            return root;
+        }
+        let mut code = format!("(() => {{ let value = {}; ", root);
+        for reference in references {
+            let (key, null_safe) = match reference {
+                Reference::Dotted(key) => (reference_key(key), false),
+                Reference::QuestionDotted(key) => (reference_key(key), true),
+                Reference::Bracketed(key) => (self.expression(key), false),
+                Reference::QuestionBracketed(key) => (self.expression(key), true),
+            };
+            write!(
+                code,
+                "value = $$lookup(value, {}, {}); if (value === undefined) return null; ",
+                key, null_safe
+            )
+            .unwrap();
+        }
+        code.push_str("return value; })()");
+        code
+    }
+}
+
+fn reference_key(key: &ReferenceKey) -> String {
+    match key {
+        ReferenceKey::Number(i) => i.to_string(),
+        ReferenceKey::Name(name) => string(name),
+    }
+}
+
+/// The name of a content kind in the runtime, which is the same as a template's `kind`.
+fn content_kind(kind: ContentKind) -> &'static str {
+    match kind {
+        ContentKind::Html => "html",
+        ContentKind::Text => "text",
+        ContentKind::Attributes => "attributes",
+        ContentKind::Uri => "uri",
+        ContentKind::TrustedResourceUri => "trusted_resource_uri",
+        ContentKind::Css => "css",
+        ContentKind::Js => "js",
+    }
+}
+
+/// A JavaScript number literal, in parentheses if it is negative.
+fn number(n: f64) -> String {
+    if n.is_nan() {
+        "NaN".to_owned()
+    } else if n.is_infinite() {
+        format!("({}Infinity)", if n < 0.0 { "-" } else { "" })
+    } else if n < 0.0 || (n == 0.0 && n.is_sign_negative()) {
+        format!("(-{:?})", -n)
+    } else {
+        format!("{:?}", n)
+    }
+}
+
+/// A JavaScript string literal.
+fn string(s: &str) -> String {
+    let mut literal = String::with_capacity(s.len() + 2);
+    literal.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            // Line terminators in JavaScript, but not in JSON, so escaped too
+            '\u{2028}' | '\u{2029}' => write!(literal, "\\u{:04x}", c as u32).unwrap(),
+            // Escaped so that the output can be inlined in a script element
+            '<' => literal.push_str("\\x3c"),
+            c if c.is_control() => write!(literal, "\\u{:04x}", c as u32).unwrap(),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// A property name, which is quoted unless it is an identifier.
+fn property(name: &str) -> String {
+    if is_identifier(name) {
+        name.to_owned()
+    } else {
+        string(name)
+    }
+}
+
+/// A name for a top level binding, with `_` added if the name is reserved or already used
+/// by the runtime.
+fn binding(name: &str) -> String {
+    const RESERVED: &[&str] = &[
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "debugger",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "enum",
+        "export",
+        "extends",
+        "false",
+        "finally",
+        "for",
+        "function",
+        "if",
+        "implements",
+        "import",
+        "in",
+        "instanceof",
+        "interface",
+        "let",
+        "new",
+        "null",
+        "package",
+        "private",
+        "protected",
+        "public",
+        "return",
+        "static",
+        "super",
+        "switch",
+        "this",
+        "throw",
+        "true",
+        "try",
+        "typeof",
+        "var",
+        "void",
+        "while",
+        "with",
+        "yield",
+        "arguments",
+        "eval",
+        "SanitizedContent",
+        "trusted",
+        "trustedHtml",
+    ];
+    if RESERVED.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_owned()
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
@@ -0,0 +1,515 @@
+// The runtime shared by the templates below. It evaluates expressions the same way as
+// soy's Rust renderer, so that templates render the same on the server and the client.
+
+/** Content of a kind, such as HTML, that isn't escaped when printed in that context. */
+export class SanitizedContent {
+  constructor(kind, content) {
+    this.kind = kind;
+    this.content = String(content);
+  }
+
+  toString() {
+    return this.content;
+  }
+}
+
+/**
+ * Content that is printed as is in templates of the same kind (`html`, `text`,
+ * `attributes`, `uri`, `trusted_resource_uri`, `css` or `js`). It must come from a
+ * trusted source or have been sanitized, since anything in it ends up in the page.
+ */
+export function trusted(kind, content) {
+  return new SanitizedContent(kind, content);
+}
+
+/** HTML that is printed as is in HTML templates. See `trusted`. */
+export function trustedHtml(html) {
+  return trusted('html', html);
+}
+
+function $$typeError(message) {
+  return new TypeError(message);
+}
+
+function $$isNull(value) {
+  return value === null || value === undefined;
+}
+
+function $$isMap(value) {
+  return typeof value === 'object' && value !== null && !Array.isArray(value) &&
+      !(value instanceof SanitizedContent);
+}
+
+function $$typeName(value) {
+  if ($$isNull(value)) return 'null';
+  if (typeof value === 'boolean') return 'bool';
+  if (typeof value === 'number') return Number.isInteger(value) ? 'int' : 'float';
+  if (typeof value === 'string') return 'string';
+  if (Array.isArray(value)) return 'list';
+  if (value instanceof SanitizedContent) return value.kind;
+  return 'map';
+}
+
+function $$truthy(value) {
+  if ($$isNull(value)) return false;
+  if (typeof value === 'boolean') return value;
+  if (typeof value === 'number') return value !== 0 && !Number.isNaN(value);
+  if (typeof value === 'string') return value !== '';
+  if (value instanceof SanitizedContent) return value.content !== '';
+  return true;
+}
+
+/** Formats a number without an exponent, as Rust does. */
+function $$formatNumber(n) {
+  if (n === Infinity) return 'Infinity';
+  if (n === -Infinity) return '-Infinity';
+  const text = String(n);
+  const e = text.indexOf('e');
+  if (e < 0) return text;
+  let mantissa = text.slice(0, e);
+  const negative = mantissa[0] === '-';
+  if (negative) mantissa = mantissa.slice(1);
+  const point = mantissa.indexOf('.');
+  const digits = mantissa.replace('.', '');
+  const position = (point < 0 ? mantissa.length : point) + Number(text.slice(e + 1));
+  let formatted;
+  if (position <= 0) {
+    formatted = '0.' + '0'.repeat(-position) + digits;
+  } else if (position >= digits.length) {
+    formatted = digits + '0'.repeat(position - digits.length);
+  } else {
+    formatted = digits.slice(0, position) + '.' + digits.slice(position);
+  }
+  return (negative ? '-' : '') + formatted;
+}
+
+/** Formats a value the way it is printed by a template. */
+function $$str(value) {
+  if ($$isNull(value)) return 'null';
+  if (typeof value === 'number') return $$formatNumber(value);
+  if (typeof value !== 'object') return String(value);
+  if (value instanceof SanitizedContent) return value.content;
+  if (Array.isArray(value)) return '[' + value.map($$str).join(', ') + ']';
+  return '{' + Object.keys(value).sort().map((key) => key + ': ' + $$str(value[key]))
+      .join(', ') + '}';
+}
+
+/** The class name for `{css $base, name}`, where the base must be a class name itself. */
+function $$cssClass(base, name) {
+  const text = $$str(base);
+  if (!/^[A-Za-z0-9_-]+$/.test(text)) {
+    throw $$typeError(`css base ${JSON.stringify(text)} is not a valid class name`);
+  }
+  return text + '-' + name;
+}
+
+function $$param(params, name) {
+  return Object.prototype.hasOwnProperty.call(params, name) && !$$isNull(params[name]) ?
+      params[name] : null;
+}
+
+/** The data passed to a called template with `data="$foo"`, which must be a map or null. */
+function $$callData(data, target) {
+  if ($$isNull(data)) return {};
+  if (!$$isMap(data)) {
+    throw $$typeError(`the data passed to ${target} must be a map, not a ${$$typeName(data)}`);
+  }
+  return data;
+}
+
+/**
+ * Looks up a key in a list or map. Returns `undefined` if the lookup is null safe and the
+ * value is null.
+ */
+function $$lookup(value, key, nullSafe) {
+  if ($$isNull(value) && nullSafe) return undefined;
+  if (Array.isArray(value) && Number.isInteger(key)) {
+    return key >= 0 && key < value.length && !$$isNull(value[key]) ? value[key] : null;
+  }
+  if ($$isMap(value) && typeof key === 'string') return $$param(value, key);
+  throw $$typeError(`can't look up a ${$$typeName(key)} key in a ${$$typeName(value)}`);
+}
+
+function $$invalid(op, lhs, rhs) {
+  return $$typeError(`can't apply ${op} to a ${$$typeName(lhs)} and a ${$$typeName(rhs)}`);
+}
+
+function $$numbers(op, lhs, rhs) {
+  if (typeof lhs !== 'number' || typeof rhs !== 'number') throw $$invalid(op, lhs, rhs);
+}
+
+function $$plus(lhs, rhs) {
+  if (typeof lhs === 'string' || typeof rhs === 'string' ||
+      lhs instanceof SanitizedContent || rhs instanceof SanitizedContent) {
+    return $$str(lhs) + $$str(rhs);
+  }
+  $$numbers('Plus', lhs, rhs);
+  return lhs + rhs;
+}
+
+function $$minus(lhs, rhs) {
+  $$numbers('Minus', lhs, rhs);
+  return lhs - rhs;
+}
+
+function $$times(lhs, rhs) {
+  $$numbers('Times', lhs, rhs);
+  return lhs * rhs;
+}
+
+function $$divide(lhs, rhs) {
+  $$numbers('Divide', lhs, rhs);
+  return lhs / rhs;
+}
+
+function $$modulo(lhs, rhs) {
+  $$numbers('Modulo', lhs, rhs);
+  return lhs % rhs;
+}
+
+function $$compare(op, lhs, rhs) {
+  if (!(typeof lhs === 'string' && typeof rhs === 'string')) $$numbers(op, lhs, rhs);
+  switch (op) {
+    case 'Less': return lhs < rhs;
+    case 'LessEquals': return lhs <= rhs;
+    case 'Greater': return lhs > rhs;
+    default: return lhs >= rhs;
+  }
+}
+
+function $$strictEquals(lhs, rhs) {
+  if ($$isNull(lhs) || $$isNull(rhs)) return $$isNull(lhs) && $$isNull(rhs);
+  if (Array.isArray(lhs) || Array.isArray(rhs)) {
+    return Array.isArray(lhs) && Array.isArray(rhs) && lhs.length === rhs.length &&
+        lhs.every((item, i) => $$strictEquals(item, rhs[i]));
+  }
+  if (lhs instanceof SanitizedContent || rhs instanceof SanitizedContent) {
+    return lhs instanceof SanitizedContent && rhs instanceof SanitizedContent &&
+        lhs.kind === rhs.kind && lhs.content === rhs.content;
+  }
+  if ($$isMap(lhs) || $$isMap(rhs)) {
+    if (!$$isMap(lhs) || !$$isMap(rhs)) return false;
+    const keys = Object.keys(lhs);
+    return keys.length === Object.keys(rhs).length &&
+        keys.every((key) => Object.prototype.hasOwnProperty.call(rhs, key) &&
+            $$strictEquals(lhs[key], rhs[key]));
+  }
+  return lhs === rhs;
+}
+
+/**
+ * Soy's `==`, which compares numbers by value, strings with numbers by their string form,
+ * and sanitized content with strings by its content.
+ */
+function $$equals(lhs, rhs) {
+  const text = (value) => typeof value === 'string' || value instanceof SanitizedContent;
+  if ((lhs instanceof SanitizedContent && text(rhs)) ||
+      (rhs instanceof SanitizedContent && text(lhs))) {
+    return $$str(lhs) === $$str(rhs);
+  }
+  if (typeof lhs === 'string' && typeof rhs === 'number') return lhs === $$str(rhs);
+  if (typeof rhs === 'string' && typeof lhs === 'number') return rhs === $$str(lhs);
+  return $$strictEquals(lhs, rhs);
+}
+
+function $$negate(value) {
+  if (typeof value !== 'number') throw $$typeError(`can't negate a ${$$typeName(value)}`);
+  // Integers have no negative zero
+  return value === 0 ? 0 : -value;
+}
+
+function $$arity(name, args, n) {
+  if (args.length !== n) {
+    throw $$typeError(`${name}() takes ${n} argument(s), not ${args.length}`);
+  }
+}
+
+function $$number(name, value) {
+  if (typeof value !== 'number') {
+    throw $$typeError(`${name}() expects a number, not a ${$$typeName(value)}`);
+  }
+  return value;
+}
+
+function $$roundFunction(name, round) {
+  return (...args) => {
+    $$arity(name, args, 1);
+    // Adding zero turns negative zero into zero
+    return round($$number(name, args[0])) + 0;
+  };
+}
+
+const $$LRM = '\u200E';
+const $$RLE = '\u202B';
+const $$PDF = '\u202C';
+
+function $$isRtlChar(c) {
+  return (c >= 0x0591 && c <= 0x06EF) || (c >= 0x06FA && c <= 0x07FF) || c === 0x200F ||
+      (c >= 0xFB1D && c <= 0xFDFF) || (c >= 0xFE70 && c <= 0xFEFC);
+}
+
+function $$isLtrChar(c) {
+  return (c >= 0x41 && c <= 0x5A) || (c >= 0x61 && c <= 0x7A) ||
+      (c >= 0xC0 && c <= 0xD6) || (c >= 0xD8 && c <= 0xF6) || (c >= 0xF8 && c <= 0x2B8) ||
+      (c >= 0x300 && c <= 0x590) || (c >= 0x800 && c <= 0x1FFF) || c === 0x200E ||
+      (c >= 0x2C00 && c <= 0xFB1C) || (c >= 0xFE00 && c <= 0xFE6F) ||
+      (c >= 0xFEFD && c <= 0xFFFF);
+}
+
+function $$stripHtml(text) {
+  return text.replace(/<[^>]*>|&[^;]*;/g, '');
+}
+
+function $$firstStrongDir(chars) {
+  for (const c of chars) {
+    const code = c.codePointAt(0);
+    if ($$isRtlChar(code)) return -1;
+    if ($$isLtrChar(code)) return 1;
+  }
+  return 0;
+}
+
+/** The direction of text: 1 for left to right, -1 for right to left, or 0 if unknown. */
+function $$estimateDir(text, isHtml) {
+  if (isHtml) text = $$stripHtml(text);
+  let rtlCount = 0;
+  let totalCount = 0;
+  let hasWeaklyLtr = false;
+  for (const word of text.split(/\s+/)) {
+    if (word === '') continue;
+    const chars = Array.from(word);
+    if ($$firstStrongDir(chars) === -1) {
+      rtlCount++;
+      totalCount++;
+    } else if (word.startsWith('http://')) {
+      hasWeaklyLtr = true;
+    } else if (chars.some((c) => $$isLtrChar(c.codePointAt(0)))) {
+      totalCount++;
+    } else if (/[0-9]/.test(word)) {
+      hasWeaklyLtr = true;
+    }
+  }
+  if (totalCount === 0) return hasWeaklyLtr ? 1 : 0;
+  return rtlCount / totalCount > 0.4 ? -1 : 1;
+}
+
+/** The mark to put after text so that what follows stays left to right. */
+function $$markAfter(dir, text, isHtml) {
+  if (isHtml) text = $$stripHtml(text);
+  return dir === -1 || $$firstStrongDir(Array.from(text).reverse()) === -1 ? $$LRM : '';
+}
+
+function $$bidiTextArgs(name, args) {
+  if (args.length !== 1 && args.length !== 2) {
+    throw $$typeError(`${name}() takes 1 or 2 arguments, not ${args.length}`);
+  }
+  return [$$str(args[0]), args.length === 2 && $$truthy(args[1])];
+}
+
+/** The functions templates can call, rendering with a left to right page. */
+const $$functions = {
+  isNonnull(...args) {
+    $$arity('isNonnull', args, 1);
+    return !$$isNull(args[0]);
+  },
+  isNull(...args) {
+    $$arity('isNull', args, 1);
+    return $$isNull(args[0]);
+  },
+  length(...args) {
+    $$arity('length', args, 1);
+    if (!Array.isArray(args[0])) {
+      throw $$typeError(`length() expects a list, not a ${$$typeName(args[0])}`);
+    }
+    return args[0].length;
+  },
+  keys(...args) {
+    $$arity('keys', args, 1);
+    if (!$$isMap(args[0])) {
+      throw $$typeError(`keys() expects a map, not a ${$$typeName(args[0])}`);
+    }
+    return Object.keys(args[0]).sort();
+  },
+  // Halves are rounded away from zero, as in Rust
+  round: $$roundFunction('round', (n) => Math.sign(n) * Math.floor(Math.abs(n) + 0.5)),
+  floor: $$roundFunction('floor', Math.floor),
+  ceiling: $$roundFunction('ceiling', Math.ceil),
+  min(...args) {
+    $$arity('min', args, 2);
+    return $$number('min', args[0]) <= $$number('min', args[1]) ? args[0] : args[1];
+  },
+  max(...args) {
+    $$arity('max', args, 2);
+    return $$number('max', args[0]) <= $$number('max', args[1]) ? args[1] : args[0];
+  },
+  css(...args) {
+    const name = args[args.length - 1];
+    if (args.length < 1 || args.length > 2 || typeof name !== 'string') {
+      throw $$typeError('css() expects a class name');
+    }
+    return args.length === 2 ? $$cssClass(args[0], name) : name;
+  },
+  xid(...args) {
+    if (args.length !== 1 || typeof args[0] !== 'string') {
+      throw $$typeError('xid() expects an id');
+    }
+    return args[0];
+  },
+  bidiGlobalDir(...args) {
+    $$arity('bidiGlobalDir', args, 0);
+    return 1;
+  },
+  bidiStartEdge(...args) {
+    $$arity('bidiStartEdge', args, 0);
+    return 'left';
+  },
+  bidiEndEdge(...args) {
+    $$arity('bidiEndEdge', args, 0);
+    return 'right';
+  },
+  bidiTextDir(...args) {
+    return $$estimateDir(...$$bidiTextArgs('bidiTextDir', args));
+  },
+  bidiDirAttr(...args) {
+    return $$estimateDir(...$$bidiTextArgs('bidiDirAttr', args)) === -1 ? 'dir=rtl' : '';
+  },
+  bidiMarkAfter(...args) {
+    const [text, isHtml] = $$bidiTextArgs('bidiMarkAfter', args);
+    return $$markAfter($$estimateDir(text, isHtml), text, isHtml);
+  },
+};
+
+function $$escapeHtml(text) {
+  return text.replace(/[&<>"']/g, (c) => ({
+    '&': '&amp;',
+    '<': '&lt;',
+    '>': '&gt;',
+    '"': '&quot;',
+    '\'': '&#39;',
+  })[c]);
+}
+
+function $$escapeUri(text) {
+  return encodeURIComponent(text)
+      .replace(/[!'()*]/g, (c) => '%' + c.charCodeAt(0).toString(16).toUpperCase());
+}
+
+function $$escapeJsString(text) {
+  return text.replace(/[\\\n\r\t/"'`&<=>\x00-\x1f\x85\u2028\u2029]/g, (c) => {
+    switch (c) {
+      case '\\': return '\\\\';
+      case '\n': return '\\n';
+      case '\r': return '\\r';
+      case '\t': return '\\t';
+      case '/': return '\\/';
+    }
+    const code = c.charCodeAt(0);
+    return code < 0x80 ? '\\x' + code.toString(16).padStart(2, '0') :
+                         '\\u' + code.toString(16).padStart(4, '0');
+  });
+}
+
+function $$escapeJsValue(text, literal) {
+  return literal ? ` ${text} ` : `'${$$escapeJsString(text)}'`;
+}
+
+function $$filterCssValue(text) {
+  const value = text.toLowerCase();
+  const word = /^[.#]?[a-z0-9_-]+$/.test(value) &&
+      !/^[.#]?-*(expression|binding|moz-binding)/.test(value);
+  const number = /^-?(\d+\.?\d*|\.\d+)([a-z]{0,2}|%)$/.test(value);
+  return value === '' || value === '!important' || word || number ? text : 'zSoyz';
+}
+
+function $$truncate(text, maxLength, addEllipsis) {
+  const chars = Array.from(text);
+  if (maxLength < 0 || chars.length <= maxLength) return text;
+  if (addEllipsis && maxLength > 3) return chars.slice(0, maxLength - 3).join('') + '...';
+  return chars.slice(0, maxLength).join('');
+}
+
+/** Escapes text for the kind of template it is printed in, as `Printed` does in Rust. */
+function $$escapeFor(kind, text, jsLiteral) {
+  switch (kind) {
+    case 'html':
+    case 'attributes':
+      return $$escapeHtml(text);
+    case 'uri':
+    case 'trusted_resource_uri':
+      return $$escapeUri(text);
+    case 'js':
+      return $$escapeJsValue(text, jsLiteral);
+    case 'css':
+      return $$filterCssValue(text);
+    default:
+      return text;
+  }
+}
+
+/**
+ * Prints a value in a template of the given kind, applying print directives (given as
+ * `[name, args]`). It is escaped for the template's kind (unless told not to, or it is
+ * sanitized content trusted there) before the first directive that produces HTML, or at
+ * the end.
+ */
+function $$print(value, kind, autoescape, directives) {
+  const sanitizedKind = value instanceof SanitizedContent ? value.kind : null;
+  const jsLiteral = $$isNull(value) || typeof value === 'boolean' || typeof value === 'number';
+  let text = $$str(value);
+  // Trusted resource URIs are also URIs, but not the other way around
+  let escaped = !autoescape || sanitizedKind === kind ||
+      (sanitizedKind === 'trusted_resource_uri' && kind === 'uri');
+  const escape = () => {
+    if (!escaped) {
+      text = $$escapeFor(kind, text, jsLiteral);
+      escaped = true;
+    }
+  };
+  for (const [name, args] of directives) {
+    switch (name) {
+      case 'noAutoescape':
+        escaped = true;
+        break;
+      case 'escapeHtml':
+        // Sanitized HTML is already safe, as in Closure
+        if (sanitizedKind !== 'html') text = $$escapeHtml(text);
+        escaped = true;
+        break;
+      case 'escapeUri':
+        text = $$escapeUri(text);
+        escaped = true;
+        break;
+      case 'changeNewlineToBr':
+        escape();
+        text = text.replace(/\r\n|\r|\n/g, '<br>');
+        break;
+      case 'bidiSpanWrap': {
+        escape();
+        const dir = $$estimateDir(text, true);
+        const mark = $$markAfter(dir, text, true);
+        text = (dir === -1 ? `<span dir="rtl">${text}</span>` : text) + mark;
+        break;
+      }
+      case 'bidiUnicodeWrap': {
+        const dir = $$estimateDir(text, escaped);
+        const mark = $$markAfter(dir, text, escaped);
+        text = (dir === -1 ? $$RLE + text + $$PDF : text) + mark;
+        break;
+      }
+      case 'truncate':
+        if (!Number.isInteger(args[0]) || args.length > 2) {
+          throw $$typeError('|truncate expects a length');
+        }
+        text = $$truncate(text, args[0], args.length === 1 || $$truthy(args[1]));
+        break;
+    }
+  }
+  escape();
+  return text;
+}
+
+/** The English plural category of a number minus an offset. */
+function $$pluralCategory(value, offset) {
+  if (typeof value !== 'number' || !Number.isFinite(value)) return 'other';
+  return Math.abs(value - offset) === 1 ? 'one' : 'other';
+}
@@ -0,0 +1,156 @@
+use super::*;
+use crate::parser;
+
+/// The code generated for a set of files, and the diagnostics reported for them.
+fn generated(files: &[&str]) -> (String, Diagnostics) {
+    let mut diagnostics = Diagnostics::default();
+    let files = files
+        .iter()
+        .map(|file| parser::parse(file).unwrap())
+        .collect();
+    let code = generate(files, &mut diagnostics);
+    (code, diagnostics)
+}
+
+fn generated_ok(files: &[&str]) -> String {
+    let (code, diagnostics) = generated(files);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    code
+}
+
+#[test]
+fn test_functions_and_exports() {
+    let code = generated_ok(&[
+        "{namespace foo.barBaz}
+        /**
+         * @param userName
+         */
+        {template .helloWorld}Hello {$userName}{/template}
+        /** Hidden. */
+        {template .hidden visibility=\"private\"}{/template}",
+        "{namespace class.a}\n/** A. */\n{template .b}\"</script>\"\n{/template}",
+    ]);
+    assert!(code.starts_with("// Generated by soy"));
+    assert!(code.contains("export class SanitizedContent {"));
+    assert!(code.contains("function foo$barBaz$helloWorld(params = {}, ij = {}) {"));
+    assert!(code.contains("  out += \"Hello \";\n"));
+    assert!(code.contains("out += $$print($$param(params, \"userName\"), 'html', true, []);"));
+    assert!(code.contains("function foo$barBaz$hidden("));
+    assert!(code.contains(
+        "export const foo = {\n  barBaz: {\n    helloWorld: foo$barBaz$helloWorld,\n  },\n};"
+    ));
+    assert!(!code.contains("hidden: "));
+    assert!(code.contains("export const class_ = {\n  a: {\n    b: class$a$b,\n  },\n};"));
+    assert!(code.contains("out += \"\\\"\\x3c/script>\\\"\";"));
+}
+
+#[test]
+fn test_expressions() {
+    let code = generated_ok(&["{namespace foo}
+        /**
+         * @param x
+         */
+        {template .bar}{$x?.a[0] + 1}{if $x and not $x.b}{max($x, -2.5)}{/if}{/template}"]);
+    assert!(code.contains(
+        "$$plus((() => { let value = $$param(params, \"x\"); \
+         value = $$lookup(value, \"a\", true); if (value === undefined) return null; \
+         value = $$lookup(value, 0, false); if (value === undefined) return null; \
+         return value; })(), 1)"
+    ));
+    assert!(code.contains("if ($$truthy(($$truthy($$param(params, \"x\")) && $$truthy(!$$truthy("));
+    assert!(code.contains("$$functions.max($$param(params, \"x\"), (-2.5))"));
+}
+
+#[test]
+fn test_msg() {
+    let code = generated_ok(&["{namespace foo}
+        /**
+         * @param n
+         */
+        {template .bar}
+          {msg desc=\"Count\"}
+            {plural $n}{case 0}none{case 1}one{default}{$n} items{/plural}
+          {/msg}
+        {/template}"]);
+    assert!(code.contains("const value = $$param(params, \"n\");"));
+    assert!(code.contains("if (value === 0.0) {"));
+    assert!(code.contains("switch ($$pluralCategory(value, 0)) {"));
+    assert!(code.contains("default: {"));
+}
+
+#[test]
+fn test_unsupported() {
+    let (_, diagnostics) = generated(&["{namespace foo}
+        /** Bar. */
+        {template .bar}{GLOBAL}{OTHER.GLOBAL}{/template}"]);
+    let errors: Vec<_> = diagnostics.errors().map(|e| e.to_string()).collect();
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert!(errors[0].contains("unknown global GLOBAL"), "{}", errors[0]);
+    assert!(errors[0].contains("foo.bar"), "{}", errors[0]);
+}
+
+#[test]
+fn test_literals() {
+    assert_eq!(
+        string("a\"b\\c\n\u{2028}\u{1}"),
+        "\"a\\\"b\\\\c\\n\\u2028\\u0001\""
+    );
+    assert_eq!(number(1.0), "1.0");
+    assert_eq!(number(-0.5), "(-0.5)");
+    assert_eq!(number(f64::NEG_INFINITY), "(-Infinity)");
+    assert_eq!(property("a-b"), "\"a-b\"");
+}
+
+#[test]
+fn test_calls() {
+    let code = generated_ok(&[
+        "{namespace foo.bar}
+        /**
+         * @param name
+         */
+        {template .hello}Hello, {$name}!{/template}
+        /**
+         * @param? name
+         * @param user
+         */
+        {template .caller}
+          {call .hello data=\"all\" /}
+          {call baz.other data=\"$user\"}{param x: 1 /}{/call}
+          {call .hello}{param name kind=\"text\"}<{$name}>{/param}{/call}
+        {/template}",
+        "{namespace baz}\n/**\n * @param x\n */\n{template .other}{$x}{/template}",
+    ]);
+    assert!(code.contains("  out += foo$bar$hello({...params}, ij);\n"));
+    assert!(code.contains(
+        "out += baz$other({...$$callData($$param(params, \"user\"), \"baz.other\"), \
+         [\"x\"]: 1}, ij);"
+    ));
+    assert!(code.contains("    const $$block1 = (() => {\n      let out = '';\n"));
+    assert!(code.contains("out += $$print($$param(params, \"name\"), 'text', false, []);"));
+    assert!(code.contains("return new SanitizedContent('text', out);"));
+    assert!(code.contains("out += foo$bar$hello({[\"name\"]: $$block1}, ij);"));
+    assert!(code.contains("function $$callData(data, target) {"));
+}
+
+#[test]
+fn test_calls_to_templates_of_another_kind_are_escaped() {
+    let code = generated_ok(&["{namespace foo}
+        /**
+         * @param name
+         */
+        {template .name kind=\"text\"}[{$name}]{/template}
+        /** */
+        {template .html}<p>{call .name}{param name: '<Bob>' /}{/call}</p>{/template}"]);
+    assert!(code.contains(
+        "out += $$print(new SanitizedContent('text', foo$name({[\"name\"]: \"\\x3cBob>\"}, ij)), \
+         'html', true, []);"
+    ));
+}
+
+#[test]
+fn test_calls_to_unknown_templates() {
+    let (_, diagnostics) =
+        generated(&["{namespace foo}\n/** */\n{template .bar}{call .baz /}{/template}"]);
+    let codes: Vec<_> = diagnostics.errors().map(|e| e.kind.code()).collect();
+    assert_eq!(codes, vec!["E0016"]);
+}
@@ -0,0 +1,740 @@
+//! Generates a Rust module from a set of soy files, with a module for each namespace
+//! containing a function and a parameter struct for each template.
+//!
+//! Expressions are evaluated with the same functions as the interpreter (through
+//! `soy::runtime`), so that both render the same output. The generated functions render
+//! with the default options: no CSS or id renaming, a left to right page, no injected
+//! data, and messages as written.
+//!
+//! Templates are called through their functions, so `data="all"` only passes on the
+//! parameters the caller declares, which are all it has fields for.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use crate::ast::{
+    BinaryOperator, Call, CallData, CallParamValue, Command, ContentKind, Expression, MsgBody,
+    Reference, ReferenceKey, Referent, SoyFile, SoydocParam, Template, TemplateNode, Visibility,
+};
+use crate::error::{CompileError, CompileErrorKind, Diagnostics, TemplateLocation};
+use crate::link::Linker;
+use crate::msg::{Message, MsgPart, PlaceholderPiece, PluralCaseSpec};
+use crate::tofu::{self, optimize, DirectiveKind, Function};
+
+#[cfg(test)]
+mod tests;
+
+/// Generates the code for the templates in a set of files, reporting any templates that
+/// can't be compiled to `diagnostics`.
+pub(crate) fn generate(files: Vec<SoyFile>, diagnostics: &mut Diagnostics) -> String {
+    let mut root = Module::default();
+    let globals = Default::default();
+    // Where each template was defined, by module path and name
+    let mut defined = HashMap::new();
+    let mut linker = Linker::default();
+    // The function each template is called through, by fully-qualified name
+    let mut callees = HashMap::new();
+    for file in &files {
+        linker.add_file(file);
+        let path: Vec<_> = file.namespace.name.split('.').map(snake_case).collect();
+        for template in &file.templates {
+            let qualified_name = format!("{}.{}", file.namespace.name, template.name);
+            callees.entry(qualified_name).or_insert_with(|| Callee {
+                path: path.clone(),
+                name: template.name.clone(),
+                kind: template.attributes.kind,
+                params: template
+                    .soydoc_params
+                    .iter()
+                    .map(|param| (param.name.clone(), param.required))
+                    .collect(),
+            });
+        }
+    }
+    linker.check(diagnostics);
+    for file in files {
+        let path: Vec<_> = file.namespace.name.split('.').map(snake_case).collect();
+        let module = path.iter().fold(&mut root, |module, name| {
+            module.children.entry(name.clone()).or_default()
+        });
+        for mut template in file.templates {
+            let qualified_name = format!("{}.{}", file.namespace.name, template.name);
+            let key = (path.clone(), template.name.clone());
+            if let Some(first) = defined.get(&key) {
+                diagnostics.push(CompileError {
+                    kind: CompileErrorKind::DuplicateTemplate(qualified_name, Box::clone(first)),
+                    location: Some(Box::new(template.location.0.clone())),
+                    cause: None,
+                });
+                continue;
+            }
+            defined.insert(key, Box::new(template.location.0.clone()));
+            optimize::optimize(&mut template, &globals);
+            let generator = TemplateGenerator {
+                template: &template,
+                qualified_name: &qualified_name,
+                kind: template.attributes.kind,
+                depth: path.len(),
+                callees: &callees,
+                code: String::new(),
+                indent: 1,
+                labels: 0,
+                diagnostics: &mut *diagnostics,
+            };
+            let code = generator.generate();
+            module.templates.insert(template.name.clone(), code);
+        }
+    }
+    let mut code = String::from("// Generated by soy::build from soy templates. Do not edit.\n");
+    for (name, module) in &root.children {
+        code.push_str(
+            "\n#[allow(dead_code, unused_mut, unused_variables, unreachable_patterns, clippy::all)]",
+        );
+        module.write(&mut code, name, 0);
+    }
+    code
+}
+
+#[derive(Default)]
+struct Module {
+    children: BTreeMap<String, Module>,
+    /// The code of each template, by name.
+    templates: BTreeMap<String, String>,
+}
+
+impl Module {
+    fn write(&self, code: &mut String, name: &str, depth: usize) {
+        let indent = "    ".repeat(depth);
+        write!(code, "\n{}pub mod {} {{", indent, ident(name)).unwrap();
+        for template in self.templates.values() {
+            code.push('\n');
+            for line in template.lines() {
+                if !line.is_empty() {
+                    code.push_str(&indent);
+                }
+                code.push_str(line);
+                code.push('\n');
+            }
+        }
+        for (name, child) in &self.children {
+            child.write(code, name, depth + 1);
+        }
+        writeln!(code, "{}}}", indent).unwrap();
+    }
+}
+
+/// A template as seen by its callers.
+struct Callee {
+    /// The module path of its namespace.
+    path: Vec<String>,
+    name: String,
+    /// The kind of content it writes, which callers of another kind escape.
+    kind: ContentKind,
+    /// The names of its parameters, and whether each is required.
+    params: Vec<(String, bool)>,
+}
+
+struct TemplateGenerator<'a> {
+    template: &'a Template,
+    qualified_name: &'a str,
+    /// The kind of content being generated, which printed values are escaped for. It is
+    /// the template's kind, except in the params of a call.
+    kind: ContentKind,
+    /// The depth of the template's module, which calls go up from.
+    depth: usize,
+    callees: &'a HashMap<String, Callee>,
+    code: String,
+    indent: usize,
+    /// The number of labelled blocks generated so far, which are numbered to be unique.
+    labels: usize,
+    diagnostics: &'a mut Diagnostics,
+}
+
+impl<'a> TemplateGenerator<'a> {
+    fn generate(mut self) -> String {
+        let template = self.template;
+        let struct_name = format!("{}Params", upper_camel_case(&template.name));
+        let visibility = match template.attributes.visibility {
+            Visibility::Public => "pub ",
+            Visibility::Private => "",
+        };
+        let mut code = String::new();
+        writeln!(code, "    /// The parameters of `{}`.", self.qualified_name).unwrap();
+        writeln!(code, "    #[derive(Debug, Clone)]").unwrap();
+        writeln!(code, "    pub struct {} {{", struct_name).unwrap();
+        for param in &template.soydoc_params {
+            let field_type = if param.required {
+                "::soy::SoyValue"
+            } else {
+                "Option<::soy::SoyValue>"
+            };
+            writeln!(code, "        pub {}: {},", field_name(param), field_type).unwrap();
+        }
+        writeln!(code, "    }}\n").unwrap();
+        writeln!(code, "    /// Renders `{}`.", self.qualified_name).unwrap();
+        writeln!(
+            code,
+            "    {}fn {}<W: ::std::io::Write>(\n        writer: &mut W,\n        params: &{},\n    ) \
+             -> Result<(), ::soy::error::RenderError> {{",
+            visibility,
+            ident(&snake_case(&template.name)),
+            struct_name
+        )
+        .unwrap();
+        self.indent = 2;
+        self.block(&template.body);
+        self.line("Ok(())");
+        code.push_str(&self.code);
+        code.push_str("    }\n");
+        code
+    }
+
+    fn line(&mut self, line: &str) {
+        for _ in 0..self.indent {
+            self.code.push_str("    ");
+        }
+        self.code.push_str(line);
+        self.code.push('\n');
+    }
+
+    fn error(&mut self, feature: String) {
+        self.diagnostics.push(CompileError {
+            kind: CompileErrorKind::Unsupported(feature),
+            location: Some(Box::new(TemplateLocation {
+                template_name: Some(self.qualified_name.to_owned()),
+                ..Default::default()
+            })),
+            cause: None,
+        });
+    }
+
+    fn block(&mut self, block: &[TemplateNode]) {
+        for node in block {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &TemplateNode) {
+        match node {
+            TemplateNode::RawText(text) | TemplateNode::Special(text) => self.text(text),
+            TemplateNode::Statement(command) => self.command(command),
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.line(&format!("writer.write_all({:?}.as_bytes())?;", text));
+        }
+    }
+
+    fn command(&mut self, command: &Command) {
+        match command {
+            Command::If {
+                if_block,
+                else_ifs,
+                else_block,
+            } => {
+                for (i, conditional) in std::iter::once(if_block).chain(else_ifs).enumerate() {
+                    let condition = self.expression(&conditional.expression);
+                    let keyword = if i == 0 { "if" } else { "} else if" };
+                    self.line(&format!("{} ({}).is_truthy() {{", keyword, condition));
+                    self.indented_block(&conditional.block);
+                }
+                if let Some(block) = else_block {
+                    self.line("} else {");
+                    self.indented_block(block);
+                }
+                self.line("}");
+            }
+            Command::Msg { body, .. } => self.msg(body),
+            Command::Print {
+                expression,
+                directives,
+                ..
+            } => {
+                let (kind, autoescape) = (self.kind, tofu::autoescape(self.kind));
+                let value = self.expression(expression);
+                self.line("{");
+                self.indent += 1;
+                self.line(&format!("let value = {};", value));
+                self.line(&format!(
+                    "let mut printed = ::soy::runtime::Printed::new(value, {}, {});",
+                    content_kind(kind),
+                    autoescape
+                ));
+                for directive in directives {
+                    let kind = match DirectiveKind::from_name(&directive.name) {
+                        DirectiveKind::Unknown(name) => {
+                            self.error(format!("unknown print directive |{}", name));
+                            continue;
+                        }
+                        kind => kind,
+                    };
+                    let args = self.expressions(&directive.arguments);
+                    self.line(&format!(
+                        "printed.apply(&::soy::runtime::DirectiveKind::{:?}, &[{}], \
+                         ::soy::runtime::BidiDir::Ltr)?;",
+                        kind, args
+                    ));
+                }
+                self.line("writer.write_all(printed.finish().as_bytes())?;");
+                self.indent -= 1;
+                self.line("}");
+            }
+            Command::Literal(text) => self.text(text),
+            Command::Css { base, name } => match (base, name.strip_prefix('%')) {
+                (Some(base), _) => {
+                    let base = self.expression(base);
+                    self.line(&format!(
+                        "writer.write_all(::soy::runtime::css_class(&{}, {:?})?.as_bytes())?;",
+                        base, name
+                    ));
+                }
+                (None, Some(suffix)) => match &self.template.attributes.css_base {
+                    Some(css_base) => self.text(&format!("{}-{}", css_base, suffix)),
+                    None => self.error(format!("{{css {}}} without a cssbase", name)),
+                },
+                (None, None) => self.text(name),
+            },
+            Command::Xid(name) => self.text(name),
+            Command::Call(call) => self.call(call),
+        }
+    }
+
+    /// Calls the function of a template with its parameters, which are taken from the
+    /// params of the call, then from the data passed to it. Params that the template
+    /// doesn't declare have no field to go in, so are left out.
+    fn call(&mut self, call: &Call) {
+        let callees = self.callees;
+        // Calls to unknown templates are reported by the linker
+        let callee = match callees.get(&call.target) {
+            Some(callee) => callee,
+            None => return,
+        };
+        let escaped = callee.kind != self.kind;
+        let scoped = escaped
+            || matches!(call.data, Some(CallData::Expression(_)))
+            || call
+                .params
+                .iter()
+                .any(|param| matches!(param.value, CallParamValue::Block { .. }));
+        if scoped {
+            self.line("{");
+            self.indent += 1;
+        }
+        if let Some(CallData::Expression(data)) = &call.data {
+            let data = self.expression(data);
+            self.line(&format!(
+                "let mut data = ::soy::runtime::call_data({:?}, {})?;",
+                call.target, data
+            ));
+        }
+        if escaped {
+            self.line("let mut output = Vec::new();");
+        }
+        let mut values = HashMap::new();
+        for param in &call.params {
+            let value = match &param.value {
+                CallParamValue::Expression(expression) => self.expression(expression),
+                CallParamValue::Block { kind, block } => {
+                    let name = format!("block{}", values.len());
+                    self.line(&format!("let {} = {{", name));
+                    self.indent += 1;
+                    self.line("use ::std::io::Write as _;");
+                    self.line("let mut buffer = Vec::new();");
+                    self.line("{");
+                    self.indent += 1;
+                    self.line("let writer = &mut buffer;");
+                    let outer = std::mem::replace(&mut self.kind, *kind);
+                    self.block(block);
+                    self.kind = outer;
+                    self.indent -= 1;
+                    self.line("}");
+                    self.line(&format!(
+                        "::soy::runtime::sanitized({}, buffer)?",
+                        content_kind(*kind)
+                    ));
+                    self.indent -= 1;
+                    self.line("};");
+                    name
+                }
+            };
+            values.insert(param.name.as_str(), value);
+        }
+        let mut path = "super::".repeat(self.depth);
+        for segment in &callee.path {
+            path.push_str(&ident(segment));
+            path.push_str("::");
+        }
+        self.line(&format!(
+            "{}{}({}, &{}{}Params {{",
+            path,
+            ident(&snake_case(&callee.name)),
+            if escaped { "&mut output" } else { "writer" },
+            path,
+            upper_camel_case(&callee.name)
+        ));
+        self.indent += 1;
+        for (name, required) in &callee.params {
+            let value = match (values.get(name.as_str()), &call.data) {
+                (Some(value), _) if *required => value.clone(),
+                (Some(value), _) => format!("Some({})", value),
+                (None, Some(CallData::All)) => {
+                    let param = self
+                        .template
+                        .soydoc_params
+                        .iter()
+                        .find(|param| param.name == *name);
+                    match param {
+                        Some(param) => match (param.required, required) {
+                            (true, true) => format!("params.{}.clone()", field_name(param)),
+                            (true, false) => format!("Some(params.{}.clone())", field_name(param)),
+                            (false, true) => format!(
+                                "params.{}.clone().unwrap_or(::soy::SoyValue::Null)",
+                                field_name(param)
+                            ),
+                            (false, false) => format!("params.{}.clone()", field_name(param)),
+                        },
+                        None if *required => "::soy::SoyValue::Null".to_owned(),
+                        None => "None".to_owned(),
+                    }
+                }
+                (None, Some(CallData::Expression(_))) if *required => {
+                    format!("data.remove({:?}).unwrap_or(::soy::SoyValue::Null)", name)
+                }
+                (None, Some(CallData::Expression(_))) => format!("data.remove({:?})", name),
+                (None, None) if *required => "::soy::SoyValue::Null".to_owned(),
+                (None, None) => "None".to_owned(),
+            };
+            self.line(&format!("{}: {},", ident(&snake_case(name)), value));
+        }
+        self.indent -= 1;
+        self.line("})?;");
+        if escaped {
+            // The callee's output is trusted only as its own kind
+            self.line(&format!(
+                "let printed = ::soy::runtime::Printed::new(\
+                 ::soy::runtime::sanitized({}, output)?, {}, {});",
+                content_kind(callee.kind),
+                content_kind(self.kind),
+                tofu::autoescape(self.kind)
+            ));
+            self.line("writer.write_all(printed.finish().as_bytes())?;");
+        }
+        if scoped {
+            self.indent -= 1;
+            self.line("}");
+        }
+    }
+
+    fn indented_block(&mut self, block: &[TemplateNode]) {
+        self.indent += 1;
+        self.block(block);
+        self.indent -= 1;
+    }
+
+    /// Renders a message as written, since translations aren't compiled in.
+    fn msg(&mut self, body: &MsgBody) {
+        let message = Message::new(body);
+        self.msg_parts(&message, &message.parts);
+    }
+
+    fn msg_parts(&mut self, message: &Message, parts: &[MsgPart]) {
+        for part in parts {
+            match part {
+                MsgPart::RawText(text) => self.text(text),
+                MsgPart::Placeholder(name) => {
+                    for piece in message.placeholder(name).unwrap_or_default() {
+                        match piece {
+                            PlaceholderPiece::Text(text) => self.text(text),
+                            PlaceholderPiece::Node(node) => self.node(node),
+                        }
+                    }
+                }
+                MsgPart::Plural { var, offset, cases } => {
+                    let value = self.msg_variable(message, var);
+                    self.line("{");
+                    self.indent += 1;
+                    self.line(&format!("let value = {};", value));
+                    // Explicit cases are checked first, then the category of the value
+                    let mut closing = 0;
+                    for (spec, parts) in cases {
+                        if let PluralCaseSpec::Explicit(n) = spec {
+                            self.line(&format!("if value.as_number() == Some({:?}) {{", *n as f64));
+                            self.indented_msg_parts(message, parts);
+                            self.line("} else {");
+                            self.indent += 1;
+                            closing += 1;
+                        }
+                    }
+                    self.line(&format!(
+                        "match ::soy::runtime::plural_category(&value, {}) {{",
+                        offset
+                    ));
+                    self.indent += 1;
+                    for (spec, parts) in cases {
+                        if let PluralCaseSpec::Category(category) = spec {
+                            let pattern = if category == "other" {
+                                "_".to_owned()
+                            } else {
+                                format!("{:?}", category)
+                            };
+                            self.line(&format!("{} => {{", pattern));
+                            self.indented_msg_parts(message, parts);
+                            self.line("}");
+                        }
+                    }
+                    self.indent -= 1;
+                    self.line("}");
+                    for _ in 0..closing {
+                        self.indent -= 1;
+                        self.line("}");
+                    }
+                    self.indent -= 1;
+                    self.line("}");
+                }
+                MsgPart::Select { var, cases } => {
+                    let value = self.msg_variable(message, var);
+                    self.line(&format!("match ({}).to_string().as_str() {{", value));
+                    self.indent += 1;
+                    for (value, parts) in cases {
+                        let pattern = if value == "other" {
+                            "_".to_owned()
+                        } else {
+                            format!("{:?}", value)
+                        };
+                        self.line(&format!("{} => {{", pattern));
+                        self.indented_msg_parts(message, parts);
+                        self.line("}");
+                    }
+                    self.indent -= 1;
+                    self.line("}");
+                }
+            }
+        }
+    }
+
+    fn indented_msg_parts(&mut self, message: &Message, parts: &[MsgPart]) {
+        self.indent += 1;
+        self.msg_parts(message, parts);
+        self.indent -= 1;
+    }
+
+    fn msg_variable(&mut self, message: &Message, var: &str) -> String {
+        match message.variable(var) {
+            Some(expression) => self.expression(expression),
+            None => "::soy::SoyValue::Null".to_owned(),
+        }
+    }
+
+    fn expressions(&mut self, expressions: &[Expression]) -> String {
+        expressions
+            .iter()
+            .map(|expression| self.expression(expression))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The code for an expression, which evaluates to a `SoyValue` and may return errors
+    /// with `?`.
+    fn expression(&mut self, expression: &Expression) -> String {
+        match expression {
+            Expression::Null => "::soy::SoyValue::Null".to_owned(),
+            Expression::Boolean(b) => format!("::soy::SoyValue::Bool({})", b),
+            Expression::Integer(i) => format!("::soy::SoyValue::Int({}i64)", i),
+            // Written as bits so that infinities and NaN round trip
+            Expression::Float(f) => {
+                format!("::soy::SoyValue::Float(f64::from_bits({:#x}))", f.to_bits())
+            }
+            Expression::String(s) => format!("::soy::SoyValue::String({:?}.to_owned())", s),
+            Expression::List(items) => {
+                format!("::soy::SoyValue::List(vec![{}])", self.expressions(items))
+            }
+            Expression::Map(entries) => {
+                // Sorted so the output is stable
+                let mut entries: Vec<_> = entries.iter().collect();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                let entries: Vec<_> = entries
+                    .into_iter()
+                    .map(|(key, value)| {
+                        format!("({:?}.to_owned(), {})", key, self.expression(value))
+                    })
+                    .collect();
+                format!(
+                    "::soy::SoyValue::Map(vec![{}].into_iter().collect())",
+                    entries.join(", ")
+                )
+            }
+            Expression::Function { name, parameters } => match Function::from_name(name) {
+                Some(function) => format!(
+                    "::soy::runtime::call_function(::soy::runtime::Function::{:?}, vec![{}])?",
+                    function,
+                    self.expressions(parameters)
+                ),
+                None => {
+                    self.error(format!("unknown function {}()", name));
+                    "::soy::SoyValue::Null".to_owned()
+                }
+            },
+            Expression::DataReference {
+                referent,
+                references,
+            } => self.reference(referent, references),
+            Expression::GlobalReference(name) => {
+                self.error(format!("unknown global {}", name));
+                "::soy::SoyValue::Null".to_owned()
+            }
+            Expression::BinaryOperation { lhs, op, rhs } => {
+                let (lhs, rhs) = (self.expression(lhs), self.expression(rhs));
+                match op {
+                    BinaryOperator::And => format!(
+                        "::soy::SoyValue::Bool(({}).is_truthy() && ({}).is_truthy())",
+                        lhs, rhs
+                    ),
+                    BinaryOperator::Or => format!(
+                        "::soy::SoyValue::Bool(({}).is_truthy() || ({}).is_truthy())",
+                        lhs, rhs
+                    ),
+                    BinaryOperator::Elvis => format!(
+                        "match {} {{ ::soy::SoyValue::Null => {}, value => value }}",
+                        lhs, rhs
+                    ),
+                    op => format!(
+                        "::soy::runtime::binary_operation(&::soy::runtime::BinaryOperator::{:?}, \
+                         {}, {})?",
+                        op, lhs, rhs
+                    ),
+                }
+            }
+            Expression::UnaryOperation { op, rhs } => format!(
+                "::soy::runtime::unary_operation(::soy::runtime::UnaryOperator::{:?}, {})?",
+                op,
+                self.expression(rhs)
+            ),
+            Expression::TernaryOperation {
+                condition,
+                if_true,
+                if_false,
+            } => format!(
+                "if ({}).is_truthy() {{ {} }} else {{ {} }}",
+                self.expression(condition),
+                self.expression(if_true),
+                self.expression(if_false)
+            ),
+        }
+    }
+
+    /// The code for a data reference, which follows the references in a labelled block
+    /// so that null safe references can stop early.
+    fn reference(&mut self, referent: &Referent, references: &[Reference]) -> String {
+        let root = match referent {
+            Referent::Variable(name) => {
+                let param = self
+                    .template
+                    .soydoc_params
+                    .iter()
+                    .find(|param| param.name == *name);
+                match param {
+                    Some(param) if param.required => format!("&params.{}", field_name(param)),
+                    Some(param) => format!(
+                        "params.{}.as_ref().unwrap_or(&::soy::SoyValue::Null)",
+                        field_name(param)
+                    ),
+                    None => "&::soy::SoyValue::Null".to_owned(),
+                }
+            }
+            // Generated code is rendered without injected data
+            Referent::Injected(_) => "&::soy::SoyValue::Null".to_owned(),
+        };
+        if references.is_empty() {
+            return format!("({}).clone()", root);
+        }
+        self.labels += 1;
+        let label = format!("'reference{}", self.labels);
+        let mut code = format!("{}: {{ let value: &::soy::SoyValue = {}; ", label, root);
+        for reference in references {
+            let (key, null_safe) = match reference {
+                Reference::Dotted(key) => (reference_key(key), false),
+                Reference::QuestionDotted(key) => (reference_key(key), true),
+                Reference::Bracketed(key) => (self.expression(key), false),
+                Reference::QuestionBracketed(key) => (self.expression(key), true),
+            };
+            write!(
+                code,
+                "let value = match ::soy::runtime::lookup(value, &{}, {})? {{ \
+                 Some(value) => value, None => break {} ::soy::SoyValue::Null }}; ",
+                key, null_safe, label
+            )
+            .unwrap();
+        }
+        code.push_str("value.clone() }");
+        code
+    }
+}
+
+fn reference_key(key: &ReferenceKey) -> String {
+    match key {
+        ReferenceKey::Number(i) => format!("::soy::SoyValue::Int({}i64)", i),
+        ReferenceKey::Name(name) => format!("::soy::SoyValue::String({:?}.to_owned())", name),
+    }
+}
+
+fn content_kind(kind: ContentKind) -> String {
+    format!("::soy::runtime::ContentKind::{:?}", kind)
+}
+
+fn field_name(param: &SoydocParam) -> String {
+    ident(&snake_case(&param.name))
+}
+
+/// Converts a name in lower camel case to snake case, e.g. `helloWorld` to `hello_world`.
+fn snake_case(name: &str) -> String {
+    let chars: Vec<_> = name.chars().collect();
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            let previous = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|c| c.is_ascii_lowercase());
+            if previous.is_ascii_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_ascii_uppercase() && next_is_lower)
+            {
+                snake.push('_');
+            }
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+/// Converts a name in lower camel or snake case to upper camel case, e.g. `helloWorld` to
+/// `HelloWorld`.
+fn upper_camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// A Rust identifier for a name, which is a raw identifier if the name is a keyword.
+fn ident(name: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do",
+        "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in",
+        "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+        "return", "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe",
+        "unsized", "use", "virtual", "where", "while", "yield",
+    ];
+    match name {
+        // These can't be raw identifiers
+        "self" | "Self" | "super" | "crate" => format!("{}_", name),
+        name if KEYWORDS.contains(&name) => format!("r#{}", name),
+        name => name.to_owned(),
+    }
+}
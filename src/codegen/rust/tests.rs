@@ -0,0 +1,154 @@
+use super::*;
+use crate::parser;
+
+/// The code generated for a set of files, and the diagnostics reported for them.
+fn generated(files: &[&str]) -> (String, Diagnostics) {
+    let mut diagnostics = Diagnostics::default();
+    let files = files
+        .iter()
+        .map(|file| parser::parse(file).unwrap())
+        .collect();
+    let code = generate(files, &mut diagnostics);
+    (code, diagnostics)
+}
+
+fn generated_ok(files: &[&str]) -> String {
+    let (code, diagnostics) = generated(files);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    code
+}
+
+#[test]
+fn test_modules_and_signatures() {
+    let code = generated_ok(&[
+        "{namespace foo.barBaz}
+        /**
+         * @param userName
+         * @param? type
+         */
+        {template .helloWorld}Hello {$userName}{/template}
+        /** Hidden. */
+        {template .hidden visibility=\"private\"}{/template}",
+        "{namespace foo.self}\n/** A. */\n{template .a}{/template}",
+    ]);
+    assert!(code.contains("pub mod foo {"));
+    assert!(code.contains("    pub mod bar_baz {"));
+    assert!(code.contains("    pub mod self_ {"));
+    assert!(code.contains("pub struct HelloWorldParams {"));
+    assert!(code.contains("pub user_name: ::soy::SoyValue,"));
+    assert!(code.contains("pub r#type: Option<::soy::SoyValue>,"));
+    assert!(code.contains("pub fn hello_world<W: ::std::io::Write>("));
+    assert!(code.contains("\n        fn hidden<W: ::std::io::Write>("));
+    assert!(code.contains("writer.write_all(\"Hello \".as_bytes())?;"));
+    assert!(code.contains("let value = (&params.user_name).clone();"));
+}
+
+#[test]
+fn test_expressions() {
+    let code = generated_ok(&["{namespace foo}
+        /**
+         * @param x
+         */
+        {template .bar}{$x?.a[0] + 1}{if $x and not $x.b}{max($x, 2.5)}{/if}{/template}"]);
+    assert!(code.contains(
+        "'reference1: { let value: &::soy::SoyValue = &params.x; \
+         let value = match ::soy::runtime::lookup(value, \
+         &::soy::SoyValue::String(\"a\".to_owned()), true)? {"
+    ));
+    assert!(code.contains("::soy::runtime::BinaryOperator::Plus"));
+    assert!(code.contains("::soy::runtime::UnaryOperator::Not"));
+    assert!(code.contains("::soy::runtime::call_function(::soy::runtime::Function::Max"));
+    assert!(code.contains(").is_truthy() && ("));
+}
+
+#[test]
+fn test_constants_are_folded() {
+    let code = generated_ok(&["{namespace foo}\n/** Bar. */\n{template .bar}{1 + 2}{/template}"]);
+    assert!(code.contains("let value = ::soy::SoyValue::Int(3i64);"));
+    assert!(!code.contains("binary_operation"));
+}
+
+#[test]
+fn test_unsupported() {
+    let (_, diagnostics) = generated(&["{namespace foo}
+        /** Bar. */
+        {template .bar}{GLOBAL}{OTHER.GLOBAL}{/template}"]);
+    let errors: Vec<_> = diagnostics.errors().map(|e| e.to_string()).collect();
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert!(errors[0].contains("unknown global GLOBAL"), "{}", errors[0]);
+    assert!(errors[0].contains("foo.bar"), "{}", errors[0]);
+}
+
+#[test]
+fn test_duplicate_templates() {
+    let (_, diagnostics) = generated(&[
+        "{namespace foo}\n/** Bar. */\n{template .bar}{/template}",
+        "{namespace foo}\n/** Bar. */\n{template .bar}{/template}",
+    ]);
+    assert_eq!(diagnostics.errors().count(), 1);
+    assert!(diagnostics.errors().all(|e| e.kind.code() == "E0003"));
+    let error = diagnostics.errors().next().unwrap().to_string();
+    assert!(error.contains("note: first defined here"), "{}", error);
+}
+
+#[test]
+fn test_calls() {
+    let code = generated_ok(&[
+        "{namespace foo.bar}
+        /**
+         * @param name
+         * @param? greeting
+         */
+        {template .hello}{$greeting ?: 'Hello'}, {$name}!{/template}
+        /**
+         * @param? name
+         * @param user
+         */
+        {template .caller}
+          {call .hello data=\"all\" /}
+          {call baz.other data=\"$user\" /}
+          {call .hello}{param name kind=\"text\"}<{$name}>{/param}{/call}
+        {/template}",
+        "{namespace baz}\n/**\n * @param x\n */\n{template .other}{$x}{/template}",
+    ]);
+    assert!(code.contains(
+        "super::super::foo::bar::hello(writer, &super::super::foo::bar::HelloParams {\n\
+         \x20               name: params.name.clone().unwrap_or(::soy::SoyValue::Null),\n\
+         \x20               greeting: None,\n\
+         \x20           })?;"
+    ));
+    assert!(code.contains(
+        "let mut data = ::soy::runtime::call_data(\"baz.other\", (&params.user).clone())?;"
+    ));
+    assert!(code.contains("x: data.remove(\"x\").unwrap_or(::soy::SoyValue::Null),"));
+    // Blocks are rendered to a buffer, and printed values in them escaped for their kind
+    assert!(code.contains("let writer = &mut buffer;"));
+    assert!(code.contains("::soy::runtime::ContentKind::Text, false"));
+    assert!(code.contains("::soy::runtime::sanitized(::soy::runtime::ContentKind::Text, buffer)?"));
+    assert!(code.contains("name: block0,"));
+}
+
+#[test]
+fn test_calls_to_templates_of_another_kind_are_escaped() {
+    let code = generated_ok(&["{namespace foo}
+        /**
+         * @param name
+         */
+        {template .name kind=\"text\"}[{$name}]{/template}
+        /** */
+        {template .html}<p>{call .name}{param name: '<Bob>' /}{/call}</p>{/template}"]);
+    assert!(code.contains("super::foo::name(&mut output, &super::foo::NameParams {"));
+    assert!(code.contains(
+        "let printed = ::soy::runtime::Printed::new(\
+         ::soy::runtime::sanitized(::soy::runtime::ContentKind::Text, output)?, \
+         ::soy::runtime::ContentKind::Html, true);"
+    ));
+}
+
+#[test]
+fn test_calls_to_unknown_templates() {
+    let (_, diagnostics) =
+        generated(&["{namespace foo}\n/** */\n{template .bar}{call .baz /}{/template}"]);
+    let codes: Vec<_> = diagnostics.errors().map(|e| e.kind.code()).collect();
+    assert_eq!(codes, vec!["E0016"]);
+}
@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::ast::{CallParamValue, Command, TemplateBlock, TemplateNode};
+use crate::error::{CompileErrors, Diagnostics};
+use crate::msg::{Message, MsgPart, PluralCaseSpec};
+use crate::tofu::Source;
+use crate::xml;
+
+#[cfg(test)]
+mod tests;
+
+/// Extracts the messages from soy sources into an XLIFF file for translators, in the
+/// same format as the Closure Templates `SoyMsgExtractor`.
+#[derive(Default)]
+pub struct MsgExtractor {
+    sources: Vec<Source>,
+    source_locale: Option<String>,
+    target_locale: Option<String>,
+}
+
+/// A message found by the extractor.
+struct ExtractedMsg {
+    desc: Option<String>,
+    meaning: Option<String>,
+    parts: Vec<MsgPart>,
+}
+
+impl MsgExtractor {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn add_string_template(mut self, template: &str) -> Self {
+        self.sources.push(Source::String(template.to_owned()));
+        self
+    }
+
+    /// Adds a soy file. The file is read when messages are extracted.
+    pub fn add_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.sources.push(Source::File(path.into()));
+        self
+    }
+
+    /// The language the messages are written in, `en` by default.
+    pub fn source_locale(mut self, locale: &str) -> Self {
+        self.source_locale = Some(locale.to_owned());
+        self
+    }
+
+    /// The language the messages are to be translated into, if known.
+    pub fn target_locale(mut self, locale: &str) -> Self {
+        self.target_locale = Some(locale.to_owned());
+        self
+    }
+
+    /// Extracts every message into an XLIFF document, ordered by id. Messages that
+    /// appear more than once (with the same content and meaning) are extracted once.
+    pub fn extract(&self) -> Result<String, CompileErrors> {
+        let mut diagnostics = Diagnostics::default();
+        let mut messages = BTreeMap::new();
+        for source in &self.sources {
+            if let Some(file) = source.parse(&mut diagnostics) {
+                for template in &file.templates {
+                    collect_messages(&template.body, &mut messages);
+                }
+            }
+        }
+        if diagnostics.has_errors() {
+            return Err(CompileErrors { diagnostics });
+        }
+        Ok(self.write_xliff(&messages))
+    }
+
+    fn write_xliff(&self, messages: &BTreeMap<u64, ExtractedMsg>) -> String {
+        let mut xliff = String::new();
+        xliff.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xliff.push_str("<xliff version=\"1.2\" xmlns=\"urn:oasis:names:tc:xliff:document:1.2\">\n");
+        xliff.push_str(&format!(
+            "  <file original=\"SoyMsgBundle\" datatype=\"x-soy-msg\" xml:space=\"preserve\" \
+             source-language=\"{}\"",
+            xml::escape(self.source_locale.as_deref().unwrap_or("en"))
+        ));
+        if let Some(target) = &self.target_locale {
+            xliff.push_str(&format!(" target-language=\"{}\"", xml::escape(target)));
+        }
+        xliff.push_str(">\n    <body>\n");
+        for (id, msg) in messages {
+            xliff.push_str(&format!(
+                "      <trans-unit id=\"{}\" datatype=\"html\">\n",
+                id
+            ));
+            xliff.push_str("        <source>");
+            write_parts(&mut xliff, &msg.parts);
+            xliff.push_str("</source>\n");
+            for (from, note) in &[("description", &msg.desc), ("meaning", &msg.meaning)] {
+                if let Some(note) = note {
+                    xliff.push_str(&format!(
+                        "        <note priority=\"1\" from=\"{}\">{}</note>\n",
+                        from,
+                        xml::escape(note)
+                    ));
+                }
+            }
+            xliff.push_str("      </trans-unit>\n");
+        }
+        xliff.push_str("    </body>\n  </file>\n</xliff>\n");
+        xliff
+    }
+}
+
+fn collect_messages(block: &TemplateBlock, messages: &mut BTreeMap<u64, ExtractedMsg>) {
+    for node in block {
+        let command = match node {
+            TemplateNode::Statement(command) => command,
+            _ => continue,
+        };
+        match command {
+            Command::Msg {
+                id,
+                desc,
+                meaning,
+                body,
+            } => {
+                messages.entry(*id).or_insert_with(|| ExtractedMsg {
+                    desc: desc.clone(),
+                    meaning: meaning.clone(),
+                    parts: Message::new(body).parts,
+                });
+            }
+            Command::If {
+                if_block,
+                else_ifs,
+                else_block,
+            } => {
+                collect_messages(&if_block.block, messages);
+                for else_if in else_ifs {
+                    collect_messages(&else_if.block, messages);
+                }
+                if let Some(block) = else_block {
+                    collect_messages(block, messages);
+                }
+            }
+            Command::Call(call) => {
+                for param in &call.params {
+                    if let CallParamValue::Block { block, .. } = &param.value {
+                        collect_messages(block, messages);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Writes message parts as XLIFF content, with `<x id="NAME"/>` placeholders and plurals
+/// and selects in ICU syntax, which is what translations are read in.
+fn write_parts(xliff: &mut String, parts: &[MsgPart]) {
+    for part in parts {
+        match part {
+            MsgPart::RawText(text) => xliff.push_str(&xml::escape(text)),
+            MsgPart::Placeholder(name) => {
+                xliff.push_str(&format!("<x id=\"{}\"/>", xml::escape(name)))
+            }
+            MsgPart::Plural { var, offset, cases } => {
+                xliff.push_str(&format!("{{{},plural,", var));
+                if *offset != 0 {
+                    xliff.push_str(&format!("offset:{} ", offset));
+                }
+                for (spec, parts) in cases {
+                    match spec {
+                        PluralCaseSpec::Explicit(n) => xliff.push_str(&format!("={}", n)),
+                        PluralCaseSpec::Category(category) => xliff.push_str(category),
+                    }
+                    xliff.push('{');
+                    write_parts(xliff, parts);
+                    xliff.push('}');
+                }
+                xliff.push('}');
+            }
+            MsgPart::Select { var, cases } => {
+                xliff.push_str(&format!("{{{},select,", var));
+                for (value, parts) in cases {
+                    xliff.push_str(&xml::escape(value));
+                    xliff.push('{');
+                    write_parts(xliff, parts);
+                    xliff.push('}');
+                }
+                xliff.push('}');
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use super::*;
+use crate::{Tofu, TranslationBundle};
+
+static TEMPLATES: &str = "{namespace foo}\n\
+     /**\n * @param name\n * @param count\n */\n\
+     {template .bar}\n\
+       {msg desc=\"greeting\" meaning=\"noun\"}Hello {$name} & <b>friends</b>!{/msg}\n\
+       {msg desc=\"select\"}{select $name}{case 'me'}I did{default}They did{/select}{/msg}\n\
+       {if $count}\n\
+         {msg desc=\"count\"}{plural $count}{case 1}One {$name}{default}{$count} people{/plural}{/msg}\n\
+       {/if}\n\
+     {/template}\n\
+     /**\n * @param name\n */\n\
+     {template .baz}\n\
+       {msg desc=\"repeated\" meaning=\"noun\"}Hello {$name} & <b>friends</b>!{/msg}\n\
+     {/template}";
+
+#[test]
+fn test_extract_xliff() {
+    let xliff = MsgExtractor::new()
+        .add_string_template(TEMPLATES)
+        .target_locale("fr")
+        .extract()
+        .unwrap_or_else(|e| panic!("{}", e));
+    assert!(xliff.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"));
+    assert!(xliff.contains("source-language=\"en\" target-language=\"fr\">"));
+    assert!(xliff.contains(
+        "<source>Hello <x id=\"NAME\"/> &amp; <x id=\"START_BOLD\"/>friends<x id=\"END_BOLD\"/>!</source>\n        \
+         <note priority=\"1\" from=\"description\">greeting</note>\n        \
+         <note priority=\"1\" from=\"meaning\">noun</note>\n"
+    ));
+    assert!(xliff.contains(
+        "<source>{COUNT,plural,=1{One <x id=\"NAME\"/>}other{<x id=\"COUNT\"/> people}}</source>\n        \
+         <note priority=\"1\" from=\"description\">count</note>\n"
+    ));
+    assert!(xliff.contains("<source>{NAME,select,me{I did}other{They did}}</source>"));
+    // Repeated messages are extracted once, with the first description
+    assert_eq!(xliff.matches("<trans-unit ").count(), 3);
+    assert!(!xliff.contains("repeated"));
+}
+
+#[test]
+fn test_extracted_messages_translate() {
+    // Translating by filling in targets that are the same as the sources
+    let xliff = MsgExtractor::new()
+        .add_string_template(TEMPLATES)
+        .target_locale("fr")
+        .extract()
+        .unwrap();
+    let translated = xliff
+        .replace("<source>", "<target>[")
+        .replace("</source>", "]</target>");
+    let tofu = Tofu::builder()
+        .add_string_template(TEMPLATES)
+        .add_translations(TranslationBundle::from_xliff(&translated).unwrap())
+        .build()
+        .unwrap();
+    let render = |count: i64| {
+        tofu.renderer("foo.bar")
+            .param("name", "Ann")
+            .param("count", count)
+            .locale("fr")
+            .render_to_string()
+            .unwrap()
+    };
+    assert_eq!(render(0), "[Hello Ann & <b>friends</b>!][They did]");
+    assert_eq!(
+        render(1),
+        "[Hello Ann & <b>friends</b>!][They did][One Ann]"
+    );
+    assert_eq!(
+        render(2),
+        "[Hello Ann & <b>friends</b>!][They did][2 people]"
+    );
+}
+
+#[test]
+fn test_extract_errors() {
+    let errors = MsgExtractor::new()
+        .add_string_template("{namespace foo}\n/** */\n{template .bar}{msg}{/template}")
+        .add_file("does/not/exist.soy")
+        .extract()
+        .expect_err("should fail");
+    let codes: Vec<_> = errors.diagnostics.errors().map(|e| e.kind.code()).collect();
+    assert_eq!(codes, vec!["E0001", "E0004"]);
+}
@@ -1,9 +1,25 @@
 #![allow(dead_code)]
 
-pub mod error;
+pub mod build;
+#[doc(hidden)]
+pub mod runtime;
 
-pub use self::tofu::Tofu;
+pub use self::ast::ContentKind;
+pub use self::bidi::BidiDir;
+pub use self::extract::MsgExtractor;
+pub use self::renaming::{RenamingMap, RenamingStyle};
+pub use self::tofu::{ReloadingTofu, Renderer, Tofu, TofuBuilder};
+pub use self::translation::TranslationBundle;
+pub use self::value::{SanitizedContent, SoyParams, SoyValue};
+pub use soy_frontend::error;
+pub use soy_macros::{include_templates, SoyParams};
 
-mod ast;
-mod parser;
+use soy_frontend::{ast, ir, link, msg, parser, plural, value};
+
+mod bidi;
+mod codegen;
+mod extract;
+mod renaming;
 mod tofu;
+mod translation;
+mod xml;
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::error::{CompileError, CompileErrorKind};
+
+/// How names are looked up in a `RenamingMap`, as in Closure's `goog.setCssNameMapping`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenamingStyle {
+    /// Each hyphen-separated part of a name is renamed separately (`foo-bar` is renamed
+    /// to `a-b` if `foo` maps to `a` and `bar` to `b`).
+    ByPart,
+    /// Names are renamed as a whole.
+    ByWhole,
+}
+
+/// Renames the class names produced by `{css}` or the ids produced by `{xid}`. Names
+/// (or parts of names) that aren't in the map are left as they are.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenamingMap {
+    style: RenamingStyle,
+    mappings: HashMap<String, String>,
+}
+
+impl RenamingMap {
+    pub fn new(style: RenamingStyle, mappings: HashMap<String, String>) -> Self {
+        RenamingMap { style, mappings }
+    }
+
+    /// Parses a renaming map produced by the Closure Compiler, either as a JSON object
+    /// or as a call to `goog.setCssNameMapping({...}, 'BY_PART')`. Maps without a style
+    /// are renamed by part, as in Closure.
+    pub fn parse(input: &str) -> Result<RenamingMap, CompileError> {
+        let input = input.trim().trim_end_matches(';').trim_end();
+        let args = match input.strip_prefix("goog.setCssNameMapping") {
+            Some(call) => call
+                .trim_start()
+                .strip_prefix('(')
+                .and_then(|call| call.strip_suffix(')'))
+                .ok_or_else(|| malformed("expected goog.setCssNameMapping(...)"))?,
+            None => input,
+        };
+        let mut chars = args.chars().peekable();
+        let mappings = parse_object(&mut chars)?;
+        skip_whitespace(&mut chars);
+        let style = match chars.next() {
+            None => RenamingStyle::ByPart,
+            Some(',') => {
+                skip_whitespace(&mut chars);
+                let style = match parse_string(&mut chars)?.as_str() {
+                    "BY_PART" => RenamingStyle::ByPart,
+                    "BY_WHOLE" => RenamingStyle::ByWhole,
+                    style => return Err(malformed(&format!("unknown style {}", style))),
+                };
+                skip_whitespace(&mut chars);
+                style
+            }
+            Some(c) => return Err(malformed(&format!("unexpected {:?}", c))),
+        };
+        match chars.next() {
+            None => Ok(RenamingMap { style, mappings }),
+            Some(c) => Err(malformed(&format!("unexpected {:?}", c))),
+        }
+    }
+
+    pub fn style(&self) -> RenamingStyle {
+        self.style
+    }
+
+    pub fn rename(&self, name: &str) -> String {
+        let rename = |name: &str| self.mappings.get(name).map_or(name, |n| n).to_owned();
+        match self.style {
+            RenamingStyle::ByWhole => rename(name),
+            RenamingStyle::ByPart => name.split('-').map(rename).collect::<Vec<_>>().join("-"),
+        }
+    }
+}
+
+fn malformed(message: &str) -> CompileError {
+    CompileError {
+        kind: CompileErrorKind::Malformed(format!("renaming map: {}", message)),
+        location: None,
+        cause: None,
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn expect(chars: &mut Peekable<Chars>, expected: char) -> Result<(), CompileError> {
+    skip_whitespace(chars);
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        Some(c) => Err(malformed(&format!(
+            "expected {:?}, found {:?}",
+            expected, c
+        ))),
+        None => Err(malformed(&format!("expected {:?}", expected))),
+    }
+}
+
+/// Parses a JSON object whose values are all strings.
+fn parse_object(chars: &mut Peekable<Chars>) -> Result<HashMap<String, String>, CompileError> {
+    let mut mappings = HashMap::new();
+    expect(chars, '{')?;
+    skip_whitespace(chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+        return Ok(mappings);
+    }
+    loop {
+        skip_whitespace(chars);
+        let key = parse_string(chars)?;
+        expect(chars, ':')?;
+        skip_whitespace(chars);
+        let value = parse_string(chars)?;
+        mappings.insert(key, value);
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => {}
+            Some('}') => return Ok(mappings),
+            _ => return Err(malformed("expected ',' or '}'")),
+        }
+    }
+}
+
+/// Parses a single or double quoted string, with JSON escapes.
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, CompileError> {
+    let quote = match chars.next() {
+        Some(quote @ '"') | Some(quote @ '\'') => quote,
+        _ => return Err(malformed("expected a string")),
+    };
+    let mut string = String::new();
+    loop {
+        match chars.next() {
+            Some(c) if c == quote => return Ok(string),
+            Some('\\') => string.push(match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some('b') => '\u{8}',
+                Some('f') => '\u{c}',
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(std::char::from_u32)
+                        .ok_or_else(|| malformed(&format!("invalid escape \\u{}", hex)))?
+                }
+                Some(c) => c,
+                None => return Err(malformed("unterminated string")),
+            }),
+            Some(c) => string.push(c),
+            None => return Err(malformed("unterminated string")),
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//! Support for the Rust code that `soy::build` generates from templates. This isn't meant
+//! to be used directly, and may change between versions.
+
+pub use crate::ast::{BinaryOperator, ContentKind, UnaryOperator};
+pub use crate::bidi::BidiDir;
+pub use crate::error::RenderError;
+pub use crate::tofu::{
+    binary_operation, call_data, css_class, lookup, sanitized, unary_operation, DirectiveKind,
+    Function, Printed,
+};
+pub use crate::value::SoyValue;
+
+use crate::ast::SoyFile;
+use crate::tofu::{self, RenderOptions, Tofu};
+
+/// The syntax tree of templates, which `include_templates!` writes templates as.
+pub mod ast {
+    pub use crate::ast::*;
+}
+
+/// Calls a function with the default render options: no renaming, and a left to right
+/// page.
+pub fn call_function(function: Function, args: Vec<SoyValue>) -> Result<SoyValue, RenderError> {
+    tofu::call_function(function, args, &RenderOptions::default())
+}
+
+/// The plural category of a value minus an offset, in English, which is what messages
+/// are written in.
+pub fn plural_category(value: &SoyValue, offset: i64) -> &'static str {
+    tofu::plural_category("en", value, offset)
+}
+
+/// Compiles templates that were parsed by `include_templates!`, which has already checked
+/// them for errors.
+pub fn tofu(files: Vec<SoyFile>) -> Tofu {
+    Tofu::with_parsed_files(files).expect("templates are checked when they are included")
+}
@@ -1,86 +1,635 @@
-use crate::ast::{Command, SoyFile, Template, TemplateNode};
-use crate::error::{CompileError, RenderError, RenderErrorKind};
+use crate::ast::{ContentKind, SoyFile, Visibility};
+use crate::bidi::BidiDir;
+use crate::error::{
+    CompileError, CompileErrorKind, CompileErrors, Diagnostics, RenderError, RenderErrorKind,
+};
+use crate::ir::{self, CallData, CallParam, Directive, Node};
+use crate::link::Linker;
 use crate::parser;
-use std::collections::HashMap;
+use crate::renaming::RenamingMap;
+use crate::translation::TranslationBundle;
+use crate::value::{SanitizedContent, SoyParams, SoyValue};
+use std::borrow::Cow;
+use std::collections::hash_map::{Entry, HashMap};
+use std::fs;
 use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+mod escape;
+mod eval;
+mod incremental;
+pub(crate) mod optimize;
+mod print;
+mod reload;
+#[cfg(test)]
+mod tests;
+mod translate;
+
+// Shared with the code generated by `soy::build`, through `soy::runtime`
+pub use self::eval::{binary_operation, css_class, lookup, unary_operation};
+pub(crate) use self::eval::{call_function, RenderOptions};
+pub use self::print::Printed;
+pub use self::reload::ReloadingTofu;
+pub(crate) use self::translate::plural_category;
+pub(crate) use crate::ir::autoescape;
+pub use crate::ir::{DirectiveKind, Function};
+
+/// A compiled set of templates. It can be shared between threads, and cloning it is cheap
+/// since clones share the compiled templates, e.g. to give each thread in a pool its own.
+#[derive(Clone)]
 pub struct Tofu {
-    // TODO: should there be an intermediary object instead of the AST node?
-    templates: HashMap<String, Template>,
+    templates: Arc<Vec<LinkedTemplate>>,
+    /// Indexes into `templates`, by fully-qualified name.
+    names: Arc<HashMap<String, usize>>,
+    /// Keyed by locale.
+    translations: Arc<HashMap<String, TranslationBundle>>,
+    warnings: Arc<Diagnostics>,
+}
+
+/// A compiled template, with its calls resolved to the templates they call.
+#[derive(Clone)]
+struct LinkedTemplate {
+    template: Arc<ir::Template>,
+    /// The indexes of the called templates in `Tofu::templates`, by slot.
+    callees: Vec<usize>,
+}
+
+/// Collects soy sources and compiles them into a `Tofu`, reporting every problem
+/// found across all of the sources at once.
+#[derive(Default)]
+pub struct TofuBuilder {
+    sources: Vec<Source>,
+    translations: Vec<Source<TranslationBundle>>,
+    globals: HashMap<String, SoyValue>,
+    /// What was compiled by `recompile`, to be reused the next time.
+    cache: incremental::Cache,
+}
+
+pub(crate) enum Source<T = String> {
+    String(T),
+    File(PathBuf),
+}
+
+impl Source {
+    /// Reads and parses the source, reporting any problems to `diagnostics`.
+    pub(crate) fn parse(&self, diagnostics: &mut Diagnostics) -> Option<SoyFile> {
+        match self {
+            Source::String(content) => parser::parse_file(None, content, diagnostics),
+            Source::File(path) => {
+                let filename = path.to_string_lossy();
+                match fs::read_to_string(path) {
+                    Ok(content) => parser::parse_file(Some(&filename), &content, diagnostics),
+                    Err(e) => {
+                        diagnostics.push(CompileError {
+                            kind: CompileErrorKind::Io(filename.into_owned(), e),
+                            location: None,
+                            cause: None,
+                        });
+                        None
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl TofuBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn add_string_template(mut self, template: &str) -> Self {
+        self.sources.push(Source::String(template.to_owned()));
+        self
+    }
+
+    /// Adds a soy file. The file is read when the builder is built.
+    pub fn add_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.sources.push(Source::File(path.into()));
+        self
+    }
+
+    /// Adds translations, which are used when rendering in their locale.
+    pub fn add_translations(mut self, translations: TranslationBundle) -> Self {
+        self.translations.push(Source::String(translations));
+        self
+    }
+
+    /// Adds an XLIFF (`.xlf`) or XTB (`.xtb`) translation file. The file is read when the
+    /// builder is built.
+    pub fn add_translation_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.translations.push(Source::File(path.into()));
+        self
+    }
+
+    /// Sets the value of a global, which is substituted into the templates when they are
+    /// compiled. Conditions on globals are resolved then too, so branches that can't be
+    /// taken cost nothing when rendering.
+    pub fn compile_time_global<V: Into<SoyValue>>(mut self, name: &str, value: V) -> Self {
+        self.globals.insert(name.to_owned(), value.into());
+        self
+    }
+
+    pub fn build(&self) -> Result<Tofu, CompileErrors> {
+        let mut diagnostics = Diagnostics::default();
+        let mut tofu = Tofu::empty();
+        let mut linker = Linker::default();
+        for source in &self.sources {
+            let file = source.parse(&mut diagnostics);
+            if let Some(file) = file {
+                linker.add_file(&file);
+                tofu.add_file(file, &self.globals, &mut diagnostics);
+            }
+        }
+        linker.check(&mut diagnostics);
+        self.finish(tofu, diagnostics)
+    }
+
+    /// Compiles the templates again after some of the files have changed, reading only
+    /// those files and the ones that depend on them (which alias their namespaces or call
+    /// their templates). The rest are reused from the last call, so the first call
+    /// compiles everything.
+    ///
+    /// Files are also reused when their content is the same as one compiled before, and
+    /// files that had errors or warnings are always compiled again, so their problems
+    /// are reported every time.
+    pub fn recompile<P: AsRef<Path>>(
+        &mut self,
+        changed_paths: &[P],
+    ) -> Result<Tofu, CompileErrors> {
+        let mut diagnostics = Diagnostics::default();
+        let mut tofu = Tofu::empty();
+        let files = self.cache.compile(
+            &self.sources,
+            changed_paths,
+            &self.globals,
+            &mut diagnostics,
+        );
+        let mut linker = Linker::default();
+        for file in files {
+            linker.add(
+                file.templates
+                    .iter()
+                    .map(|(name, template)| (name.clone(), template.visibility)),
+                file.calls.clone(),
+            );
+            tofu.add_templates(file.templates.iter().cloned(), &mut diagnostics);
+        }
+        linker.check(&mut diagnostics);
+        self.finish(tofu, diagnostics)
+    }
+
+    /// Adds the translations to compiled templates, and links them if there were no errors.
+    fn finish(&self, mut tofu: Tofu, mut diagnostics: Diagnostics) -> Result<Tofu, CompileErrors> {
+        for source in &self.translations {
+            let bundle = match source {
+                Source::String(bundle) => Ok(bundle.clone()),
+                Source::File(path) => TranslationBundle::from_file(path)
+                    .map_err(|e| e.with_filename(Some(&path.to_string_lossy()))),
+            };
+            let result = bundle.and_then(|bundle| {
+                match Arc::make_mut(&mut tofu.translations).entry(bundle.locale().to_owned()) {
+                    Entry::Occupied(mut entry) => entry.get_mut().merge(bundle),
+                    Entry::Vacant(entry) => {
+                        entry.insert(bundle);
+                        Ok(())
+                    }
+                }
+            });
+            if let Err(e) = result {
+                diagnostics.push(e);
+            }
+        }
+        tofu.link(diagnostics)
+    }
 }
 
 impl Tofu {
-    pub fn with_string_template(template: &str) -> Result<Tofu, CompileError> {
-        let file = parser::parse(template)?;
-        let mut tofu = Tofu {
-            templates: HashMap::new(),
-        };
-        tofu.add_file(file);
-        Ok(tofu)
+    pub fn builder() -> TofuBuilder {
+        TofuBuilder::new()
+    }
+
+    pub fn with_string_template(template: &str) -> Result<Tofu, CompileErrors> {
+        Tofu::builder().add_string_template(template).build()
     }
 
+    fn empty() -> Tofu {
+        Tofu {
+            templates: Default::default(),
+            names: Default::default(),
+            translations: Default::default(),
+            warnings: Default::default(),
+        }
+    }
+
+    /// Compiles files that have already been parsed.
+    pub(crate) fn with_parsed_files(files: Vec<SoyFile>) -> Result<Tofu, CompileErrors> {
+        let mut diagnostics = Diagnostics::default();
+        let mut tofu = Tofu::empty();
+        let mut linker = Linker::default();
+        for file in files {
+            linker.add_file(&file);
+            tofu.add_file(file, &HashMap::new(), &mut diagnostics);
+        }
+        linker.check(&mut diagnostics);
+        tofu.link(diagnostics)
+    }
+
+    /// Resolves the calls in the templates, returning them if there were no errors. Calls
+    /// to unknown templates are reported by the `Linker` first, so this only runs once it
+    /// has found none.
+    fn link(mut self, mut diagnostics: Diagnostics) -> Result<Tofu, CompileErrors> {
+        if !diagnostics.has_errors() {
+            let names = &self.names;
+            for linked in Arc::make_mut(&mut self.templates) {
+                let template = &linked.template;
+                linked.callees = template
+                    .calls
+                    .iter()
+                    .filter_map(|target| match names.get(target) {
+                        Some(&index) => Some(index),
+                        None => {
+                            diagnostics.push(CompileError {
+                                kind: CompileErrorKind::UnknownTemplate(target.clone()),
+                                location: Some(Box::new(template.location.clone())),
+                                cause: None,
+                            });
+                            None
+                        }
+                    })
+                    .collect();
+            }
+        }
+        if diagnostics.has_errors() {
+            return Err(CompileErrors { diagnostics });
+        }
+        self.warnings = Arc::new(diagnostics);
+        Ok(self)
+    }
+
+    /// The warnings reported while compiling the templates.
+    pub fn warnings(&self) -> &Diagnostics {
+        &self.warnings
+    }
+
+    /// Creates a renderer for a template, given its fully-qualified name, so that
+    /// parameters and render options can be set.
+    pub fn renderer<'a>(&'a self, template_name: &'a str) -> Renderer<'a> {
+        Renderer {
+            tofu: self,
+            template_name,
+            data: HashMap::new(),
+            injected_data: HashMap::new(),
+            css_renaming_map: None,
+            xid_renaming_map: None,
+            locale: None,
+            bidi_global_dir: None,
+        }
+    }
+
+    /// Renders a template that takes no parameters.
     pub fn render<W: Write>(&self, writer: W, template_name: &str) -> Result<(), RenderError> {
-        let mut writer = writer;
-        self.render_template(&mut writer, self.template(template_name)?)
+        self.renderer(template_name).render(writer)
     }
 
     pub fn render_to_string(&self, template_name: &str) -> Result<String, RenderError> {
+        self.renderer(template_name).render_to_string()
+    }
+
+    fn add_file(
+        &mut self,
+        file: SoyFile,
+        globals: &HashMap<String, SoyValue>,
+        diagnostics: &mut Diagnostics,
+    ) {
+        self.add_templates(compile_file(file, globals), diagnostics);
+    }
+
+    /// Adds compiled templates, given with their fully-qualified names.
+    fn add_templates<I>(&mut self, templates: I, diagnostics: &mut Diagnostics)
+    where
+        I: IntoIterator<Item = (String, Arc<ir::Template>)>,
+    {
+        for (name, template) in templates {
+            match Arc::make_mut(&mut self.names).entry(name) {
+                Entry::Occupied(entry) => diagnostics.push(CompileError {
+                    kind: CompileErrorKind::DuplicateTemplate(
+                        entry.key().clone(),
+                        Box::new(self.templates[*entry.get()].template.location.clone()),
+                    ),
+                    location: Some(Box::new(template.location.clone())),
+                    cause: None,
+                }),
+                Entry::Vacant(entry) => {
+                    entry.insert(self.templates.len());
+                    Arc::make_mut(&mut self.templates).push(LinkedTemplate {
+                        template,
+                        callees: vec![],
+                    });
+                }
+            }
+        }
+    }
+
+    fn template(&self, name: &str) -> Result<&LinkedTemplate, RenderError> {
+        self.names
+            .get(name)
+            .map(|&index| &self.templates[index])
+            .ok_or_else(|| RenderError {
+                kind: RenderErrorKind::TemplateNotFound(name.to_owned()),
+                location: Default::default(),
+            })
+    }
+}
+
+/// Optimizes and lowers the templates in a file, giving them their fully-qualified names.
+fn compile_file(
+    file: SoyFile,
+    globals: &HashMap<String, SoyValue>,
+) -> Vec<(String, Arc<ir::Template>)> {
+    let namespace = file.namespace.name;
+    file.templates
+        .into_iter()
+        .map(|mut template| {
+            optimize::optimize(&mut template, globals);
+            (
+                format!("{}.{}", namespace, template.name),
+                Arc::new(ir::lower(&template)),
+            )
+        })
+        .collect()
+}
+
+/// The entries of the data passed to a called template with `data="$foo"`, which must be
+/// a map or null.
+pub fn call_data(target: &str, data: SoyValue) -> Result<HashMap<String, SoyValue>, RenderError> {
+    match data {
+        SoyValue::Map(entries) => Ok(entries),
+        SoyValue::Null => Ok(HashMap::new()),
+        value => Err(RenderError {
+            kind: RenderErrorKind::TypeError(format!(
+                "the data passed to {} must be a map, not a {}",
+                target,
+                value.type_name()
+            )),
+            location: None,
+        }),
+    }
+}
+
+/// The output of a block, such as a `{param}` of a call, as content of the block's kind.
+pub fn sanitized(kind: ContentKind, output: Vec<u8>) -> Result<SoyValue, RenderError> {
+    let content = String::from_utf8(output)?;
+    // Values printed in the output were escaped for its kind
+    Ok(unsafe { SanitizedContent::from_trusted_unchecked(kind, content) }.into())
+}
+
+/// A template being rendered, with the values of its parameters by slot.
+struct Frame<'r> {
+    template: &'r ir::Template,
+    /// The indexes of the templates it calls, by slot.
+    callees: &'r [usize],
+    params: Vec<Option<&'r SoyValue>>,
+    /// All of the data the template was given, which `data="all"` passes on.
+    data: &'r HashMap<String, SoyValue>,
+    /// The kind of content being rendered, which printed values are escaped for. It is the
+    /// template's kind, except in the params of a call.
+    kind: ContentKind,
+    autoescape: bool,
+}
+
+impl<'r> Frame<'r> {
+    fn new(linked: &'r LinkedTemplate, data: &'r HashMap<String, SoyValue>) -> Self {
+        let template = &*linked.template;
+        Frame {
+            template,
+            callees: &linked.callees,
+            params: template.params.iter().map(|name| data.get(name)).collect(),
+            data,
+            kind: template.kind,
+            autoescape: template.autoescape,
+        }
+    }
+
+    /// The frame for rendering a block of another kind in the same template.
+    fn with_kind(&self, kind: ContentKind) -> Self {
+        Frame {
+            params: self.params.clone(),
+            kind,
+            autoescape: autoescape(kind),
+            ..*self
+        }
+    }
+}
+
+/// Renders a single template. Created by `Tofu::renderer`.
+pub struct Renderer<'a> {
+    tofu: &'a Tofu,
+    template_name: &'a str,
+    data: HashMap<String, SoyValue>,
+    injected_data: HashMap<String, SoyValue>,
+    css_renaming_map: Option<&'a RenamingMap>,
+    xid_renaming_map: Option<&'a RenamingMap>,
+    locale: Option<&'a str>,
+    bidi_global_dir: Option<BidiDir>,
+}
+
+impl<'a> Renderer<'a> {
+    /// Sets a template parameter.
+    pub fn param<V: Into<SoyValue>>(mut self, name: &str, value: V) -> Self {
+        self.data.insert(name.to_owned(), value.into());
+        self
+    }
+
+    /// Sets several template parameters, e.g. from a struct that derives `SoyParams`.
+    pub fn params<P: SoyParams>(mut self, params: P) -> Self {
+        self.data.extend(params.into_params());
+        self
+    }
+
+    /// Sets an injected parameter, available to every template as `$ij.name`.
+    pub fn injected_param<V: Into<SoyValue>>(mut self, name: &str, value: V) -> Self {
+        self.injected_data.insert(name.to_owned(), value.into());
+        self
+    }
+
+    /// Renames the class names produced by `{css}` and `css()`.
+    pub fn css_renaming_map(mut self, map: &'a RenamingMap) -> Self {
+        self.css_renaming_map = Some(map);
+        self
+    }
+
+    /// Renames the ids produced by `{xid}` and `xid()`.
+    pub fn xid_renaming_map(mut self, map: &'a RenamingMap) -> Self {
+        self.xid_renaming_map = Some(map);
+        self
+    }
+
+    /// Renders messages in the given locale, using the translations added to the
+    /// builder. Messages without a translation are rendered as written.
+    pub fn locale(mut self, locale: &'a str) -> Self {
+        self.locale = Some(locale);
+        self
+    }
+
+    /// Sets the overall direction of the page being rendered, which the bidi functions
+    /// and directives work relative to. By default it is the direction of the locale's
+    /// script, or left to right if there is no locale.
+    pub fn bidi_global_dir(mut self, dir: BidiDir) -> Self {
+        self.bidi_global_dir = Some(dir);
+        self
+    }
+
+    /// Renders the template. Private templates can't be rendered directly.
+    pub fn render<W: Write>(&self, writer: W) -> Result<(), RenderError> {
+        let mut writer = writer;
+        let linked = self.tofu.template(self.template_name)?;
+        let template = &*linked.template;
+        if template.visibility == Visibility::Private {
+            return Err(RenderError {
+                kind: RenderErrorKind::PrivateTemplate(self.template_name.to_owned()),
+                location: Default::default(),
+            });
+        }
+        let frame = Frame::new(linked, &self.data);
+        self.render_block(&mut writer, &frame, &template.body)
+    }
+
+    pub fn render_to_string(&self) -> Result<String, RenderError> {
         let mut output = Vec::with_capacity(8 * 1024);
-        self.render(&mut output, template_name)?;
+        self.render(&mut output)?;
         // TODO: is it safe to use from_utf8_unchecked? probably not if we allow byte slices in input data...
         // anything that comes from a String should already be valid utf8 though
         let mut output = String::from_utf8(output)?;
         output.shrink_to_fit();
         Ok(output)
     }
+}
 
-    fn add_file(&mut self, file: SoyFile) {
-        let namespace = file.namespace.name;
-        self.templates.extend(
-            file.templates
-                .into_iter()
-                .map(|t| (format!("{}.{}", namespace, t.name), t)),
-        );
+// Rendering
+impl<'a> Renderer<'a> {
+    pub(super) fn global_dir(&self) -> BidiDir {
+        self.bidi_global_dir
+            .unwrap_or_else(|| self.locale.map_or(BidiDir::Ltr, BidiDir::for_locale))
     }
 
-    fn template(&self, name: &str) -> Result<&Template, RenderError> {
-        self.templates.get(name).ok_or_else(|| RenderError {
-            kind: RenderErrorKind::TemplateNotFound(name.to_owned()),
-            location: Default::default(),
-        })
+    fn render_block<W: Write>(
+        &self,
+        writer: &mut W,
+        frame: &Frame,
+        block: &[Node],
+    ) -> Result<(), RenderError> {
+        for node in block {
+            self.render_node(writer, frame, node)?;
+        }
+        Ok(())
     }
-}
 
-// Rendering
-impl Tofu {
-    fn render_template<W: Write>(
+    fn render_node<W: Write>(
         &self,
         writer: &mut W,
-        template: &Template,
+        frame: &Frame,
+        node: &Node,
     ) -> Result<(), RenderError> {
-        // todo: handle space joining
-        let mut add_space_if_text = false;
-        for node in &template.body {
-            match node {
-                TemplateNode::RawText { value, newline } => {
-                    if add_space_if_text {
-                        writer.write_all(&[b' '])?;
-                    }
-                    writer.write_all(value.as_bytes())?;
-                    add_space_if_text = *newline;
-                }
-                TemplateNode::Statement { command, .. } => {
-                    match command {
-                        Command::Literal(literal) => writer.write_all(literal.as_bytes())?,
-                        Command::If { .. } => {}    // TODO: implement
-                        Command::Msg { .. } => {}   // TODO: implement
-                        Command::Print { .. } => {} // TODO: implement
+        match node {
+            Node::Text(text) => writer.write_all(text.as_bytes())?,
+            Node::If {
+                branches,
+                else_block,
+            } => {
+                for (condition, block) in branches {
+                    if self.evaluate(frame, condition)?.is_truthy() {
+                        return self.render_block(writer, frame, block);
                     }
-                    add_space_if_text = false;
                 }
-                TemplateNode::Special(special) => writer.write_all(special.as_bytes())?,
+                self.render_block(writer, frame, else_block)?;
             }
+            Node::Msg(msg) => self.render_msg(writer, frame, msg)?,
+            Node::Print {
+                expression,
+                directives,
+            } => {
+                let value = self.evaluate(frame, expression)?;
+                let output = self.apply_directives(frame, value, directives)?;
+                writer.write_all(output.as_bytes())?
+            }
+            Node::Css { base, name } => {
+                let name = name.as_ref().map_err(|_| RenderError {
+                    kind: RenderErrorKind::MissingCssBase(frame.template.name.clone()),
+                    location: None,
+                })?;
+                let class = match base {
+                    Some(base) => css_class(&self.evaluate(frame, base)?, &self.rename_css(name))?,
+                    None => self.rename_css(name),
+                };
+                writer.write_all(class.as_bytes())?
+            }
+            Node::Xid(name) => writer.write_all(self.rename_xid(name).as_bytes())?,
+            Node::Call(call) => self.render_call(writer, frame, call)?,
         }
         Ok(())
     }
+
+    /// Renders a called template with the data and params passed to it. Its output is
+    /// written as is, since the template escapes what it prints itself.
+    fn render_call<W: Write>(
+        &self,
+        writer: &mut W,
+        frame: &Frame,
+        call: &ir::Call,
+    ) -> Result<(), RenderError> {
+        let linked = &self.tofu.templates[frame.callees[call.slot]];
+        let template = &*linked.template;
+        let mut data = match &call.data {
+            None => Cow::Owned(HashMap::new()),
+            Some(CallData::All) => Cow::Borrowed(frame.data),
+            Some(CallData::Expression(expression)) => {
+                let target = &frame.template.calls[call.slot];
+                Cow::Owned(call_data(target, self.evaluate(frame, expression)?)?)
+            }
+        };
+        for (name, param) in &call.params {
+            let value = match param {
+                CallParam::Expression(expression) => self.evaluate(frame, expression)?,
+                CallParam::Block(kind, block) => {
+                    let mut output = vec![];
+                    self.render_block(&mut output, &frame.with_kind(*kind), block)?;
+                    sanitized(*kind, output)?
+                }
+            };
+            data.to_mut().insert(name.clone(), value);
+        }
+        let callee = Frame::new(linked, &data);
+        if template.kind == frame.kind {
+            return self.render_block(writer, &callee, &template.body);
+        }
+        // The callee's output is trusted only as its own kind, so a caller of
+        // another kind escapes it like a printed value.
+        let mut output = vec![];
+        self.render_block(&mut output, &callee, &template.body)?;
+        let content = sanitized(template.kind, output)?;
+        let printed = Printed::new(content, frame.kind, frame.autoescape).finish();
+        writer.write_all(printed.as_bytes())?;
+        Ok(())
+    }
+
+    /// Applies print directives to a printed value, escaping it as needed.
+    fn apply_directives(
+        &self,
+        frame: &Frame,
+        value: SoyValue,
+        directives: &[Directive],
+    ) -> Result<String, RenderError> {
+        let mut printed = Printed::new(value, frame.kind, frame.autoescape);
+        for directive in directives {
+            let args = directive
+                .arguments
+                .iter()
+                .map(|arg| self.evaluate(frame, arg))
+                .collect::<Result<Vec<_>, _>>()?;
+            printed.apply(&directive.kind, &args, self.global_dir())?;
+        }
+        Ok(printed.finish())
+    }
 }
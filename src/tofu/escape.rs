@@ -0,0 +1,113 @@
+/// Escapes text for use in HTML, including inside quoted attribute values.
+pub(super) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Percent-encodes everything except unreserved URI characters.
+pub(super) fn escape_uri(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                escaped.push(byte as char)
+            }
+            byte => escaped.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    escaped
+}
+
+/// Escapes text for use in a JavaScript string literal, single or double quoted. Characters
+/// that could end the script it is in (e.g. the `<` of `</script>`) are escaped too.
+pub(super) fn escape_js_string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '/' => escaped.push_str("\\/"),
+            '"' | '\'' | '`' | '&' | '<' | '=' | '>' | '\0'..='\x1f' => {
+                escaped.push_str(&format!("\\x{:02x}", c as u32))
+            }
+            '\u{85}' | '\u{2028}' | '\u{2029}' => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes a value as a JavaScript expression: numbers, booleans and null as they are (with
+/// spaces around, so that they can't join the tokens beside them), and anything else as a
+/// quoted string.
+pub(super) fn escape_js_value(text: &str, literal: bool) -> String {
+    if literal {
+        format!(" {} ", text)
+    } else {
+        format!("'{}'", escape_js_string(text))
+    }
+}
+
+/// Passes through CSS values that can't change the meaning of the CSS around them, such as
+/// `red`, `#fff`, `1.5em` or `!important`, and replaces anything else with `zSoyz`, as
+/// Closure's `|filterCssValue` does.
+pub(super) fn filter_css_value(text: &str) -> String {
+    let value = text.to_ascii_lowercase();
+    let is_word = |word: &str| {
+        let word = word.strip_prefix(['.', '#']).unwrap_or(word);
+        let name = word.trim_start_matches('-');
+        !word.is_empty()
+            && word
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            && !["expression", "binding", "moz-binding"]
+                .iter()
+                .any(|keyword| name.starts_with(keyword))
+    };
+    let is_number = |number: &str| {
+        let number = number.strip_prefix('-').unwrap_or(number);
+        let unit_start = number
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(number.len());
+        let (digits, unit) = number.split_at(unit_start);
+        digits.chars().any(|c| c.is_ascii_digit())
+            && digits.matches('.').count() <= 1
+            && (unit == "%" || (unit.len() <= 2 && unit.chars().all(|c| c.is_ascii_alphabetic())))
+    };
+    if value.is_empty() || value == "!important" || is_word(&value) || is_number(&value) {
+        text.to_owned()
+    } else {
+        "zSoyz".to_owned()
+    }
+}
+
+/// Replaces each line break (`\r\n`, `\r` or `\n`) with `<br>`.
+pub(super) fn change_newline_to_br(text: &str) -> String {
+    text.replace("\r\n", "<br>").replace(['\r', '\n'], "<br>")
+}
+
+/// Truncates text to at most `max_len` characters, including the `...` if added.
+pub(super) fn truncate(text: &str, max_len: usize, add_ellipsis: bool) -> String {
+    if text.chars().count() <= max_len {
+        return text.to_owned();
+    }
+    if add_ellipsis && max_len > 3 {
+        let mut truncated: String = text.chars().take(max_len - 3).collect();
+        truncated.push_str("...");
+        truncated
+    } else {
+        text.chars().take(max_len).collect()
+    }
+}
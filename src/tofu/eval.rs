@@ -0,0 +1,497 @@
+use super::{Frame, Renderer};
+use crate::ast::{BinaryOperator, UnaryOperator};
+use crate::bidi::{self, BidiDir};
+use crate::error::{RenderError, RenderErrorKind};
+use crate::ir::{Expression, Function, Key, Reference};
+use crate::renaming::RenamingMap;
+use crate::value::SoyValue;
+use std::cmp::Ordering;
+
+fn error(kind: RenderErrorKind) -> RenderError {
+    RenderError {
+        kind,
+        location: None,
+    }
+}
+
+fn type_error(message: String) -> RenderError {
+    error(RenderErrorKind::TypeError(message))
+}
+
+// Expression evaluation
+impl<'a> Renderer<'a> {
+    pub(super) fn evaluate(
+        &self,
+        frame: &Frame,
+        expression: &Expression,
+    ) -> Result<SoyValue, RenderError> {
+        Ok(match expression {
+            Expression::Constant(value) => value.clone(),
+            Expression::List(items) => SoyValue::List(
+                items
+                    .iter()
+                    .map(|item| self.evaluate(frame, item))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expression::Map(entries) => SoyValue::Map(
+                entries
+                    .iter()
+                    .map(|(key, value)| Ok((key.clone(), self.evaluate(frame, value)?)))
+                    .collect::<Result<_, RenderError>>()?,
+            ),
+            Expression::Function {
+                function,
+                arguments,
+            } => {
+                let args = arguments
+                    .iter()
+                    .map(|argument| self.evaluate(frame, argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.call_function(*function, args)?
+            }
+            Expression::UnknownFunction(name) => {
+                return Err(error(RenderErrorKind::UnknownFunction(name.clone())))
+            }
+            Expression::Param { slot, references } => {
+                self.evaluate_reference(frame, frame.params[*slot], references)?
+            }
+            Expression::Injected { name, references } => {
+                self.evaluate_reference(frame, self.injected_data.get(name), references)?
+            }
+            Expression::Global(name) => {
+                return Err(error(RenderErrorKind::UnknownGlobal(name.clone())))
+            }
+            Expression::Binary { lhs, op, rhs } => match op {
+                // These only evaluate the right hand side when it's needed
+                BinaryOperator::And => SoyValue::Bool(
+                    self.evaluate(frame, lhs)?.is_truthy()
+                        && self.evaluate(frame, rhs)?.is_truthy(),
+                ),
+                BinaryOperator::Or => SoyValue::Bool(
+                    self.evaluate(frame, lhs)?.is_truthy()
+                        || self.evaluate(frame, rhs)?.is_truthy(),
+                ),
+                BinaryOperator::Elvis => match self.evaluate(frame, lhs)? {
+                    SoyValue::Null => self.evaluate(frame, rhs)?,
+                    value => value,
+                },
+                op => binary_operation(op, self.evaluate(frame, lhs)?, self.evaluate(frame, rhs)?)?,
+            },
+            Expression::Unary { op, rhs } => unary_operation(*op, self.evaluate(frame, rhs)?)?,
+            Expression::Ternary {
+                condition,
+                if_true,
+                if_false,
+            } => {
+                if self.evaluate(frame, condition)?.is_truthy() {
+                    self.evaluate(frame, if_true)?
+                } else {
+                    self.evaluate(frame, if_false)?
+                }
+            }
+        })
+    }
+
+    /// Looks up a chain of references in a value, cloning only the value found.
+    fn evaluate_reference(
+        &self,
+        frame: &Frame,
+        value: Option<&SoyValue>,
+        references: &[Reference],
+    ) -> Result<SoyValue, RenderError> {
+        let mut value = value.unwrap_or(&SoyValue::Null);
+        for reference in references {
+            let computed;
+            let key = match &reference.key {
+                Key::Constant(key) => key,
+                Key::Computed(key) => {
+                    computed = self.evaluate(frame, key)?;
+                    &computed
+                }
+            };
+            value = match lookup(value, key, reference.null_safe)? {
+                Some(value) => value,
+                None => return Ok(SoyValue::Null),
+            };
+        }
+        Ok(value.clone())
+    }
+
+    fn call_function(
+        &self,
+        function: Function,
+        args: Vec<SoyValue>,
+    ) -> Result<SoyValue, RenderError> {
+        call_function(function, args, &self.options())
+    }
+
+    fn options(&self) -> RenderOptions<'a> {
+        RenderOptions {
+            css_renaming_map: self.css_renaming_map,
+            xid_renaming_map: self.xid_renaming_map,
+            global_dir: self.global_dir(),
+        }
+    }
+
+    pub(super) fn rename_css(&self, name: &str) -> String {
+        self.options().rename_css(name)
+    }
+
+    pub(super) fn rename_xid(&self, name: &str) -> String {
+        self.options().rename_xid(name)
+    }
+}
+
+/// The render options that some functions depend on.
+#[derive(Default)]
+pub(crate) struct RenderOptions<'a> {
+    pub css_renaming_map: Option<&'a RenamingMap>,
+    pub xid_renaming_map: Option<&'a RenamingMap>,
+    pub global_dir: BidiDir,
+}
+
+impl<'a> RenderOptions<'a> {
+    fn rename_css(&self, name: &str) -> String {
+        self.css_renaming_map
+            .map_or_else(|| name.to_owned(), |map| map.rename(name))
+    }
+
+    fn rename_xid(&self, name: &str) -> String {
+        self.xid_renaming_map
+            .map_or_else(|| name.to_owned(), |map| map.rename(name))
+    }
+}
+
+/// The class name for `{css $base, name}` or `css($base, name)`. The base must be a class
+/// name itself, so that a value from the user can't be used to inject markup.
+pub fn css_class(base: &SoyValue, name: &str) -> Result<String, RenderError> {
+    let base = base.to_string();
+    let valid = !base.is_empty()
+        && base
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(type_error(format!(
+            "css base {:?} is not a valid class name",
+            base
+        )));
+    }
+    Ok(format!("{}-{}", base, name))
+}
+
+pub(crate) fn call_function(
+    function: Function,
+    args: Vec<SoyValue>,
+    options: &RenderOptions,
+) -> Result<SoyValue, RenderError> {
+    let name = function.name();
+    Ok(match function {
+        Function::Css => match args.as_slice() {
+            [SoyValue::String(name)] => SoyValue::String(options.rename_css(name)),
+            [base, SoyValue::String(name)] => {
+                SoyValue::String(css_class(base, &options.rename_css(name))?)
+            }
+            _ => return Err(type_error("css() expects a class name".to_owned())),
+        },
+        Function::Xid => match args.as_slice() {
+            [SoyValue::String(name)] => SoyValue::String(options.rename_xid(name)),
+            _ => return Err(type_error("xid() expects an id".to_owned())),
+        },
+        Function::BidiGlobalDir => {
+            arity(function, &args, 0)?;
+            SoyValue::Int(options.global_dir.value())
+        }
+        Function::BidiStartEdge => {
+            arity(function, &args, 0)?;
+            options.global_dir.start_edge().into()
+        }
+        Function::BidiEndEdge => {
+            arity(function, &args, 0)?;
+            options.global_dir.end_edge().into()
+        }
+        Function::BidiTextDir | Function::BidiDirAttr | Function::BidiMarkAfter => {
+            let (text, is_html) = match args.as_slice() {
+                [text] => (text.to_string(), false),
+                [text, is_html] => (text.to_string(), is_html.is_truthy()),
+                _ => {
+                    return Err(type_error(format!(
+                        "{}() takes 1 or 2 arguments, not {}",
+                        name,
+                        args.len()
+                    )))
+                }
+            };
+            let global = options.global_dir;
+            match function {
+                Function::BidiTextDir => {
+                    SoyValue::Int(bidi::estimate_dir(&text, is_html).map_or(0, BidiDir::value))
+                }
+                Function::BidiDirAttr => bidi::dir_attr(global, &text, is_html).into(),
+                _ => {
+                    let dir = bidi::estimate_dir(&text, is_html);
+                    bidi::mark_after(global, dir, &text, is_html).into()
+                }
+            }
+        }
+        function => return call_pure_function(function, args),
+    })
+}
+
+/// Looks up a key in a list or map, giving `None` if the lookup is null safe and the
+/// value is null.
+pub fn lookup<'v>(
+    value: &'v SoyValue,
+    key: &SoyValue,
+    null_safe: bool,
+) -> Result<Option<&'v SoyValue>, RenderError> {
+    Ok(Some(match (value, key) {
+        (SoyValue::Null, _) if null_safe => return Ok(None),
+        (SoyValue::List(items), SoyValue::Int(i)) if *i >= 0 => {
+            items.get(*i as usize).unwrap_or(&SoyValue::Null)
+        }
+        (SoyValue::List(_), SoyValue::Int(_)) => &SoyValue::Null,
+        (SoyValue::Map(entries), SoyValue::String(key)) => {
+            entries.get(key).unwrap_or(&SoyValue::Null)
+        }
+        (value, key) => {
+            return Err(type_error(format!(
+                "can't look up a {} key in a {}",
+                key.type_name(),
+                value.type_name()
+            )))
+        }
+    }))
+}
+
+/// Calls a function whose result depends only on its arguments, so that it can also be
+/// called at compile time.
+pub(crate) fn call_pure_function(
+    function: Function,
+    args: Vec<SoyValue>,
+) -> Result<SoyValue, RenderError> {
+    let name = function.name();
+    let number = |value: &SoyValue| {
+        value.as_number().ok_or_else(|| {
+            type_error(format!(
+                "{}() expects a number, not a {}",
+                name,
+                value.type_name()
+            ))
+        })
+    };
+    Ok(match function {
+        Function::IsNonnull => {
+            arity(function, &args, 1)?;
+            SoyValue::Bool(!args[0].is_null())
+        }
+        Function::IsNull => {
+            arity(function, &args, 1)?;
+            SoyValue::Bool(args[0].is_null())
+        }
+        Function::Length => {
+            arity(function, &args, 1)?;
+            match &args[0] {
+                SoyValue::List(items) => SoyValue::Int(items.len() as i64),
+                value => {
+                    return Err(type_error(format!(
+                        "length() expects a list, not a {}",
+                        value.type_name()
+                    )))
+                }
+            }
+        }
+        Function::Keys => {
+            arity(function, &args, 1)?;
+            match &args[0] {
+                SoyValue::Map(entries) => {
+                    let mut keys: Vec<_> = entries.keys().cloned().collect();
+                    keys.sort();
+                    keys.into()
+                }
+                value => {
+                    return Err(type_error(format!(
+                        "keys() expects a map, not a {}",
+                        value.type_name()
+                    )))
+                }
+            }
+        }
+        Function::Round | Function::Floor | Function::Ceiling => {
+            arity(function, &args, 1)?;
+            match &args[0] {
+                SoyValue::Int(i) => SoyValue::Int(*i),
+                value => {
+                    let n = number(value)?;
+                    let n = match function {
+                        Function::Round => n.round(),
+                        Function::Floor => n.floor(),
+                        _ => n.ceil(),
+                    };
+                    float_to_value(n)
+                }
+            }
+        }
+        Function::Min | Function::Max => {
+            arity(function, &args, 2)?;
+            let (a, b) = (number(&args[0])?, number(&args[1])?);
+            let pick_first = (a <= b) == (function == Function::Min);
+            args.into_iter()
+                .nth(if pick_first { 0 } else { 1 })
+                .unwrap_or(SoyValue::Null)
+        }
+        _ => {
+            return Err(type_error(format!(
+                "{}() depends on the render options, so can't be called without them",
+                name
+            )))
+        }
+    })
+}
+
+fn arity(function: Function, args: &[SoyValue], n: usize) -> Result<(), RenderError> {
+    if args.len() == n {
+        Ok(())
+    } else {
+        Err(type_error(format!(
+            "{}() takes {} argument(s), not {}",
+            function.name(),
+            n,
+            args.len()
+        )))
+    }
+}
+
+/// Converts an integral float (e.g. the result of `round`) back to an int if it fits.
+fn float_to_value(n: f64) -> SoyValue {
+    if n.fract() == 0.0 && n.abs() < i64::MAX as f64 {
+        SoyValue::Int(n as i64)
+    } else {
+        SoyValue::Float(n)
+    }
+}
+
+pub fn unary_operation(op: UnaryOperator, value: SoyValue) -> Result<SoyValue, RenderError> {
+    Ok(match (op, value) {
+        (UnaryOperator::Not, value) => SoyValue::Bool(!value.is_truthy()),
+        // Like the other integer operations, negating the smallest integer gives a float
+        (UnaryOperator::Minus, SoyValue::Int(i)) => i
+            .checked_neg()
+            .map_or(SoyValue::Float(-(i as f64)), SoyValue::Int),
+        (UnaryOperator::Minus, SoyValue::Float(f)) => SoyValue::Float(-f),
+        (UnaryOperator::Minus, value) => {
+            return Err(type_error(format!("can't negate a {}", value.type_name())))
+        }
+    })
+}
+
+/// Applies an operator to two values. Templates evaluate `and`, `or` and `?:` without
+/// this, since they don't always evaluate their right hand side, but they give the same
+/// results here.
+pub fn binary_operation(
+    op: &BinaryOperator,
+    lhs: SoyValue,
+    rhs: SoyValue,
+) -> Result<SoyValue, RenderError> {
+    use self::SoyValue::*;
+    let invalid = |lhs: &SoyValue, rhs: &SoyValue| {
+        type_error(format!(
+            "can't apply {:?} to a {} and a {}",
+            op,
+            lhs.type_name(),
+            rhs.type_name()
+        ))
+    };
+    Ok(match op {
+        BinaryOperator::Plus => match (lhs, rhs) {
+            (Int(a), Int(b)) => a.checked_add(b).map_or(Float(a as f64 + b as f64), Int),
+            (lhs @ String(_), rhs)
+            | (lhs, rhs @ String(_))
+            | (lhs @ Sanitized(_), rhs)
+            | (lhs, rhs @ Sanitized(_)) => String(format!("{}{}", lhs, rhs)),
+            (lhs, rhs) => match (lhs.as_number(), rhs.as_number()) {
+                (Some(a), Some(b)) => Float(a + b),
+                _ => return Err(invalid(&lhs, &rhs)),
+            },
+        },
+        BinaryOperator::Minus | BinaryOperator::Times | BinaryOperator::Modulo => {
+            match (&lhs, &rhs) {
+                (Int(a), Int(b)) => {
+                    let result = match op {
+                        BinaryOperator::Minus => a.checked_sub(*b),
+                        BinaryOperator::Times => a.checked_mul(*b),
+                        _ => a.checked_rem(*b),
+                    };
+                    match result {
+                        Some(result) => Int(result),
+                        None => float_operation(op, *a as f64, *b as f64),
+                    }
+                }
+                _ => match (lhs.as_number(), rhs.as_number()) {
+                    (Some(a), Some(b)) => float_operation(op, a, b),
+                    _ => return Err(invalid(&lhs, &rhs)),
+                },
+            }
+        }
+        // Division is always floating point
+        BinaryOperator::Divide => match (lhs.as_number(), rhs.as_number()) {
+            (Some(a), Some(b)) => Float(a / b),
+            _ => return Err(invalid(&lhs, &rhs)),
+        },
+        BinaryOperator::Equals => Bool(loose_equals(&lhs, &rhs)),
+        BinaryOperator::NotEquals => Bool(!loose_equals(&lhs, &rhs)),
+        BinaryOperator::Less
+        | BinaryOperator::LessEquals
+        | BinaryOperator::Greater
+        | BinaryOperator::GreaterEquals => {
+            let ordering = match (&lhs, &rhs) {
+                (String(a), String(b)) => Some(a.cmp(b)),
+                _ => match (lhs.as_number(), rhs.as_number()) {
+                    (Some(a), Some(b)) => a.partial_cmp(&b),
+                    _ => return Err(invalid(&lhs, &rhs)),
+                },
+            };
+            // Comparisons with NaN are always false
+            Bool(ordering.is_some_and(|ordering| match op {
+                BinaryOperator::Less => ordering == Ordering::Less,
+                BinaryOperator::LessEquals => ordering != Ordering::Greater,
+                BinaryOperator::Greater => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            }))
+        }
+        BinaryOperator::And => Bool(lhs.is_truthy() && rhs.is_truthy()),
+        BinaryOperator::Or => Bool(lhs.is_truthy() || rhs.is_truthy()),
+        BinaryOperator::Elvis => match lhs {
+            Null => rhs,
+            lhs => lhs,
+        },
+    })
+}
+
+fn float_operation(op: &BinaryOperator, a: f64, b: f64) -> SoyValue {
+    SoyValue::Float(match op {
+        BinaryOperator::Minus => a - b,
+        BinaryOperator::Times => a * b,
+        _ => a % b,
+    })
+}
+
+/// Soy's `==`, which compares numbers by value, strings with numbers by their string
+/// form, and sanitized content with strings by its content.
+fn loose_equals(lhs: &SoyValue, rhs: &SoyValue) -> bool {
+    match (lhs, rhs) {
+        (SoyValue::Sanitized(sanitized), other) | (other, SoyValue::Sanitized(sanitized))
+            if matches!(other, SoyValue::String(_) | SoyValue::Sanitized(_)) =>
+        {
+            sanitized.content() == other.to_string()
+        }
+        (SoyValue::String(s), other) | (other, SoyValue::String(s))
+            if other.as_number().is_some() =>
+        {
+            *s == other.to_string()
+        }
+        _ => match (lhs.as_number(), rhs.as_number()) {
+            (Some(a), Some(b)) => a == b,
+            _ => lhs == rhs,
+        },
+    }
+}
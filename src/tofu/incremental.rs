@@ -0,0 +1,226 @@
+//! Recompiles a set of templates after some of its files change, reusing what was compiled
+//! from the rest.
+//!
+//! Compiled files are cached by their content, and only reused under the same filename,
+//! which their templates' locations include. A file depends on the namespaces it aliases
+//! and the namespaces of the templates it calls, so when a file changes, the files that
+//! depend on its namespace (the one it had, or the one it has now) are checked again too,
+//! and so on. Calls between all of the files are checked after every compile.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::{compile_file, ir, Source};
+use crate::ast::SoyFile;
+use crate::error::{CompileError, CompileErrorKind, Diagnostics};
+use crate::link::CallSite;
+use crate::parser;
+use crate::value::SoyValue;
+
+#[derive(Default)]
+pub(super) struct Cache {
+    /// Compiled files, by their content.
+    files: HashMap<Arc<str>, Arc<CompiledFile>>,
+    /// Each file's content when it was last read.
+    contents: HashMap<PathBuf, Arc<str>>,
+    /// The globals the files were compiled with, which are substituted into them.
+    globals: HashMap<String, SoyValue>,
+}
+
+pub(super) struct CompiledFile {
+    content: Arc<str>,
+    filename: Option<String>,
+    namespace: String,
+    /// The namespaces the file aliases or calls templates in, which it depends on.
+    dependencies: HashSet<String>,
+    /// The templates, by fully-qualified name.
+    pub(super) templates: Vec<(String, Arc<ir::Template>)>,
+    /// The calls made by the templates, which are checked against the whole set.
+    pub(super) calls: Vec<CallSite>,
+}
+
+impl Cache {
+    /// Compiles the sources, reading only the files that have changed (or have never been
+    /// compiled) and their dependents. Returns the compiled files in the order of the
+    /// sources, leaving out those with errors.
+    pub(super) fn compile<P: AsRef<Path>>(
+        &mut self,
+        sources: &[Source],
+        changed_paths: &[P],
+        globals: &HashMap<String, SoyValue>,
+        diagnostics: &mut Diagnostics,
+    ) -> Vec<Arc<CompiledFile>> {
+        if self.globals != *globals {
+            *self = Cache {
+                globals: globals.clone(),
+                ..Default::default()
+            };
+        }
+        let changed: HashSet<_> = changed_paths
+            .iter()
+            .map(|path| canonical(path.as_ref()))
+            .collect();
+        let mut compiled: Vec<_> = sources.iter().map(|source| self.cached(source)).collect();
+        let mut pending: Vec<_> = sources
+            .iter()
+            .enumerate()
+            .filter(|(i, source)| match source {
+                Source::File(path) => compiled[*i].is_none() || changed.contains(&canonical(path)),
+                Source::String(_) => compiled[*i].is_none(),
+            })
+            .map(|(i, _)| (i, false))
+            .rev()
+            .collect();
+        let mut done = HashSet::new();
+        while let Some((i, dependent)) = pending.pop() {
+            if !done.insert(i) {
+                continue;
+            }
+            let old = compiled[i].take();
+            // Dependents haven't changed themselves, so are compiled again even if their
+            // content is cached
+            let new = self.load(&sources[i], dependent, diagnostics);
+            let namespaces: HashSet<_> = old
+                .iter()
+                .chain(&new)
+                .map(|file| file.namespace.clone())
+                .collect();
+            compiled[i] = new;
+            for (j, file) in compiled.iter().enumerate() {
+                let depends = file.as_ref().is_some_and(|file| {
+                    file.dependencies
+                        .iter()
+                        .any(|dependency| namespaces.contains(dependency))
+                });
+                if depends && !done.contains(&j) {
+                    pending.push((j, true));
+                }
+            }
+        }
+
+        // Forget files that are no longer used
+        let used: HashSet<_> = compiled
+            .iter()
+            .flatten()
+            .map(|file| Arc::clone(&file.content))
+            .collect();
+        self.files.retain(|content, _| used.contains(content));
+        let paths: HashSet<_> = sources
+            .iter()
+            .filter_map(|source| match source {
+                Source::File(path) => Some(path),
+                Source::String(_) => None,
+            })
+            .collect();
+        self.contents.retain(|path, _| paths.contains(path));
+        compiled.into_iter().flatten().collect()
+    }
+
+    /// The compiled source, if its content hasn't changed since it was last compiled (as
+    /// far as is known, for files).
+    fn cached(&self, source: &Source) -> Option<Arc<CompiledFile>> {
+        match source {
+            Source::String(content) => self.lookup(content, None),
+            Source::File(path) => {
+                self.lookup(self.contents.get(path)?, Some(&path.to_string_lossy()))
+            }
+        }
+    }
+
+    /// The file compiled from the content, if it was compiled under the same filename.
+    fn lookup(&self, content: &str, filename: Option<&str>) -> Option<Arc<CompiledFile>> {
+        self.files
+            .get(content)
+            .filter(|file| file.filename.as_deref() == filename)
+            .cloned()
+    }
+
+    /// Reads and compiles a source, unless its content is cached and `recheck` is false.
+    /// Files with errors or warnings aren't cached, so that they are reported again.
+    fn load(
+        &mut self,
+        source: &Source,
+        recheck: bool,
+        diagnostics: &mut Diagnostics,
+    ) -> Option<Arc<CompiledFile>> {
+        let (filename, content) = match source {
+            Source::String(content) => (None, Arc::from(content.as_str())),
+            Source::File(path) => {
+                self.contents.remove(path);
+                let filename = path.to_string_lossy().into_owned();
+                match fs::read_to_string(path) {
+                    Ok(content) => (Some(filename), Arc::from(content)),
+                    Err(e) => {
+                        diagnostics.push(CompileError {
+                            kind: CompileErrorKind::Io(filename, e),
+                            location: None,
+                            cause: None,
+                        });
+                        return None;
+                    }
+                }
+            }
+        };
+        let cached = self
+            .lookup(&content, filename.as_deref())
+            .filter(|_| !recheck);
+        let file = match cached {
+            Some(file) => file,
+            None => {
+                let mut file_diagnostics = Diagnostics::default();
+                let file = parser::parse_file(filename.as_deref(), &content, &mut file_diagnostics);
+                let clean = file_diagnostics.is_empty();
+                diagnostics.extend(file_diagnostics);
+                let file = CompiledFile::new(Arc::clone(&content), filename, file?, &self.globals);
+                let file = Arc::new(file);
+                if !clean {
+                    return Some(file);
+                }
+                self.files.insert(Arc::clone(&content), Arc::clone(&file));
+                file
+            }
+        };
+        if let Source::File(path) = source {
+            self.contents.insert(path.clone(), content);
+        }
+        Some(file)
+    }
+}
+
+impl CompiledFile {
+    fn new(
+        content: Arc<str>,
+        filename: Option<String>,
+        file: SoyFile,
+        globals: &HashMap<String, SoyValue>,
+    ) -> Self {
+        let calls = CallSite::in_file(&file);
+        let called = calls.iter().filter_map(|call| {
+            call.target
+                .rsplit_once('.')
+                .map(|(namespace, _)| namespace.to_owned())
+        });
+        CompiledFile {
+            content,
+            filename,
+            namespace: file.namespace.name.clone(),
+            dependencies: file
+                .aliases
+                .iter()
+                .map(|alias| alias.from.clone())
+                .chain(called)
+                .collect(),
+            calls,
+            templates: compile_file(file, globals),
+        }
+    }
+}
+
+/// The path with symlinks and relative components resolved, so that paths given in
+/// different ways can be compared, or the path as given if that fails (e.g. if it has been
+/// deleted).
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
@@ -0,0 +1,297 @@
+//! Simplifies templates before they are lowered: compile-time globals are substituted,
+//! expressions that don't depend on the render are folded into literals, branches that
+//! can't be taken are removed, and adjacent text is merged. Messages are left as they
+//! are, since their placeholders are named after what they contain.
+
+use super::eval::{binary_operation, call_pure_function, unary_operation};
+use crate::ast::{
+    BinaryOperator, Call, CallData, CallParam, CallParamValue, Command, ConditionalBlock,
+    Expression, PrintDirective, Reference, Template, TemplateBlock, TemplateNode,
+};
+use crate::ir::Function;
+use crate::value::SoyValue;
+use std::collections::HashMap;
+
+#[cfg(test)]
+mod tests;
+
+pub(crate) fn optimize(template: &mut Template, globals: &HashMap<String, SoyValue>) {
+    let optimizer = Optimizer { globals };
+    template.body = optimizer.block(std::mem::take(&mut template.body));
+}
+
+struct Optimizer<'g> {
+    globals: &'g HashMap<String, SoyValue>,
+}
+
+impl<'g> Optimizer<'g> {
+    fn block(&self, block: TemplateBlock) -> TemplateBlock {
+        let mut nodes = vec![];
+        for node in block {
+            match node {
+                TemplateNode::Statement(Command::If {
+                    if_block,
+                    else_ifs,
+                    else_block,
+                }) => {
+                    for node in self.conditional(if_block, else_ifs, else_block) {
+                        push_node(&mut nodes, node);
+                    }
+                }
+                TemplateNode::Statement(command) => {
+                    push_node(&mut nodes, TemplateNode::Statement(self.command(command)))
+                }
+                node => push_node(&mut nodes, node),
+            }
+        }
+        nodes
+    }
+
+    /// Removes the branches of an `{if}` whose conditions are constant, which leaves just
+    /// the nodes of one branch if the branch taken is known.
+    fn conditional(
+        &self,
+        if_block: ConditionalBlock,
+        else_ifs: Vec<ConditionalBlock>,
+        else_block: Option<TemplateBlock>,
+    ) -> TemplateBlock {
+        let mut branches = vec![];
+        let mut else_block = else_block;
+        for conditional in std::iter::once(if_block).chain(else_ifs) {
+            let expression = self.expression(conditional.expression);
+            match constant(&expression).map(|value| value.is_truthy()) {
+                Some(false) => {}
+                Some(true) => {
+                    else_block = Some(conditional.block);
+                    break;
+                }
+                None => branches.push(ConditionalBlock {
+                    expression,
+                    block: self.block(conditional.block),
+                }),
+            }
+        }
+        let else_block = else_block.map(|block| self.block(block));
+        let mut branches = branches.into_iter();
+        match branches.next() {
+            Some(if_block) => vec![TemplateNode::Statement(Command::If {
+                if_block,
+                else_ifs: branches.collect(),
+                else_block,
+            })],
+            None => else_block.unwrap_or_default(),
+        }
+    }
+
+    fn command(&self, command: Command) -> Command {
+        match command {
+            Command::Print {
+                expression,
+                directives,
+                phname,
+            } => Command::Print {
+                expression: self.expression(expression),
+                directives: directives
+                    .into_iter()
+                    .map(|directive| PrintDirective {
+                        name: directive.name,
+                        arguments: self.expressions(directive.arguments),
+                    })
+                    .collect(),
+                phname,
+            },
+            Command::Css {
+                base: Some(base),
+                name,
+            } => Command::Css {
+                base: Some(self.expression(base)),
+                name,
+            },
+            Command::Call(call) => Command::Call(Call {
+                data: call.data.map(|data| match data {
+                    CallData::Expression(expression) => {
+                        CallData::Expression(self.expression(expression))
+                    }
+                    data => data,
+                }),
+                params: call
+                    .params
+                    .into_iter()
+                    .map(|param| CallParam {
+                        name: param.name,
+                        value: match param.value {
+                            CallParamValue::Expression(expression) => {
+                                CallParamValue::Expression(self.expression(expression))
+                            }
+                            CallParamValue::Block { kind, block } => CallParamValue::Block {
+                                kind,
+                                block: self.block(block),
+                            },
+                        },
+                    })
+                    .collect(),
+                ..call
+            }),
+            command => command,
+        }
+    }
+
+    fn expressions(&self, expressions: Vec<Expression>) -> Vec<Expression> {
+        expressions
+            .into_iter()
+            .map(|expression| self.expression(expression))
+            .collect()
+    }
+
+    fn expression(&self, expression: Expression) -> Expression {
+        match expression {
+            Expression::GlobalReference(name) => match self.globals.get(&name) {
+                Some(value) => literal(value.clone()).unwrap_or(Expression::GlobalReference(name)),
+                None => Expression::GlobalReference(name),
+            },
+            Expression::List(items) => Expression::List(self.expressions(items)),
+            Expression::Map(entries) => Expression::Map(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, self.expression(value)))
+                    .collect(),
+            ),
+            Expression::Function { name, parameters } => {
+                let parameters = self.expressions(parameters);
+                let value = Function::from_name(&name)
+                    .filter(|function| function.is_pure())
+                    .and_then(|function| {
+                        let args = parameters.iter().map(constant).collect::<Option<_>>()?;
+                        call_pure_function(function, args).ok()
+                    });
+                value
+                    .and_then(literal)
+                    .unwrap_or(Expression::Function { name, parameters })
+            }
+            Expression::DataReference {
+                referent,
+                references,
+            } => Expression::DataReference {
+                referent,
+                references: references
+                    .into_iter()
+                    .map(|reference| match reference {
+                        Reference::Bracketed(key) => Reference::Bracketed(self.expression(key)),
+                        Reference::QuestionBracketed(key) => {
+                            Reference::QuestionBracketed(self.expression(key))
+                        }
+                        reference => reference,
+                    })
+                    .collect(),
+            },
+            Expression::BinaryOperation { lhs, op, rhs } => {
+                let (lhs, rhs) = (self.expression(*lhs), self.expression(*rhs));
+                let value = match (constant(&lhs), op) {
+                    (Some(SoyValue::Null), BinaryOperator::Elvis) => return rhs,
+                    (Some(value), BinaryOperator::Elvis) => Some(value),
+                    (Some(value), BinaryOperator::And) if !value.is_truthy() => {
+                        Some(SoyValue::Bool(false))
+                    }
+                    (Some(value), BinaryOperator::Or) if value.is_truthy() => {
+                        Some(SoyValue::Bool(true))
+                    }
+                    (Some(_), BinaryOperator::And) | (Some(_), BinaryOperator::Or) => {
+                        constant(&rhs).map(|value| SoyValue::Bool(value.is_truthy()))
+                    }
+                    (Some(value), op) => constant(&rhs)
+                        .and_then(|rhs_value| binary_operation(&op, value, rhs_value).ok()),
+                    (None, _) => None,
+                };
+                value
+                    .and_then(literal)
+                    .unwrap_or_else(|| Expression::BinaryOperation {
+                        lhs: Box::new(lhs),
+                        op,
+                        rhs: Box::new(rhs),
+                    })
+            }
+            Expression::UnaryOperation { op, rhs } => {
+                let rhs = self.expression(*rhs);
+                constant(&rhs)
+                    .and_then(|value| unary_operation(op, value).ok())
+                    .and_then(literal)
+                    .unwrap_or_else(|| Expression::UnaryOperation {
+                        op,
+                        rhs: Box::new(rhs),
+                    })
+            }
+            Expression::TernaryOperation {
+                condition,
+                if_true,
+                if_false,
+            } => {
+                let condition = self.expression(*condition);
+                match constant(&condition) {
+                    Some(value) if value.is_truthy() => self.expression(*if_true),
+                    Some(_) => self.expression(*if_false),
+                    None => Expression::TernaryOperation {
+                        condition: Box::new(condition),
+                        if_true: Box::new(self.expression(*if_true)),
+                        if_false: Box::new(self.expression(*if_false)),
+                    },
+                }
+            }
+            expression => expression,
+        }
+    }
+}
+
+/// Adds a node to the end of a block, merging it into the last node if both are text.
+/// Special characters are the same as raw text once they're parsed.
+fn push_node(nodes: &mut TemplateBlock, node: TemplateNode) {
+    let text = match node {
+        TemplateNode::RawText(text) | TemplateNode::Special(text) => text,
+        node => return nodes.push(node),
+    };
+    match nodes.last_mut() {
+        Some(TemplateNode::RawText(last)) => last.push_str(&text),
+        _ => nodes.push(TemplateNode::RawText(text)),
+    }
+}
+
+/// The value of a literal expression.
+fn constant(expression: &Expression) -> Option<SoyValue> {
+    Some(match expression {
+        Expression::Null => SoyValue::Null,
+        Expression::Boolean(b) => SoyValue::Bool(*b),
+        Expression::Integer(i) => SoyValue::Int(*i),
+        Expression::Float(f) => SoyValue::Float(*f),
+        Expression::String(s) => SoyValue::String(s.clone()),
+        Expression::List(items) => {
+            SoyValue::List(items.iter().map(constant).collect::<Option<_>>()?)
+        }
+        Expression::Map(entries) => SoyValue::Map(
+            entries
+                .iter()
+                .map(|(key, value)| Some((key.clone(), constant(value)?)))
+                .collect::<Option<_>>()?,
+        ),
+        _ => return None,
+    })
+}
+
+/// A literal expression for a value, if it can be written as one.
+fn literal(value: SoyValue) -> Option<Expression> {
+    Some(match value {
+        SoyValue::Null => Expression::Null,
+        SoyValue::Bool(b) => Expression::Boolean(b),
+        SoyValue::Int(i) => Expression::Integer(i),
+        SoyValue::Float(f) => Expression::Float(f),
+        SoyValue::String(s) => Expression::String(s),
+        SoyValue::List(items) => {
+            Expression::List(items.into_iter().map(literal).collect::<Option<_>>()?)
+        }
+        SoyValue::Map(entries) => Expression::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| Some((key, literal(value)?)))
+                .collect::<Option<_>>()?,
+        ),
+        SoyValue::Sanitized(_) => return None,
+    })
+}
@@ -0,0 +1,97 @@
+use super::*;
+use crate::parser;
+
+/// The body of a template after optimizing it.
+fn optimized(body: &str, globals: &[(&str, SoyValue)]) -> TemplateBlock {
+    let globals = globals
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.clone()))
+        .collect();
+    let mut file = parser::parse(&template(body)).unwrap();
+    let mut template = file.templates.remove(0);
+    optimize(&mut template, &globals);
+    template.body
+}
+
+fn parsed(body: &str) -> TemplateBlock {
+    parser::parse(&template(body))
+        .unwrap()
+        .templates
+        .remove(0)
+        .body
+}
+
+fn template(body: &str) -> String {
+    format!(
+        "{{namespace foo}}\n/**\n * @param x\n */\n{{template .bar}}{}{{/template}}",
+        body
+    )
+}
+
+#[test]
+fn test_fold_expressions() {
+    assert_eq!(optimized("{1 + 2 * 3}", &[]), parsed("{7}"));
+    assert_eq!(
+        optimized("{-(4 / 8)} {not true}", &[]),
+        parsed("{-0.5} {false}")
+    );
+    assert_eq!(optimized("{'a' + 1 + [1, 2]}", &[]), parsed("{'a1[1, 2]'}"));
+    assert_eq!(
+        optimized("{max(2, round(2.6))} {length([1, 2])} {isNull(null)}", &[]),
+        parsed("{3} {2} {true}")
+    );
+    assert_eq!(
+        optimized("{true ? $x : 1} {null ?: $x} {false and $x} {1 or $x}", &[]),
+        parsed("{$x} {$x} {false} {true}")
+    );
+    assert_eq!(
+        optimized("{$x[1 + 1]} {$x + (2 - 1)}", &[]),
+        parsed("{$x[2]} {$x + 1}")
+    );
+    assert_eq!(
+        optimized("{-(-9223372036854775807 - 1)}", &[]),
+        parsed("{9223372036854775808.0}")
+    );
+    // Functions that depend on the render, and errors, are left for the render
+    assert_eq!(
+        optimized("{css('a')} {bidiGlobalDir()} {length(1)} {-'a'}", &[]),
+        parsed("{css('a')} {bidiGlobalDir()} {length(1)} {-'a'}")
+    );
+}
+
+#[test]
+fn test_globals() {
+    let globals = [("DEBUG", false.into()), ("NAME", "app".into())];
+    assert_eq!(
+        optimized("{NAME + '.js'} {OTHER}", &globals),
+        parsed("{'app.js'} {OTHER}")
+    );
+    assert_eq!(
+        optimized("{if DEBUG}debug{elseif $x}x{else}release{/if}", &globals),
+        parsed("{if $x}x{else}release{/if}")
+    );
+}
+
+#[test]
+fn test_remove_branches() {
+    assert_eq!(optimized("a{if false}b{/if}c", &[]), parsed("ac"));
+    assert_eq!(optimized("a{if 1 < 2}b{else}c{/if}d", &[]), parsed("abd"));
+    assert_eq!(
+        optimized("{if $x}a{elseif true}b{elseif $x}c{else}d{/if}", &[]),
+        parsed("{if $x}a{else}b{/if}")
+    );
+    assert_eq!(
+        optimized("{if false}a{elseif $x}{if true}b{/if}{/if}", &[]),
+        parsed("{if $x}b{/if}")
+    );
+    assert_eq!(
+        optimized("a{sp}{if false}{/if}b{nil}c", &[]),
+        vec![TemplateNode::RawText("a bc".to_owned())]
+    );
+}
+
+#[test]
+fn test_messages_are_unchanged() {
+    let body = "{msg desc=\"\"}{if true}a{/if}{1 + 1}{/msg}";
+    assert_eq!(optimized(body, &[]), parsed(body));
+}
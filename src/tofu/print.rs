@@ -0,0 +1,121 @@
+use super::escape;
+use crate::ast::ContentKind;
+use crate::bidi::{self, BidiDir};
+use crate::error::{RenderError, RenderErrorKind};
+use crate::ir::DirectiveKind;
+use crate::value::SoyValue;
+
+/// A printed value as print directives are applied to it. It is escaped for the kind of
+/// template it is printed in (unless told not to, or it is sanitized content trusted there)
+/// before the first directive that produces HTML, or at the end.
+pub struct Printed {
+    text: String,
+    escaped: bool,
+    sanitized_kind: Option<ContentKind>,
+    /// The kind of template the value is printed in.
+    kind: ContentKind,
+    /// Whether the value is a number, boolean or null, which JavaScript prints unquoted.
+    js_literal: bool,
+}
+
+impl Printed {
+    /// Starts printing a value in a template of the given kind, which escapes printed
+    /// values for its kind if `autoescape` is set.
+    pub fn new(value: SoyValue, kind: ContentKind, autoescape: bool) -> Printed {
+        let sanitized_kind = match &value {
+            SoyValue::Sanitized(sanitized) => Some(sanitized.kind()),
+            _ => None,
+        };
+        let js_literal = matches!(
+            value,
+            SoyValue::Null | SoyValue::Bool(_) | SoyValue::Int(_) | SoyValue::Float(_)
+        );
+        Printed {
+            text: value.to_string(),
+            escaped: !autoescape || sanitized_kind.is_some_and(|s| is_trusted_in(s, kind)),
+            sanitized_kind,
+            kind,
+            js_literal,
+        }
+    }
+
+    pub fn apply(
+        &mut self,
+        directive: &DirectiveKind,
+        args: &[SoyValue],
+        global_dir: BidiDir,
+    ) -> Result<(), RenderError> {
+        match directive {
+            DirectiveKind::NoAutoescape => self.escaped = true,
+            DirectiveKind::EscapeHtml => {
+                // Sanitized HTML is already safe, as in Closure
+                if self.sanitized_kind != Some(ContentKind::Html) {
+                    self.text = escape::escape_html(&self.text);
+                }
+                self.escaped = true;
+            }
+            DirectiveKind::EscapeUri => {
+                self.text = escape::escape_uri(&self.text);
+                self.escaped = true;
+            }
+            DirectiveKind::ChangeNewlineToBr => {
+                self.escape();
+                self.text = escape::change_newline_to_br(&self.text);
+            }
+            DirectiveKind::BidiSpanWrap => {
+                self.escape();
+                self.text = bidi::span_wrap(global_dir, &self.text);
+            }
+            DirectiveKind::BidiUnicodeWrap => {
+                self.text = bidi::unicode_wrap(global_dir, &self.text, self.escaped)
+            }
+            DirectiveKind::Truncate => match args {
+                [SoyValue::Int(max_len)] => {
+                    self.text = escape::truncate(&self.text, *max_len as usize, true)
+                }
+                [SoyValue::Int(max_len), ellipsis] => {
+                    self.text =
+                        escape::truncate(&self.text, *max_len as usize, ellipsis.is_truthy())
+                }
+                _ => {
+                    return Err(RenderError {
+                        kind: RenderErrorKind::TypeError("|truncate expects a length".to_owned()),
+                        location: None,
+                    })
+                }
+            },
+            DirectiveKind::Unknown(name) => {
+                return Err(RenderError {
+                    kind: RenderErrorKind::UnknownDirective(name.clone()),
+                    location: None,
+                })
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> String {
+        self.escape();
+        self.text
+    }
+
+    fn escape(&mut self) {
+        if self.escaped {
+            return;
+        }
+        self.text = match self.kind {
+            ContentKind::Html | ContentKind::Attributes => escape::escape_html(&self.text),
+            ContentKind::Uri | ContentKind::TrustedResourceUri => escape::escape_uri(&self.text),
+            ContentKind::Js => escape::escape_js_value(&self.text, self.js_literal),
+            ContentKind::Css => escape::filter_css_value(&self.text),
+            ContentKind::Text => return,
+        };
+        self.escaped = true;
+    }
+}
+
+/// Whether sanitized content of a kind is printed as is in a template of a kind. Trusted
+/// resource URIs are also URIs, but not the other way around.
+fn is_trusted_in(sanitized: ContentKind, kind: ContentKind) -> bool {
+    sanitized == kind || (sanitized == ContentKind::TrustedResourceUri && kind == ContentKind::Uri)
+}
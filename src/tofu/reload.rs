@@ -0,0 +1,178 @@
+//! Templates that are compiled again when their files change, for use in development.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, TryLockError};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::{Source, Tofu, TofuBuilder};
+use crate::build::{find_soy_files, io_error};
+use crate::error::{CompileErrors, Diagnostics};
+
+/// A set of templates that is swapped for a new one when the files it was compiled from
+/// change, so that changes show up without restarting.
+///
+/// The files are checked when a snapshot is taken (at most once every `check_interval`).
+/// If the new files don't compile, the old templates are kept and the errors are
+/// available from `error` until the files are fixed. Snapshots are never changed, so
+/// renders that are under way finish with the templates they started with.
+///
+/// It can be shared between threads, e.g. in an `Arc`, with each render taking a
+/// snapshot.
+pub struct ReloadingTofu {
+    current: RwLock<Tofu>,
+    watcher: Mutex<Watcher>,
+}
+
+struct Watcher {
+    builder: TofuBuilder,
+    /// The number of sources added to the builder, which come before the files found in
+    /// `dirs`.
+    sources: usize,
+    dirs: Vec<PathBuf>,
+    /// The modification time and length of each file, when it was last checked.
+    modified: HashMap<PathBuf, Option<(SystemTime, u64)>>,
+    check_interval: Duration,
+    last_checked: Instant,
+    error: Option<Arc<CompileErrors>>,
+}
+
+impl ReloadingTofu {
+    /// Compiles the templates added to the builder and the `.soy` files in the directories
+    /// (and their subdirectories), watching all of the files for changes. Files added to
+    /// the directories later are picked up too.
+    pub fn new<P: AsRef<Path>>(
+        builder: TofuBuilder,
+        dirs: &[P],
+    ) -> Result<ReloadingTofu, CompileErrors> {
+        let mut watcher = Watcher {
+            sources: builder.sources.len(),
+            builder,
+            dirs: dirs.iter().map(|dir| dir.as_ref().to_owned()).collect(),
+            modified: HashMap::new(),
+            check_interval: Duration::from_millis(500),
+            last_checked: Instant::now(),
+            error: None,
+        };
+        let changed = watcher.scan()?.unwrap_or_default();
+        let tofu = watcher.builder.recompile(&changed)?;
+        Ok(ReloadingTofu {
+            current: RwLock::new(tofu),
+            watcher: Mutex::new(watcher),
+        })
+    }
+
+    /// Sets how often the files are checked for changes. By default it is every half a
+    /// second.
+    pub fn check_interval(self, interval: Duration) -> Self {
+        self.lock().check_interval = interval;
+        self
+    }
+
+    /// The current templates, after checking for changes if it's time to. The snapshot
+    /// shares the templates rather than copying them, and can be sent to other threads.
+    pub fn snapshot(&self) -> Tofu {
+        // If the files are being compiled already, the current templates are used
+        let due = match self.watcher.try_lock() {
+            Ok(watcher) => watcher.last_checked.elapsed() >= watcher.check_interval,
+            Err(TryLockError::Poisoned(e)) => {
+                let watcher = e.into_inner();
+                watcher.last_checked.elapsed() >= watcher.check_interval
+            }
+            Err(TryLockError::WouldBlock) => false,
+        };
+        if due {
+            // Any errors are kept for `error`
+            let _ = self.reload();
+        }
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Checks the files for changes now, compiling them again if there are any. Returns
+    /// whether new templates were swapped in, or the errors if the files don't compile
+    /// (in which case the old templates are kept).
+    pub fn reload(&self) -> Result<bool, Arc<CompileErrors>> {
+        let mut watcher = self.lock();
+        watcher.last_checked = Instant::now();
+        let result = match watcher.scan() {
+            Ok(None) => {
+                return match &watcher.error {
+                    Some(errors) => Err(Arc::clone(errors)),
+                    None => Ok(false),
+                };
+            }
+            Ok(Some(changed)) => watcher.builder.recompile(&changed),
+            Err(errors) => Err(errors),
+        };
+        match result {
+            Ok(tofu) => {
+                *self.current.write().unwrap_or_else(PoisonError::into_inner) = tofu;
+                watcher.error = None;
+                Ok(true)
+            }
+            Err(errors) => {
+                let errors = Arc::new(errors);
+                watcher.error = Some(Arc::clone(&errors));
+                Err(errors)
+            }
+        }
+    }
+
+    /// The errors from the last time the files were compiled, if they didn't compile.
+    pub fn error(&self) -> Option<Arc<CompileErrors>> {
+        self.lock().error.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Watcher> {
+        self.watcher.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Watcher {
+    /// Finds the files in the directories, updating the builder's sources, and returns the
+    /// files that are new or have been modified, or `None` if no files have changed (or
+    /// been removed).
+    fn scan(&mut self) -> Result<Option<Vec<PathBuf>>, CompileErrors> {
+        let mut found = vec![];
+        for dir in &self.dirs {
+            if let Err(e) = find_soy_files(dir, &mut found) {
+                let mut diagnostics = Diagnostics::default();
+                diagnostics.push(io_error(dir, e));
+                return Err(CompileErrors { diagnostics });
+            }
+        }
+        found.sort();
+        self.builder.sources.truncate(self.sources);
+        self.builder
+            .sources
+            .extend(found.into_iter().map(Source::File));
+
+        let mut modified = HashMap::new();
+        let mut changed = vec![];
+        for source in &self.builder.sources {
+            if let Source::File(path) = source {
+                let metadata = fs::metadata(path)
+                    .and_then(|metadata| Ok((metadata.modified()?, metadata.len())))
+                    .ok();
+                if self.modified.get(path) != Some(&metadata) {
+                    changed.push(path.clone());
+                }
+                modified.insert(path.clone(), metadata);
+            }
+        }
+        let removed = self
+            .modified
+            .keys()
+            .any(|path| !modified.contains_key(path));
+        self.modified = modified;
+        Ok(if changed.is_empty() && !removed {
+            None
+        } else {
+            Some(changed)
+        })
+    }
+}
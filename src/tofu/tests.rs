@@ -0,0 +1,1120 @@
+use super::*;
+use crate::ast::{BinaryOperator, Command, TemplateNode};
+use crate::renaming::RenamingStyle;
+use crate::TranslationBundle;
+
+#[test]
+fn test_builder_reports_all_errors() {
+    let errors = Tofu::builder()
+        .add_string_template("{namespace foo}\n/** */\n{template .bar}{if}{/if}{/template}")
+        .add_string_template("{namespace foo}\n/** */\n{template .baz}{$qux}{/template}")
+        .add_string_template("{namespace foo}\n/** */\n{template .baz}baz{/template}")
+        .add_file("does/not/exist.soy")
+        .build()
+        .err()
+        .expect("should fail");
+    let codes: Vec<_> = errors.diagnostics.errors().map(|e| e.kind.code()).collect();
+    assert_eq!(codes, vec!["E0001", "E0002", "E0003", "E0004"]);
+    assert!(format!("{}", errors).ends_with("compilation failed with 4 errors and 0 warnings"));
+}
+
+#[test]
+fn test_duplicate_templates_report_both_definitions() {
+    let errors = Tofu::builder()
+        .add_string_template("{namespace foo}\n/** */\n{template .bar}{/template}")
+        .add_string_template("{namespace foo}\n\n/** */\n{template .bar}{/template}")
+        .build()
+        .err()
+        .expect("should fail");
+    let error = errors.diagnostics.errors().next().unwrap();
+    assert_eq!(
+        error.to_string(),
+        "error[E0003]: Template defined more than once: foo.bar\n \
+         --> <input>:4:1 (in foo.bar)\n  |\n4 | {template .bar}{/template}\n  | ^^^^^^^^^^^^^^\n\
+         note: first defined here\n \
+         --> <input>:3:1 (in foo.bar)\n  |\n3 | {template .bar}{/template}\n  | ^^^^^^^^^^^^^^"
+    );
+}
+
+#[test]
+fn test_builder_keeps_warnings() {
+    let tofu = Tofu::builder()
+        .add_string_template(
+            "{namespace foo}\n/**\n * @param bar\n */\n{template .baz}baz{/template}",
+        )
+        .build()
+        .unwrap_or_else(|e| panic!("should compile: {}", e));
+    assert!(!tofu.warnings().has_errors());
+    assert_eq!(tofu.warnings().len(), 1);
+    assert_eq!(tofu.render_to_string("foo.baz").unwrap(), "baz");
+}
+
+#[test]
+fn test_private_templates_cant_be_rendered() {
+    let tofu = Tofu::with_string_template(
+        "{namespace foo}\n/** */\n{template .bar visibility=\"private\"}bar{/template}",
+    )
+    .unwrap_or_else(|e| panic!("should compile: {}", e));
+    match tofu.render_to_string("foo.bar") {
+        Err(RenderError {
+            kind: RenderErrorKind::PrivateTemplate(name),
+            ..
+        }) => assert_eq!(name, "foo.bar"),
+        other => panic!("should be private: {:?}", other),
+    }
+}
+
+#[test]
+fn test_private_templates_can_only_be_called_from_their_file() {
+    let private = "{namespace foo}\n/** */\n{template .bar visibility=\"private\"}bar{/template}\n\
+                   /** */\n{template .baz}{call .bar /}{/template}";
+    let tofu =
+        Tofu::with_string_template(private).unwrap_or_else(|e| panic!("should compile: {}", e));
+    assert_eq!(tofu.render_to_string("foo.baz").unwrap(), "bar");
+    let errors = Tofu::builder()
+        .add_string_template(private)
+        .add_string_template("{namespace foo}\n/** */\n{template .qux}{call .bar /}{/template}")
+        .build()
+        .err()
+        .expect("should fail");
+    let error = errors.diagnostics.errors().next().unwrap();
+    assert_eq!(error.kind.code(), "E0017");
+    assert_eq!(
+        error.kind.to_string(),
+        "Call to private template foo.bar from another file"
+    );
+}
+
+#[test]
+fn test_fully_qualified_template_names() {
+    let tofu = Tofu::with_string_template(
+        "{namespace foo.bar}\n/** */\n{template foo.bar.baz}baz{/template}\n/** */\n{template .qux}qux{/template}",
+    )
+    .unwrap_or_else(|e| panic!("should compile: {}", e));
+    assert_eq!(tofu.render_to_string("foo.bar.baz").unwrap(), "baz");
+    assert_eq!(tofu.render_to_string("foo.bar.qux").unwrap(), "qux");
+}
+
+fn compile(templates: &str) -> Tofu {
+    Tofu::with_string_template(&format!("{{namespace foo}}\n{}", templates))
+        .unwrap_or_else(|e| panic!("should compile: {}", e))
+}
+
+#[test]
+fn test_render_params() {
+    let tofu = compile(
+        "/**\n * @param name\n * @param? items\n */\n\
+         {template .bar}\n\
+           {if $items and length($items) > 1}\n\
+             Hello {$name}, you have {length($items)} items: {$items[0]} and {$items?[1]}.\n\
+           {elseif isNonnull($items)}\n\
+             Hello {$name ?: 'you'}, you have an item.\n\
+           {else}\n\
+             {$name |noAutoescape}\n\
+           {/if}\n\
+         {/template}",
+    );
+    let render = |renderer: Renderer| {
+        renderer
+            .render_to_string()
+            .unwrap_or_else(|e| panic!("should render: {}", e))
+    };
+    assert_eq!(
+        render(
+            tofu.renderer("foo.bar")
+                .param("name", "<b>")
+                .param("items", vec![1.5, 2.0])
+        ),
+        "Hello &lt;b&gt;, you have 2 items: 1.5 and 2."
+    );
+    assert_eq!(
+        render(
+            tofu.renderer("foo.bar")
+                .param("name", SoyValue::Null)
+                .param("items", vec!["x"])
+        ),
+        "Hello you, you have an item."
+    );
+    assert_eq!(render(tofu.renderer("foo.bar").param("name", "<b>")), "<b>");
+}
+
+#[test]
+fn test_noncontextual_templates_are_escaped() {
+    let tofu = compile(
+        "/**\n * @param x\n */\n\
+         {template .bar autoescape=\"deprecated-noncontextual\"}{$x}{/template}",
+    );
+    assert_eq!(
+        tofu.renderer("foo.bar")
+            .param("x", "<script>alert(1)</script>")
+            .render_to_string()
+            .unwrap(),
+        "&lt;script&gt;alert(1)&lt;/script&gt;"
+    );
+}
+
+#[test]
+fn test_render_expressions() {
+    let tofu = compile(
+        "/**\n * @param a\n * @param b\n */\n\
+         {template .bar kind=\"text\"}\n\
+           {$a + $b} {$a - $b} {$a * $b} {$a / $b} {$a % $b} {-$a} {$a < $b} {$a == '7'}\n\
+           {' ' + $a + 'x'} {max($a, $b)} {round(2.5)} {$a > 5 ? 'big' : 'small'}\n\
+           {not $a} {$a ?: 3} {['k': $b]} {[1, 2]}\n\
+         {/template}",
+    );
+    assert_eq!(
+        tofu.renderer("foo.bar")
+            .param("a", 7)
+            .param("b", 2)
+            .render_to_string()
+            .unwrap(),
+        "9 5 14 3.5 1 -7 false true 7x 7 3 bigfalse 7 {k: 2} [1, 2]"
+    );
+    assert_eq!(
+        tofu.renderer("foo.bar")
+            .param("a", i64::MIN)
+            .param("b", 1)
+            .render_to_string()
+            .unwrap()
+            .split(' ')
+            .nth(5),
+        Some("9223372036854776000")
+    );
+}
+
+#[test]
+fn test_render_errors() {
+    let tofu = compile(
+        "/** */\n{template .global}{foo.bar}{/template}\n\
+         /** */\n{template .types}{-'a'}{/template}\n\
+         /** */\n{template .css}{css '\"><b', foo}{/template}",
+    );
+    let kind = |name| match tofu.render_to_string(name) {
+        Err(RenderError { kind, .. }) => kind,
+        Ok(output) => panic!("{} should fail, but rendered {:?}", name, output),
+    };
+    assert!(matches!(
+        kind("foo.global"),
+        RenderErrorKind::UnknownGlobal(_)
+    ));
+    assert!(matches!(kind("foo.types"), RenderErrorKind::TypeError(_)));
+    assert!(matches!(kind("foo.css"), RenderErrorKind::TypeError(_)));
+}
+
+#[test]
+fn test_runtime_operations_dont_panic() {
+    let op = |op, lhs: SoyValue, rhs: SoyValue| binary_operation(&op, lhs, rhs).unwrap();
+    assert_eq!(
+        op(BinaryOperator::And, 1.into(), SoyValue::Null),
+        false.into()
+    );
+    assert_eq!(
+        op(BinaryOperator::Or, SoyValue::Null, "a".into()),
+        true.into()
+    );
+    assert_eq!(
+        op(BinaryOperator::Elvis, SoyValue::Null, 2.into()),
+        2.into()
+    );
+    assert_eq!(op(BinaryOperator::Elvis, 0.into(), 2.into()), 0.into());
+    // Functions that need render options fail without them
+    let result = eval::call_pure_function(Function::Xid, vec!["a".into()]);
+    assert!(matches!(
+        result,
+        Err(RenderError {
+            kind: RenderErrorKind::TypeError(_),
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_unknown_functions_fail_to_compile() {
+    let errors = Tofu::with_string_template(
+        "{namespace foo}\n/** */\n{template .bar}{frobnicate(1)}{1 |frobnicate}{/template}",
+    )
+    .err()
+    .expect("should fail");
+    let codes: Vec<_> = errors.diagnostics.errors().map(|e| e.kind.code()).collect();
+    assert_eq!(codes, vec!["E0014", "E0015"]);
+}
+
+#[test]
+fn test_css_and_xid() {
+    let tofu = compile(
+        "/**\n * @param base\n */\n\
+         {template .bar cssbase=\"main\"}\n\
+           <div class=\"{css foo-bar} {css %title} {css $base, baz}\" id=\"{xid foo.id}\">\n\
+           {css('foo')} {css($base, 'bar')} {xid('foo')}\n\
+         {/template}",
+    );
+    let render = |css: Option<&RenamingMap>, xid: Option<&RenamingMap>| {
+        let mut renderer = tofu.renderer("foo.bar").param("base", "b");
+        if let Some(css) = css {
+            renderer = renderer.css_renaming_map(css);
+        }
+        if let Some(xid) = xid {
+            renderer = renderer.xid_renaming_map(xid);
+        }
+        renderer.render_to_string().unwrap()
+    };
+    assert_eq!(
+        render(None, None),
+        "<div class=\"foo-bar main-title b-baz\" id=\"foo.id\">foo b-bar foo"
+    );
+
+    let by_part = RenamingMap::parse(
+        "goog.setCssNameMapping({\"foo\": \"a\", \"bar\": \"b\", \"main\": \"c\"}, 'BY_PART');",
+    )
+    .unwrap();
+    assert_eq!(by_part.style(), RenamingStyle::ByPart);
+    let by_whole = RenamingMap::parse(
+        "goog.setCssNameMapping({\"foo-bar\": \"a\", \"main-title\": \"b\"}, \"BY_WHOLE\")",
+    )
+    .unwrap();
+    let xids = RenamingMap::parse("{\"foo.id\": \"x\", \"foo\": \"y\"}").unwrap();
+    assert_eq!(
+        render(Some(&by_part), Some(&xids)),
+        "<div class=\"a-b c-title b-baz\" id=\"x\">a b-b y"
+    );
+    assert_eq!(
+        render(Some(&by_whole), None),
+        "<div class=\"a b b-baz\" id=\"foo.id\">foo b-bar foo"
+    );
+}
+
+#[test]
+fn test_calls() {
+    let tofu = compile(
+        "/**\n * @param name\n * @param? greeting\n */\n\
+         {template .hello}{$greeting ?: 'Hello'}, {$name}!{/template}\n\
+         /**\n * @param name\n * @param user\n */\n\
+         {template .all}{call .hello data=\"all\" /}{/template}\n\
+         /**\n * @param user\n */\n\
+         {template .data}{call foo.hello data=\"$user\"}{param greeting: 'Hi' /}{/call}{/template}\n\
+         /** */\n\
+         {template .params}\n\
+           {call .hello}\n\
+             // Only comments and whitespace go between params\n\
+             {param name kind=\"text\"}<{call .private /}>{/param}\n\
+             {param greeting}<b>Hey</b>{/param}\n\
+           {/call}\n\
+         {/template}\n\
+         /** */\n\
+         {template .private visibility=\"private\" kind=\"text\"}{'<you>'}{/template}",
+    );
+    let render = |renderer: Renderer| {
+        renderer
+            .render_to_string()
+            .unwrap_or_else(|e| panic!("should render: {}", e))
+    };
+    assert_eq!(
+        render(tofu.renderer("foo.all").param("name", "<b>")),
+        "Hello, &lt;b&gt;!"
+    );
+    let user: HashMap<_, _> = vec![("name".to_owned(), SoyValue::from("Ann"))]
+        .into_iter()
+        .collect();
+    assert_eq!(
+        render(tofu.renderer("foo.data").param("user", user)),
+        "Hi, Ann!"
+    );
+    assert_eq!(
+        render(tofu.renderer("foo.data").param("user", SoyValue::Null)),
+        "Hi, null!"
+    );
+    // The text param is escaped as text where it's printed, the HTML one isn't
+    assert_eq!(
+        render(tofu.renderer("foo.params")),
+        "<b>Hey</b>, &lt;&lt;you&gt;&gt;!"
+    );
+    match tofu
+        .renderer("foo.data")
+        .param("user", 1)
+        .render_to_string()
+    {
+        Err(RenderError {
+            kind: RenderErrorKind::TypeError(message),
+            ..
+        }) => assert_eq!(
+            message,
+            "the data passed to foo.hello must be a map, not a int"
+        ),
+        other => panic!("should fail: {:?}", other),
+    }
+}
+
+#[test]
+fn test_calls_to_templates_of_another_kind_are_escaped() {
+    let tofu = compile(
+        "/**\n * @param name\n */\n\
+         {template .name kind=\"text\"}[{$name}]{/template}\n\
+         /** */\n\
+         {template .html}<p>{call .name}{param name: '<Bob>' /}{/call}</p>{/template}\n\
+         /** */\n\
+         {template .bold}<b>{'<Bob>'}</b>{/template}\n\
+         /** */\n\
+         {template .text kind=\"text\"}{call .bold /}{/template}",
+    );
+    assert_eq!(
+        tofu.render_to_string("foo.html").unwrap(),
+        "<p>[&lt;Bob&gt;]</p>"
+    );
+    // Text callers don't escape, and print HTML as it is
+    assert_eq!(
+        tofu.render_to_string("foo.text").unwrap(),
+        "<b>&lt;Bob&gt;</b>"
+    );
+}
+
+#[test]
+fn test_calls_through_aliases() {
+    let tofu = Tofu::builder()
+        .add_string_template("{namespace foo.bar}\n/** */\n{template .baz}baz{/template}")
+        .add_string_template(
+            "{namespace qux}\n{alias foo.bar}\n{alias foo.bar as quux}\n\
+             /** */\n{template .a}{call bar.baz /} {call quux.baz /}{/template}",
+        )
+        .build()
+        .unwrap_or_else(|e| panic!("should compile: {}", e));
+    assert_eq!(tofu.render_to_string("qux.a").unwrap(), "baz baz");
+}
+
+#[test]
+fn test_calls_to_unknown_templates() {
+    let errors = Tofu::builder()
+        .add_string_template(
+            "{namespace foo}\n/** */\n{template .bar}\n  {call .baz /}\n{/template}",
+        )
+        .build()
+        .err()
+        .expect("should fail");
+    let error = errors.diagnostics.errors().next().unwrap();
+    assert_eq!(
+        error.to_string(),
+        "error[E0016]: Call to unknown template: foo.baz\n \
+         --> <input>:4:3 (in foo.bar)\n  |\n4 |   {call .baz /}\n  |   ^^^^^^^^^^"
+    );
+}
+
+#[test]
+fn test_renaming_map_errors() {
+    for input in &[
+        "",
+        "{\"a\": 1}",
+        "{\"a\": \"b\"",
+        "{\"a\": \"b\"}, 'BY_NOTHING'",
+        "goog.setCssNameMapping({}",
+        "{} x",
+    ] {
+        let err = RenamingMap::parse(input).expect_err(input);
+        assert_eq!(err.kind.code(), "E0005", "{}", input);
+    }
+}
+
+/// The ids of the messages in a template, in order.
+fn msg_ids(templates: &str) -> Vec<u64> {
+    let file = parser::parse(&format!("{{namespace foo}}\n{}", templates)).unwrap();
+    let mut ids = vec![];
+    for template in &file.templates {
+        for node in &template.body {
+            if let TemplateNode::Statement(Command::Msg { id, .. }) = node {
+                ids.push(*id);
+            }
+        }
+    }
+    ids
+}
+
+static MESSAGES: &str = "/**\n * @param name\n * @param count\n */\n\
+     {template .bar}\n\
+       {msg desc=\"greeting\"}Hello {$name}, from {$name}!{/msg}\n\
+       {msg desc=\"count\"}{plural $count}{case 1}One {$name}{default}{$count} people{/plural}{/msg}\n\
+       {msg desc=\"untranslated\"}Bye{/msg}\n\
+     {/template}";
+
+#[test]
+fn test_xliff_translations() {
+    let ids = msg_ids(MESSAGES);
+    let xliff = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <xliff version=\"1.2\" xmlns=\"urn:oasis:names:tc:xliff:document:1.2\">\n\
+         <file original=\"SoyMsgBundle\" datatype=\"x-soy-msg\" xml:space=\"preserve\" \
+               source-language=\"en\" target-language=\"fr\">\n\
+         <body>\n\
+         <trans-unit id=\"{}\" datatype=\"html\">\n\
+           <source>Hello <x id=\"NAME\"/>, from <x id=\"NAME\"/>!</source>\n\
+           <target>De <x id=\"NAME\"/> &amp; <![CDATA[<b>]]>salut</target>\n\
+           <note priority=\"1\" from=\"description\">greeting</note>\n\
+         </trans-unit>\n\
+         <trans-unit id=\"{}\" datatype=\"html\">\n\
+           <target>{{COUNT,plural,=1{{Un <x id=\"NAME\"/>}}other{{<x id=\"COUNT\"/> personnes}}}}</target>\n\
+         </trans-unit>\n\
+         </body>\n\
+         </file>\n\
+         </xliff>",
+        ids[0], ids[1]
+    );
+    let bundle = TranslationBundle::from_xliff(&xliff).unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(bundle.locale(), "fr");
+    assert_eq!(bundle.len(), 2);
+    let tofu = Tofu::builder()
+        .add_string_template(&format!("{{namespace foo}}\n{}", MESSAGES))
+        .add_translations(bundle)
+        .build()
+        .unwrap_or_else(|e| panic!("should compile: {}", e));
+    let render = |locale: Option<&str>, count: i64| {
+        let mut renderer = tofu
+            .renderer("foo.bar")
+            .param("name", "Ann")
+            .param("count", count);
+        if let Some(locale) = locale {
+            renderer = renderer.locale(locale);
+        }
+        renderer.render_to_string().unwrap()
+    };
+    assert_eq!(render(None, 1), "Hello Ann, from Ann!One AnnBye");
+    assert_eq!(render(Some("fr"), 1), "De Ann & <b>salutUn AnnBye");
+    assert_eq!(render(Some("fr"), 3), "De Ann & <b>salut3 personnesBye");
+    assert_eq!(render(Some("de"), 3), "Hello Ann, from Ann!3 peopleBye");
+}
+
+#[test]
+fn test_xtb_translations() {
+    let ids = msg_ids(MESSAGES);
+    let xtb = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE translationbundle [<!ELEMENT translationbundle (translation)*>\n\
+           <!ATTLIST translationbundle lang CDATA #REQUIRED>]>\n\
+         <translationbundle lang=\"es\">\n\
+           <translation id=\"{}\" key=\"x\">¡Hola <ph name=\"NAME\" />!</translation>\n\
+           <!-- a comment -->\n\
+           <translation id=\"{}\">Adiós</translation>\n\
+         </translationbundle>",
+        ids[0], ids[2]
+    );
+    let tofu = Tofu::builder()
+        .add_string_template(&format!("{{namespace foo}}\n{}", MESSAGES))
+        .add_translations(TranslationBundle::from_xtb(&xtb).unwrap())
+        .build()
+        .unwrap();
+    assert_eq!(
+        tofu.renderer("foo.bar")
+            .param("name", "Ana")
+            .param("count", 2)
+            .locale("es")
+            .render_to_string()
+            .unwrap(),
+        "¡Hola Ana!2 peopleAdiós"
+    );
+}
+
+#[test]
+fn test_translation_errors() {
+    for input in &[
+        "",
+        "<xliff><file><body/></file></xliff>",
+        "<xliff><file target-language=\"fr\"><trans-unit><target/></trans-unit></file></xliff>",
+        "<xliff><file target-language=\"fr\"><trans-unit id=\"x\"/></file></xliff>",
+        "<xliff><file target-language=\"fr\"></xliff></file>",
+        "<xliff><file target-language=\"fr\"><trans-unit id=\"1\"><target><g/></target></trans-unit></file></xliff>",
+        "<xliff><file target-language=\"fr\"/><file target-language=\"de\"/></xliff>",
+    ] {
+        let err = TranslationBundle::from_xliff(input).expect_err(input);
+        assert_eq!(err.kind.code(), "E0005", "{}", input);
+    }
+    let err = TranslationBundle::from_xtb(
+        "<translationbundle><translation id=\"1\"/></translationbundle>",
+    )
+    .expect_err("missing lang");
+    assert_eq!(err.kind.code(), "E0005");
+
+    let errors = Tofu::builder()
+        .add_translation_file("does/not/exist.xtb")
+        .add_translations(TranslationBundle::new("fr"))
+        .add_translations(TranslationBundle::new("fr"))
+        .build()
+        .err()
+        .expect("should fail");
+    let codes: Vec<_> = errors.diagnostics.errors().map(|e| e.kind.code()).collect();
+    assert_eq!(codes, vec!["E0004"]);
+}
+
+#[test]
+fn test_translated_tag_placeholders() {
+    let templates = "/**\n * @param url\n * @param user\n */\n\
+         {template .bar}\n\
+           {msg desc=\"\"}<a phname=\"userLink\" href=\"{$url}\">{$user.name}</a> said <b>hi</b>{/msg}\n\
+         {/template}";
+    let id = msg_ids(templates)[0];
+    let xtb = format!(
+        "<translationbundle lang=\"de\"><translation id=\"{}\">\
+         <ph name=\"START_BOLD\"/>Hallo<ph name=\"END_BOLD\"/>, sagte \
+         <ph name=\"USER_LINK\"/><ph name=\"NAME\"/><ph name=\"END_LINK\"/>\
+         </translation></translationbundle>",
+        id
+    );
+    let tofu = Tofu::builder()
+        .add_string_template(&format!("{{namespace foo}}\n{}", templates))
+        .add_translations(TranslationBundle::from_xtb(&xtb).unwrap())
+        .build()
+        .unwrap();
+    let mut user = HashMap::new();
+    user.insert("name".to_owned(), SoyValue::from("<Ann>"));
+    let render = |locale| {
+        tofu.renderer("foo.bar")
+            .param("url", "/ann")
+            .param("user", user.clone())
+            .locale(locale)
+            .render_to_string()
+            .unwrap()
+    };
+    assert_eq!(
+        render("en"),
+        "<a href=\"/ann\">&lt;Ann&gt;</a> said <b>hi</b>"
+    );
+    assert_eq!(
+        render("de"),
+        "<b>Hallo</b>, sagte <a href=\"/ann\">&lt;Ann&gt;</a>"
+    );
+}
+
+#[test]
+fn test_translated_call_placeholders() {
+    let templates = "/**\n * @param name\n */\n\
+         {template .bar}\n\
+           {msg desc=\"\"}Hi {call .name data=\"all\" /}, see {call .link phname=\"link\" /}{/msg}\n\
+         {/template}\n\
+         /**\n * @param name\n */\n{template .name}<i>{$name}</i>{/template}\n\
+         /** */\n{template .link}<a href=\"/\">home</a>{/template}";
+    let id = msg_ids(templates)[0];
+    let xtb = format!(
+        "<translationbundle lang=\"de\"><translation id=\"{}\">\
+         <ph name=\"LINK\"/> ansehen, <ph name=\"XXX\"/>\
+         </translation></translationbundle>",
+        id
+    );
+    let tofu = Tofu::builder()
+        .add_string_template(&format!("{{namespace foo}}\n{}", templates))
+        .add_translations(TranslationBundle::from_xtb(&xtb).unwrap())
+        .build()
+        .unwrap();
+    let render = |locale| {
+        tofu.renderer("foo.bar")
+            .param("name", "<Ann>")
+            .locale(locale)
+            .render_to_string()
+            .unwrap()
+    };
+    assert_eq!(
+        render("en"),
+        "Hi <i>&lt;Ann&gt;</i>, see <a href=\"/\">home</a>"
+    );
+    assert_eq!(
+        render("de"),
+        "<a href=\"/\">home</a> ansehen, <i>&lt;Ann&gt;</i>"
+    );
+}
+
+#[test]
+fn test_plural_categories() {
+    let templates = "/**\n * @param n\n */\n\
+         {template .files}\n\
+           {msg desc=\"\"}{plural $n}{case 0}No files{case 1}One file{default}{$n} files{/plural}{/msg}\n\
+         {/template}\n\
+         /**\n * @param n\n */\n\
+         {template .people}\n\
+           {msg desc=\"\"}{plural $n offset=\"1\"}{case 1}Just you{case 'one'}You and a friend\
+           {default}You and {remainder($n)} friends{/plural}{/msg}\n\
+         {/template}";
+    let ids = msg_ids(templates);
+    let xtb = format!(
+        "<translationbundle lang=\"ru\">\n\
+           <translation id=\"{}\">{{N,plural,=0{{Нет файлов}}one{{<ph name=\"N\"/> файл}}\
+             few{{<ph name=\"N\"/> файла}}many{{<ph name=\"N\"/> файлов}}other{{<ph name=\"N\"/> файла}}}}</translation>\n\
+           <translation id=\"{}\">{{N,plural,offset:1 =1{{Только вы}}one{{Вы и <ph name=\"XXX\"/> друг}}\
+             few{{Вы и <ph name=\"XXX\"/> друга}}other{{Вы и <ph name=\"XXX\"/> друзей}}}}</translation>\n\
+         </translationbundle>",
+        ids[0], ids[1]
+    );
+    let tofu = Tofu::builder()
+        .add_string_template(&format!("{{namespace foo}}\n{}", templates))
+        .add_translations(TranslationBundle::from_xtb(&xtb).unwrap())
+        .build()
+        .unwrap_or_else(|e| panic!("{}", e));
+    let render = |template: &str, locale: &'static str, n: SoyValue| {
+        tofu.renderer(template)
+            .param("n", n)
+            .locale(locale)
+            .render_to_string()
+            .unwrap()
+    };
+    let files: Vec<_> = [0, 1, 3, 5, 21, 22]
+        .iter()
+        .map(|&n| render("foo.files", "ru", SoyValue::Int(n)))
+        .collect();
+    assert_eq!(
+        files,
+        vec![
+            "Нет файлов",
+            "1 файл",
+            "3 файла",
+            "5 файлов",
+            "21 файл",
+            "22 файла"
+        ]
+    );
+    assert_eq!(render("foo.files", "ru", SoyValue::Float(1.5)), "1.5 файла");
+    assert_eq!(render("foo.files", "en", SoyValue::Int(2)), "2 files");
+
+    let people: Vec<_> = [1, 2, 3, 6]
+        .iter()
+        .map(|&n| render("foo.people", "en", SoyValue::Int(n)))
+        .collect();
+    assert_eq!(
+        people,
+        vec![
+            "Just you",
+            "You and a friend",
+            "You and 2 friends",
+            "You and 5 friends"
+        ]
+    );
+    let people: Vec<_> = [1, 2, 4, 6]
+        .iter()
+        .map(|&n| render("foo.people", "ru", SoyValue::Int(n)))
+        .collect();
+    assert_eq!(
+        people,
+        vec!["Только вы", "Вы и 1 друг", "Вы и 3 друга", "Вы и 5 друзей"]
+    );
+}
+
+#[test]
+fn test_select_messages() {
+    let templates = "/**\n * @param gender\n * @param n\n * @param name\n */\n\
+         {template .shared}\n\
+           {msg desc=\"\"}\n\
+             {select $gender}\n\
+               {case 'female'}{plural $n}{case 1}{$name} shared her file{default}{$name} shared {$n} files{/plural}\n\
+               {case 'male'}{$name} shared his files\n\
+               {default}{$name} shared their files\n\
+             {/select}\n\
+           {/msg}\n\
+         {/template}";
+    let id = msg_ids(templates)[0];
+    let xliff = format!(
+        "<xliff><file target-language=\"fr\"><trans-unit id=\"{}\"><target>\
+         {{GENDER,select,female{{{{N,plural,=1{{<x id=\"NAME\"/> a partagé son fichier}}\
+         other{{<x id=\"NAME\"/> a partagé <x id=\"N\"/> fichiers}}}}}}\
+         other{{<x id=\"NAME\"/> a partagé ses fichiers}}}}\
+         </target></trans-unit></file></xliff>",
+        id
+    );
+    let tofu = Tofu::builder()
+        .add_string_template(&format!("{{namespace foo}}\n{}", templates))
+        .add_translations(TranslationBundle::from_xliff(&xliff).unwrap())
+        .build()
+        .unwrap_or_else(|e| panic!("{}", e));
+    let render = |locale, gender, n| {
+        tofu.renderer("foo.shared")
+            .param("gender", gender)
+            .param("n", n)
+            .param("name", "Alex")
+            .locale(locale)
+            .render_to_string()
+            .unwrap()
+    };
+    assert_eq!(render("en", "female", 1), "Alex shared her file");
+    assert_eq!(render("en", "female", 3), "Alex shared 3 files");
+    assert_eq!(render("en", "male", 3), "Alex shared his files");
+    assert_eq!(render("en", "other", 3), "Alex shared their files");
+    assert_eq!(render("fr", "female", 1), "Alex a partagé son fichier");
+    assert_eq!(render("fr", "female", 2), "Alex a partagé 2 fichiers");
+    // Cases without a translation use the translation's `other`
+    assert_eq!(render("fr", "male", 2), "Alex a partagé ses fichiers");
+}
+
+#[test]
+fn test_bidi() {
+    let tofu = compile(
+        "/**\n * @param text\n */\n\
+         {template .bar}\n\
+           {bidiGlobalDir()} {bidiStartEdge()} {bidiEndEdge()} {bidiTextDir($text)}\n\
+           <span {bidiDirAttr($text)}>{$text}</span>{bidiMarkAfter($text)}\n\
+           {$text|bidiSpanWrap} {$text|bidiUnicodeWrap}\n\
+         {/template}",
+    );
+    let hebrew = "\u{05E9}\u{05DC}\u{05D5}\u{05DD}";
+    let render = |text: &str, dir: Option<BidiDir>, locale: Option<&str>| {
+        let mut renderer = tofu.renderer("foo.bar").param("text", text);
+        if let Some(dir) = dir {
+            renderer = renderer.bidi_global_dir(dir);
+        }
+        if let Some(locale) = locale {
+            renderer = renderer.locale(locale);
+        }
+        renderer.render_to_string().unwrap()
+    };
+    assert_eq!(
+        render(hebrew, None, None),
+        format!(
+            "1 left right -1<span dir=rtl>{0}</span>\u{200E}\
+             <span dir=\"rtl\">{0}</span>\u{200E} \u{202B}{0}\u{202C}\u{200E}",
+            hebrew
+        )
+    );
+    assert_eq!(
+        render("a&b", Some(BidiDir::Rtl), None),
+        "-1 right left 1<span dir=ltr>a&amp;b</span>\u{200F}\
+         <span dir=\"ltr\">a&amp;b</span>\u{200F} \u{202A}a&amp;b\u{202C}\u{200F}"
+    );
+    // The global direction defaults to that of the locale, unless set explicitly
+    assert!(render(hebrew, None, Some("he")).starts_with("-1 right left -1<span >"));
+    assert!(render(hebrew, Some(BidiDir::Ltr), Some("he")).starts_with("1 left"));
+}
+
+#[test]
+fn test_values_are_escaped_for_the_template_kind() {
+    let tofu = compile(
+        "/**\n * @param x\n */\n{template .js kind=\"js\"}var x = {$x};{/template}\n\
+         /**\n * @param x\n */\n{template .css kind=\"css\"}color: {$x};{/template}\n\
+         /**\n * @param x\n */\n{template .uri kind=\"uri\"}/search?q={$x}{/template}\n\
+         /**\n * @param x\n */\n{template .text kind=\"text\"}{$x}{/template}",
+    );
+    let render = |name: &str, x: SoyValue| {
+        tofu.renderer(name)
+            .param("x", x)
+            .render_to_string()
+            .unwrap()
+    };
+    assert_eq!(
+        render("foo.js", "</script>'\n\\".into()),
+        "var x = '\\x3c\\/script\\x3e\\x27\\n\\\\';"
+    );
+    assert_eq!(render("foo.js", 1.5.into()), "var x =  1.5 ;");
+    assert_eq!(render("foo.js", SoyValue::Null), "var x =  null ;");
+    for safe in &["red", "#fff", "-1.5em", "50%", "!important"] {
+        assert_eq!(
+            render("foo.css", (*safe).into()),
+            format!("color: {};", safe)
+        );
+    }
+    for unsafe_value in &[
+        "red; background: url(x)",
+        "expression(alert(1))",
+        "-moz-binding",
+    ] {
+        assert_eq!(render("foo.css", (*unsafe_value).into()), "color: zSoyz;");
+    }
+    assert_eq!(
+        render("foo.uri", "a b&c=d".into()),
+        "/search?q=a%20b%26c%3Dd"
+    );
+    assert_eq!(render("foo.text", "<b>'".into()), "<b>'");
+}
+
+#[test]
+fn test_sanitized_content() {
+    let tofu = compile(
+        "/**\n * @param content\n */\n\
+         {template .html}{$content} {$content|escapeHtml} {$content + '!'}{/template}\n\
+         /**\n * @param content\n */\n\
+         {template .attributes kind=\"attributes\"}{$content}{/template}\n\
+         /**\n * @param content\n */\n\
+         {template .text kind=\"text\"}{$content}{/template}\n\
+         /**\n * @param content\n */\n\
+         {template .equals kind=\"text\"}{$content == '<b>hi</b>'}{/template}",
+    );
+    let html = unsafe { SoyValue::trusted_html("<b>hi</b>") };
+    let attributes = unsafe { SoyValue::trusted_attributes("dir=\"rtl\"") };
+    let render = |name: &str, content: &SoyValue| {
+        tofu.renderer(name)
+            .param("content", content.clone())
+            .render_to_string()
+            .unwrap()
+    };
+    assert_eq!(
+        render("foo.html", &html),
+        "<b>hi</b> <b>hi</b> &lt;b&gt;hi&lt;/b&gt;!"
+    );
+    assert_eq!(
+        render("foo.html", &"<b>hi</b>".into()),
+        "&lt;b&gt;hi&lt;/b&gt; &lt;b&gt;hi&lt;/b&gt; &lt;b&gt;hi&lt;/b&gt;!"
+    );
+    // Content is only trusted in a context of its own kind
+    assert_eq!(
+        render("foo.html", &attributes),
+        "dir=&quot;rtl&quot; dir=&quot;rtl&quot; dir=&quot;rtl&quot;!"
+    );
+    assert_eq!(render("foo.attributes", &attributes), "dir=\"rtl\"");
+    assert_eq!(render("foo.attributes", &html), "&lt;b&gt;hi&lt;/b&gt;");
+    assert_eq!(render("foo.text", &html), "<b>hi</b>");
+    assert_eq!(render("foo.equals", &html), "true");
+}
+
+#[test]
+fn test_sanitized_content_of_other_kinds() {
+    let tofu = compile(
+        "/**\n * @param x\n */\n{template .js kind=\"js\"}f({$x});{/template}\n\
+         /**\n * @param x\n */\n{template .css kind=\"css\"}a {lb}{$x}{rb}{/template}\n\
+         /**\n * @param x\n */\n{template .uri kind=\"uri\"}{$x}{/template}\n\
+         /**\n * @param x\n */\n\
+         {template .resource kind=\"trusted_resource_uri\"}{$x}{/template}",
+    );
+    let render = |name: &str, trusted: Option<unsafe fn(String) -> SoyValue>, x: &str| {
+        let x = match trusted {
+            Some(trusted) => unsafe { trusted(x.to_owned()) },
+            None => SoyValue::from(x),
+        };
+        tofu.renderer(name)
+            .param("x", x)
+            .render_to_string()
+            .unwrap()
+    };
+    let js: Option<unsafe fn(String) -> SoyValue> = Some(SoyValue::trusted_js);
+    assert_eq!(render("foo.js", js, "a.b, 'c'"), "f(a.b, 'c');");
+    assert_eq!(render("foo.js", None, "a.b"), "f('a.b');");
+    let css: Option<unsafe fn(String) -> SoyValue> = Some(SoyValue::trusted_css);
+    assert_eq!(render("foo.css", css, "color: red"), "a {color: red}");
+    assert_eq!(render("foo.css", None, "color: red"), "a {zSoyz}");
+    let url = "https://example.com/a?b=c";
+    let encoded = "https%3A%2F%2Fexample.com%2Fa%3Fb%3Dc";
+    let uri: Option<unsafe fn(String) -> SoyValue> = Some(SoyValue::trusted_uri);
+    let resource: Option<unsafe fn(String) -> SoyValue> = Some(SoyValue::trusted_resource_uri);
+    assert_eq!(render("foo.uri", uri, url), url);
+    assert_eq!(render("foo.uri", resource, url), url);
+    assert_eq!(render("foo.uri", None, url), encoded);
+    assert_eq!(render("foo.resource", resource, url), url);
+    // Any URI isn't a trusted resource URI
+    assert_eq!(render("foo.resource", uri, url), encoded);
+    // Nor is content trusted in a context of a different kind
+    assert_eq!(render("foo.uri", js, url), encoded);
+}
+
+#[test]
+fn test_template_kinds_apply_to_calls() {
+    let tofu = compile(
+        "/** */\n{template .query kind=\"text\"}a b&c=d{/template}\n\
+         /** */\n{template .link kind=\"uri\"}/search?q={call .query /}{/template}\n\
+         /** */\n\
+         {template .resource kind=\"trusted_resource_uri\"}https://example.com/a.js{/template}\n\
+         /** */\n{template .src kind=\"uri\"}{call .resource /}{/template}\n\
+         /** */\n{template .bold}<b>hi</b>{/template}\n\
+         /** */\n{template .js kind=\"js\"}f({call .bold /});{/template}\n\
+         /** */\n{template .title kind=\"attributes\"}title=\"{call .bold /}\"{/template}\n\
+         /** */\n{template .html}<p>{call .bold /}</p>{/template}",
+    );
+    let render = |name: &str| tofu.render_to_string(name).unwrap();
+    assert_eq!(render("foo.link"), "/search?q=a%20b%26c%3Dd");
+    // Trusted resource URIs are also URIs
+    assert_eq!(render("foo.src"), "https://example.com/a.js");
+    assert_eq!(render("foo.js"), "f('\\x3cb\\x3ehi\\x3c\\/b\\x3e');");
+    assert_eq!(render("foo.title"), "title=\"&lt;b&gt;hi&lt;/b&gt;\"");
+    assert_eq!(render("foo.html"), "<p><b>hi</b></p>");
+}
+
+#[test]
+fn test_compile_time_globals() {
+    let tofu = Tofu::builder()
+        .add_string_template(
+            "{namespace foo}\n/** */\n\
+             {template .bar}{if DEBUG}debug {/if}{VERSION + 1}{/template}",
+        )
+        .compile_time_global("DEBUG", true)
+        .compile_time_global("VERSION", 2)
+        .build()
+        .unwrap_or_else(|e| panic!("should compile: {}", e));
+    assert_eq!(tofu.render_to_string("foo.bar").unwrap(), "debug 3");
+    let tofu = compile("/** */\n{template .bar}{UNSET}{/template}");
+    let error = tofu.render_to_string("foo.bar").expect_err("should fail");
+    assert_eq!(error.to_string(), "error: Unknown global: UNSET");
+}
+
+/// A new, empty directory for a test's files.
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("soy-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_recompile_reads_changed_files() {
+    let dir = test_dir("recompile");
+    let (a, b) = (dir.join("a.soy"), dir.join("b.soy"));
+    fs::write(&a, "{namespace a}\n/** */\n{template .t}a1{/template}").unwrap();
+    fs::write(&b, "{namespace b}\n/** */\n{template .t}b1{/template}").unwrap();
+    let mut builder = Tofu::builder().add_file(&a).add_file(&b);
+    let tofu = builder.recompile::<&Path>(&[]).unwrap();
+    assert_eq!(tofu.render_to_string("a.t").unwrap(), "a1");
+
+    // Only the file said to have changed is read again
+    fs::write(&a, "{namespace a}\n/** */\n{template .t}a2{/template}").unwrap();
+    fs::write(&b, "{namespace b}\n/** */\n{template .t}b2{/template}").unwrap();
+    let tofu = builder.recompile(&[&a]).unwrap();
+    assert_eq!(tofu.render_to_string("a.t").unwrap(), "a2");
+    assert_eq!(tofu.render_to_string("b.t").unwrap(), "b1");
+
+    // Errors are reported until they're fixed
+    fs::write(
+        &a,
+        "{namespace a}\n/** */\n{template .t}{if}{/if}{/template}",
+    )
+    .unwrap();
+    let errors = builder.recompile(&[&a]).err().expect("should fail");
+    assert_eq!(errors.diagnostics.errors().count(), 1);
+    assert!(builder.recompile::<&Path>(&[]).is_err());
+    fs::write(&a, "{namespace a}\n/** */\n{template .t}a3{/template}").unwrap();
+    let tofu = builder.recompile(&[&a, &b]).unwrap();
+    assert_eq!(tofu.render_to_string("a.t").unwrap(), "a3");
+    assert_eq!(tofu.render_to_string("b.t").unwrap(), "b2");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_recompile_rechecks_dependents() {
+    let dir = test_dir("dependents");
+    let (a, b, c) = (dir.join("a.soy"), dir.join("b.soy"), dir.join("c.soy"));
+    fs::write(&a, "{namespace a}\n/** */\n{template .t}a{/template}").unwrap();
+    fs::write(
+        &b,
+        "{namespace b}\n{alias a}\n/** */\n{template .t}b1{/template}",
+    )
+    .unwrap();
+    fs::write(&c, "{namespace c}\n/** */\n{template .t}c1{/template}").unwrap();
+    let mut builder = Tofu::builder().add_file(&a).add_file(&b).add_file(&c);
+    builder.recompile::<&Path>(&[]).unwrap();
+
+    // b aliases a, so is checked again when a changes, but c isn't
+    fs::write(
+        &b,
+        "{namespace b}\n{alias a}\n/** */\n{template .t}b2{/template}",
+    )
+    .unwrap();
+    fs::write(&c, "{namespace c}\n/** */\n{template .t}c2{/template}").unwrap();
+    let tofu = builder.recompile(&[&a]).unwrap();
+    assert_eq!(tofu.render_to_string("b.t").unwrap(), "b2");
+    assert_eq!(tofu.render_to_string("c.t").unwrap(), "c1");
+
+    // Duplicates are found across changed and unchanged files
+    fs::write(&a, "{namespace c}\n/** */\n{template .t}a{/template}").unwrap();
+    let errors = builder.recompile(&[&a]).err().expect("should fail");
+    let codes: Vec<_> = errors.diagnostics.errors().map(|e| e.kind.code()).collect();
+    assert_eq!(codes, vec!["E0003"]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_recompile_rechecks_callers() {
+    let dir = test_dir("callers");
+    let (a, b, c) = (dir.join("a.soy"), dir.join("b.soy"), dir.join("c.soy"));
+    fs::write(&a, "{namespace a}\n/** */\n{template .t}a1{/template}").unwrap();
+    fs::write(
+        &b,
+        "{namespace b}\n/** */\n{template .t}b1 {call a.t /}{/template}",
+    )
+    .unwrap();
+    fs::write(&c, "{namespace c}\n/** */\n{template .t}c1{/template}").unwrap();
+    let mut builder = Tofu::builder().add_file(&a).add_file(&b).add_file(&c);
+    let tofu = builder.recompile::<&Path>(&[]).unwrap();
+    assert_eq!(tofu.render_to_string("b.t").unwrap(), "b1 a1");
+
+    // b calls a template in a, so is checked again when a changes, but c isn't
+    fs::write(&a, "{namespace a}\n/** */\n{template .t}a2{/template}").unwrap();
+    fs::write(
+        &b,
+        "{namespace b}\n/** */\n{template .t}b2 {call a.t /}{/template}",
+    )
+    .unwrap();
+    fs::write(&c, "{namespace c}\n/** */\n{template .t}c2{/template}").unwrap();
+    let tofu = builder.recompile(&[&a]).unwrap();
+    assert_eq!(tofu.render_to_string("b.t").unwrap(), "b2 a2");
+    assert_eq!(tofu.render_to_string("c.t").unwrap(), "c1");
+
+    // Calls from unchanged files to templates that are gone, or have become private,
+    // are errors
+    fs::write(&a, "{namespace a}\n/** */\n{template .u}a{/template}").unwrap();
+    let errors = builder.recompile(&[&a]).err().expect("should fail");
+    let codes: Vec<_> = errors.diagnostics.errors().map(|e| e.kind.code()).collect();
+    assert_eq!(codes, vec!["E0016"]);
+    fs::write(
+        &a,
+        "{namespace a}\n/** */\n{template .t visibility=\"private\"}a{/template}",
+    )
+    .unwrap();
+    let errors = builder.recompile(&[&a]).err().expect("should fail");
+    let codes: Vec<_> = errors.diagnostics.errors().map(|e| e.kind.code()).collect();
+    assert_eq!(codes, vec!["E0017"]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_reloading() {
+    let dir = test_dir("reloading");
+    let a = dir.join("a.soy");
+    fs::write(&a, "{namespace a}\n/** */\n{template .t}one{/template}").unwrap();
+    let tofu = ReloadingTofu::new(Tofu::builder(), &[&dir])
+        .unwrap_or_else(|e| panic!("should compile: {}", e))
+        .check_interval(std::time::Duration::from_secs(3600));
+    let before = tofu.snapshot();
+    assert!(!tofu.reload().unwrap());
+
+    // New and changed files are compiled, without changing snapshots already taken
+    fs::write(&a, "{namespace a}\n/** */\n{template .t}two!{/template}").unwrap();
+    fs::write(
+        dir.join("b.soy"),
+        "{namespace b}\n/** */\n{template .t}b{/template}",
+    )
+    .unwrap();
+    assert!(tofu.reload().unwrap());
+    assert_eq!(before.render_to_string("a.t").unwrap(), "one");
+    let after = tofu.snapshot();
+    assert_eq!(after.render_to_string("a.t").unwrap(), "two!");
+    assert_eq!(after.render_to_string("b.t").unwrap(), "b");
+
+    // The old templates are kept until the errors are fixed
+    fs::write(
+        &a,
+        "{namespace a}\n/** */\n{template .t}{if}{/if}{/template}",
+    )
+    .unwrap();
+    let errors = tofu.reload().expect_err("should fail");
+    assert_eq!(errors.diagnostics.errors().count(), 1);
+    assert!(tofu.reload().is_err());
+    assert!(tofu.error().is_some());
+    assert_eq!(tofu.snapshot().render_to_string("a.t").unwrap(), "two!");
+    fs::write(&a, "{namespace a}\n/** */\n{template .t}three{/template}").unwrap();
+    fs::remove_file(dir.join("b.soy")).unwrap();
+    assert!(tofu.reload().unwrap());
+    assert!(tofu.error().is_none());
+    let fixed = tofu.snapshot();
+    assert_eq!(fixed.render_to_string("a.t").unwrap(), "three");
+    assert!(fixed.render_to_string("b.t").is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_tofu_is_shared_between_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Tofu>();
+    assert_send_sync::<ReloadingTofu>();
+    assert_send_sync::<CompileErrors>();
+    assert_send_sync::<RenderError>();
+
+    let tofu = compile("/**\n * @param n\n */\n{template .bar}{$n * 2}{/template}");
+    let threads: Vec<_> = (0..4)
+        .map(|n| {
+            let tofu = tofu.clone();
+            std::thread::spawn(move || tofu.renderer("foo.bar").param("n", n).render_to_string())
+        })
+        .collect();
+    let output: Vec<_> = threads
+        .into_iter()
+        .map(|thread| thread.join().unwrap().unwrap())
+        .collect();
+    assert_eq!(output, vec!["0", "2", "4", "6"]);
+    // Clones share the templates
+    assert!(Arc::ptr_eq(&tofu.templates, &tofu.clone().templates));
+}
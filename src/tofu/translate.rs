@@ -0,0 +1,116 @@
+use super::{Frame, Renderer};
+use crate::error::{RenderError, RenderErrorKind};
+use crate::ir::Msg;
+use crate::msg::{MsgPart, PluralCaseSpec};
+use crate::plural;
+use crate::value::SoyValue;
+use std::io::Write;
+
+// Message rendering
+impl<'a> Renderer<'a> {
+    /// Renders a message, using its translation into the render's locale if there is one.
+    pub(super) fn render_msg<W: Write>(
+        &self,
+        writer: &mut W,
+        frame: &Frame,
+        msg: &Msg,
+    ) -> Result<(), RenderError> {
+        let translation = self.locale.and_then(|locale| {
+            let bundle = self.tofu.translations.get(locale)?;
+            Some((locale, bundle.message(msg.id)?))
+        });
+        // Untranslated messages are assumed to be in English
+        let (locale, parts) = translation.unwrap_or(("en", &msg.parts));
+        let context = MsgContext { frame, msg, locale };
+        self.render_parts(writer, &context, parts)
+    }
+
+    fn render_parts<W: Write>(
+        &self,
+        writer: &mut W,
+        context: &MsgContext,
+        parts: &[MsgPart],
+    ) -> Result<(), RenderError> {
+        for part in parts {
+            match part {
+                MsgPart::RawText(text) => writer.write_all(text.as_bytes())?,
+                MsgPart::Placeholder(name) => {
+                    let block = context.msg.placeholder(name).ok_or_else(|| RenderError {
+                        kind: RenderErrorKind::UnknownPlaceholder(name.clone()),
+                        location: None,
+                    })?;
+                    self.render_block(writer, context.frame, block)?;
+                }
+                MsgPart::Plural { var, offset, cases } => {
+                    let value = self.evaluate_variable(context, var)?;
+                    if let Some(parts) = plural_case(context.locale, &value, *offset, cases) {
+                        self.render_parts(writer, context, parts)?;
+                    }
+                }
+                MsgPart::Select { var, cases } => {
+                    let value = self.evaluate_variable(context, var)?.to_string();
+                    let case = cases
+                        .iter()
+                        .find(|(v, _)| *v == value)
+                        .or_else(|| cases.iter().find(|(v, _)| v == "other"));
+                    if let Some((_, parts)) = case {
+                        self.render_parts(writer, context, parts)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn evaluate_variable(&self, context: &MsgContext, var: &str) -> Result<SoyValue, RenderError> {
+        let expression = context.msg.variable(var).ok_or_else(|| RenderError {
+            kind: RenderErrorKind::UnknownPlaceholder(var.to_owned()),
+            location: None,
+        })?;
+        self.evaluate(context.frame, expression)
+    }
+}
+
+/// What the parts of a message are rendered with.
+struct MsgContext<'a> {
+    frame: &'a Frame<'a>,
+    msg: &'a Msg,
+    /// The locale whose plural rules are used.
+    locale: &'a str,
+}
+
+/// Chooses a plural case: an explicit case equal to the value if there is one, otherwise
+/// the case for the plural category of the value minus the offset, or `other`.
+fn plural_case<'p>(
+    locale: &str,
+    value: &SoyValue,
+    offset: i64,
+    cases: &'p [(PluralCaseSpec, Vec<MsgPart>)],
+) -> Option<&'p [MsgPart]> {
+    let number = value.as_number();
+    let explicit = cases.iter().find(|(spec, _)| match spec {
+        PluralCaseSpec::Explicit(n) => number == Some(*n as f64),
+        PluralCaseSpec::Category(_) => false,
+    });
+    let category = plural_category(locale, value, offset);
+    let find_category = |name: &str| {
+        cases.iter().find(|(spec, _)| match spec {
+            PluralCaseSpec::Category(c) => c == name,
+            PluralCaseSpec::Explicit(_) => false,
+        })
+    };
+    explicit
+        .or_else(|| find_category(category))
+        .or_else(|| find_category("other"))
+        .map(|(_, parts)| parts.as_slice())
+}
+
+/// The name of the plural category of a value minus an offset.
+pub(crate) fn plural_category(locale: &str, value: &SoyValue, offset: i64) -> &'static str {
+    let offset_value = match value {
+        SoyValue::Int(i) => SoyValue::Int(i.saturating_sub(offset)),
+        SoyValue::Float(f) => SoyValue::Float(f - offset as f64),
+        _ => SoyValue::Null,
+    };
+    plural::category(locale, &offset_value).name()
+}
@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::error::{CompileError, CompileErrorKind};
+use crate::msg::{MsgPart, PluralCaseSpec};
+use crate::plural::PluralCategory;
+use crate::xml::{self, Element, Node};
+
+/// The translations of a set of messages into one locale, keyed by message id.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TranslationBundle {
+    locale: String,
+    messages: HashMap<u64, Vec<MsgPart>>,
+}
+
+impl TranslationBundle {
+    pub fn new(locale: &str) -> Self {
+        TranslationBundle {
+            locale: locale.to_owned(),
+            messages: HashMap::new(),
+        }
+    }
+
+    /// Reads an XLIFF 1.2 file, as used by Closure Templates, where each `<trans-unit>`
+    /// has the message id and a `<target>` translation with `<x id="NAME"/>` placeholders.
+    pub fn from_xliff(input: &str) -> Result<Self, CompileError> {
+        let root = xml::parse(input)?;
+        let mut files = vec![];
+        root.find_all("file", &mut files);
+        let mut bundle = TranslationBundle::default();
+        for file in files {
+            let locale = file
+                .attribute("target-language")
+                .ok_or_else(|| missing("<file target-language>"))?;
+            bundle.set_locale(locale)?;
+            let mut units = vec![];
+            file.find_all("trans-unit", &mut units);
+            for unit in units {
+                let id = parse_id(unit)?;
+                if let Some(target) = unit.child_elements().find(|e| e.name == "target") {
+                    bundle.messages.insert(id, parse_parts(target, "x", "id")?);
+                }
+            }
+        }
+        Ok(bundle)
+    }
+
+    /// Reads an XTB file, where each `<translation>` has the message id and
+    /// `<ph name="NAME"/>` placeholders.
+    pub fn from_xtb(input: &str) -> Result<Self, CompileError> {
+        let root = xml::parse(input)?;
+        if root.name != "translationbundle" {
+            return Err(xml::malformed(format!(
+                "expected <translationbundle>, found <{}>",
+                root.name
+            )));
+        }
+        let mut bundle = TranslationBundle::new(
+            root.attribute("lang")
+                .ok_or_else(|| missing("<translationbundle lang>"))?,
+        );
+        for translation in root.child_elements().filter(|e| e.name == "translation") {
+            let id = parse_id(translation)?;
+            bundle
+                .messages
+                .insert(id, parse_parts(translation, "ph", "name")?);
+        }
+        Ok(bundle)
+    }
+
+    /// Reads a translation file, choosing the format by its extension (`.xlf`, `.xliff`
+    /// or `.xtb`).
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, CompileError> {
+        let path = path.as_ref();
+        let filename = path.to_string_lossy().into_owned();
+        let content = fs::read_to_string(path).map_err(|e| CompileError {
+            kind: CompileErrorKind::Io(filename.clone(), e),
+            location: None,
+            cause: None,
+        })?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("xlf") | Some("xliff") => Self::from_xliff(&content),
+            Some("xtb") => Self::from_xtb(&content),
+            _ => Err(xml::malformed(format!(
+                "unknown translation file format: {}",
+                filename
+            ))),
+        }
+    }
+
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Adds the messages from another bundle for the same locale, replacing any
+    /// translations of the same messages.
+    pub fn merge(&mut self, other: TranslationBundle) -> Result<(), CompileError> {
+        self.set_locale(&other.locale)?;
+        self.messages.extend(other.messages);
+        Ok(())
+    }
+
+    pub(crate) fn message(&self, id: u64) -> Option<&[MsgPart]> {
+        self.messages.get(&id).map(Vec::as_slice)
+    }
+
+    fn set_locale(&mut self, locale: &str) -> Result<(), CompileError> {
+        if self.locale.is_empty() {
+            self.locale = locale.to_owned();
+        } else if self.locale != locale {
+            return Err(xml::malformed(format!(
+                "translations for {} and {} can't be combined",
+                self.locale, locale
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn missing(what: &str) -> CompileError {
+    xml::malformed(format!("translation file is missing {}", what))
+}
+
+fn parse_id(element: &Element) -> Result<u64, CompileError> {
+    let id = element
+        .attribute("id")
+        .ok_or_else(|| missing(&format!("<{} id>", element.name)))?;
+    id.parse()
+        .map_err(|_| xml::malformed(format!("invalid message id: {}", id)))
+}
+
+/// A token of a translation's content: plural syntax is written in the text, around
+/// the placeholder elements.
+enum Token {
+    Char(char),
+    Placeholder(String),
+}
+
+fn parse_parts(
+    element: &Element,
+    placeholder_element: &str,
+    name_attribute: &str,
+) -> Result<Vec<MsgPart>, CompileError> {
+    let mut tokens = vec![];
+    for child in &element.children {
+        match child {
+            Node::Text(text) => tokens.extend(text.chars().map(Token::Char)),
+            Node::Element(e) if e.name == placeholder_element => {
+                let name = e
+                    .attribute(name_attribute)
+                    .ok_or_else(|| missing(&format!("<{} {}>", e.name, name_attribute)))?;
+                tokens.push(Token::Placeholder(name.to_owned()));
+            }
+            Node::Element(e) => {
+                return Err(xml::malformed(format!(
+                    "unexpected <{}> in translation",
+                    e.name
+                )))
+            }
+        }
+    }
+    let mut position = 0;
+    let parts = parse_tokens(&tokens, &mut position, false)?;
+    Ok(parts)
+}
+
+fn parse_tokens(
+    tokens: &[Token],
+    position: &mut usize,
+    in_case: bool,
+) -> Result<Vec<MsgPart>, CompileError> {
+    let mut parts = vec![];
+    while let Some(token) = tokens.get(*position) {
+        match token {
+            Token::Char('}') if in_case => return Ok(parts),
+            Token::Char('{') if choice_header(tokens, *position).is_some() => {
+                parts.push(parse_choice(tokens, position)?);
+                continue;
+            }
+            Token::Char(c) => match parts.last_mut() {
+                Some(MsgPart::RawText(text)) => text.push(*c),
+                _ => parts.push(MsgPart::RawText(c.to_string())),
+            },
+            Token::Placeholder(name) => parts.push(MsgPart::Placeholder(name.clone())),
+        }
+        *position += 1;
+    }
+    if in_case {
+        return Err(xml::malformed(
+            "unterminated plural or select case".to_owned(),
+        ));
+    }
+    Ok(parts)
+}
+
+/// If the tokens at `position` start a plural or select (`{NAME,plural,` or
+/// `{NAME,select,`), returns the variable name, whether it is a plural, and the position
+/// after the header.
+fn choice_header(tokens: &[Token], position: usize) -> Option<(String, bool, usize)> {
+    let text: String = tokens[position + 1..]
+        .iter()
+        .map_while(|token| match token {
+            Token::Char(c) => Some(*c),
+            Token::Placeholder(_) => None,
+        })
+        .take(100)
+        .collect();
+    let first_comma = text.find(',')?;
+    let second_comma = first_comma + 1 + text[first_comma + 1..].find(',')?;
+    let var = text[..first_comma].trim();
+    let valid = !var.is_empty() && var.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    let is_plural = match text[first_comma + 1..second_comma].trim() {
+        "plural" => true,
+        "select" => false,
+        _ => return None,
+    };
+    if valid {
+        Some((var.to_owned(), is_plural, position + 1 + second_comma + 1))
+    } else {
+        None
+    }
+}
+
+fn parse_choice(tokens: &[Token], position: &mut usize) -> Result<MsgPart, CompileError> {
+    let (var, is_plural, start) = choice_header(tokens, *position)
+        .ok_or_else(|| xml::malformed("invalid plural or select".to_owned()))?;
+    *position = start;
+    let mut offset = 0;
+    let mut plural_cases = vec![];
+    let mut select_cases = vec![];
+    loop {
+        let mut spec = String::new();
+        loop {
+            match tokens.get(*position) {
+                Some(Token::Char('{')) => break,
+                Some(Token::Char('}')) if spec.trim().is_empty() => {
+                    *position += 1;
+                    return Ok(if is_plural {
+                        MsgPart::Plural {
+                            var,
+                            offset,
+                            cases: plural_cases,
+                        }
+                    } else {
+                        MsgPart::Select {
+                            var,
+                            cases: select_cases,
+                        }
+                    });
+                }
+                Some(Token::Char(c)) => spec.push(*c),
+                _ => return Err(xml::malformed("invalid plural or select case".to_owned())),
+            }
+            *position += 1;
+        }
+        *position += 1;
+        let mut spec = spec.trim();
+        if !is_plural {
+            let parts = parse_tokens(tokens, position, true)?;
+            *position += 1;
+            select_cases.push((spec.to_owned(), parts));
+            continue;
+        }
+        if let Some(rest) = spec.strip_prefix("offset:") {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            offset = rest[..end]
+                .parse()
+                .map_err(|_| xml::malformed(format!("invalid plural offset: {}", spec)))?;
+            spec = rest[end..].trim();
+        }
+        let spec = match spec.strip_prefix('=') {
+            Some(n) => PluralCaseSpec::Explicit(
+                n.parse()
+                    .map_err(|_| xml::malformed(format!("invalid plural case: {}", spec)))?,
+            ),
+            None if PluralCategory::from_name(spec).is_some() => {
+                PluralCaseSpec::Category(spec.to_owned())
+            }
+            None => return Err(xml::malformed(format!("invalid plural case: {}", spec))),
+        };
+        let parts = parse_tokens(tokens, position, true)?;
+        // Skips the closing brace of the case
+        *position += 1;
+        plural_cases.push((spec, parts));
+    }
+}
@@ -0,0 +1,167 @@
+use pest::error::LineColLocation;
+use pest::iterators::Pair;
+use pest::Parser;
+use pest_derive::Parser;
+
+use crate::error::{CompileError, CompileErrorKind};
+
+#[derive(Parser)]
+#[grammar = "xml.pest"]
+struct XmlParser;
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Node {
+    Element(Element),
+    Text(String),
+}
+
+impl Element {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn child_elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|child| match child {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    /// Finds every descendant element with the given name (not looking inside matches).
+    pub fn find_all<'a>(&'a self, name: &str, found: &mut Vec<&'a Element>) {
+        for child in self.child_elements() {
+            if child.name == name {
+                found.push(child);
+            } else {
+                child.find_all(name, found);
+            }
+        }
+    }
+}
+
+pub(crate) fn malformed(message: String) -> CompileError {
+    CompileError {
+        kind: CompileErrorKind::Malformed(message),
+        location: None,
+        cause: None,
+    }
+}
+
+/// Parses an XML document into its root element.
+pub(crate) fn parse(input: &str) -> Result<Element, CompileError> {
+    let document = XmlParser::parse(Rule::document, input).map_err(|e| {
+        let (line, column) = match e.line_col {
+            LineColLocation::Pos(pos) | LineColLocation::Span(pos, _) => pos,
+        };
+        malformed(format!("invalid XML at {}:{}", line, column))
+    })?;
+    for pair in document.flatten() {
+        if pair.as_rule() == Rule::element {
+            return parse_element(pair);
+        }
+    }
+    Err(malformed("XML document has no root element".to_owned()))
+}
+
+fn parse_element(pair: Pair<Rule>) -> Result<Element, CompileError> {
+    let mut element = Element {
+        name: String::new(),
+        attributes: vec![],
+        children: vec![],
+    };
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::empty_element | Rule::start_tag => {
+                for p in p.into_inner() {
+                    match p.as_rule() {
+                        Rule::tag_name => element.name = p.as_str().to_owned(),
+                        Rule::attribute => {
+                            let mut attribute = p.into_inner();
+                            let name = attribute.next().map(|p| p.as_str().to_owned());
+                            let value = attribute
+                                .next()
+                                .and_then(|p| p.into_inner().next())
+                                .map(|p| decode_entities(p.as_str()));
+                            if let (Some(name), Some(value)) = (name, value) {
+                                element.attributes.push((name, value?));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Rule::content => {
+                for p in p.into_inner() {
+                    match p.as_rule() {
+                        Rule::element => element.children.push(Node::Element(parse_element(p)?)),
+                        Rule::text => element
+                            .children
+                            .push(Node::Text(decode_entities(p.as_str())?)),
+                        Rule::cdata => element
+                            .children
+                            .push(Node::Text(p.into_inner().as_str().to_owned())),
+                        _ => {}
+                    }
+                }
+            }
+            Rule::end_tag => {
+                let name = p.into_inner().as_str();
+                if name != element.name {
+                    return Err(malformed(format!(
+                        "<{}> is closed by </{}>",
+                        element.name, name
+                    )));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(element)
+}
+
+fn decode_entities(text: &str) -> Result<String, CompileError> {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find(';')
+            .ok_or_else(|| malformed(format!("unterminated entity in {:?}", text)))?;
+        let entity = &rest[start + 1..start + end];
+        decoded.push(match entity {
+            "lt" => '<',
+            "gt" => '>',
+            "amp" => '&',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(std::char::from_u32)
+                .ok_or_else(|| malformed(format!("unknown entity &{};", entity)))?,
+        });
+        rest = &rest[start + end + 1..];
+    }
+    decoded.push_str(rest);
+    Ok(decoded)
+}
+
+/// Escapes text for XML content or attribute values.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}